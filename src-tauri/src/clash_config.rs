@@ -2,10 +2,18 @@
 //! Optimized for mihomo (Clash Meta) kernel
 
 use indexmap::IndexMap;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 
-use crate::ini_parser::{to_clash_proxy_groups, to_clash_rules, ParsedIniConfig};
-use crate::node::Node;
+use crate::filter::sort_by_latency;
+use crate::ini_parser::{
+    to_clash_proxy_groups, to_clash_rules, ParsedIniConfig, ProxyMatcher, BUILTIN_POLICIES,
+};
+use crate::node::{Node, SmuxOpts};
+use crate::region::RegionTable;
+use crate::singbox::{build_singbox_config, SingBoxOutput};
 
 /// Complete Clash configuration (mihomo compatible)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +30,14 @@ pub struct ClashConfig {
     #[serde(rename = "bind-address", skip_serializing_if = "Option::is_none")]
     pub bind_address: Option<String>,
 
+    /// CIDRs allowed to connect when LAN access is on
+    #[serde(rename = "lan-allowed-ips", skip_serializing_if = "Option::is_none")]
+    pub lan_allowed_ips: Option<Vec<String>>,
+
+    /// CIDRs refused even when allowed by `lan-allowed-ips`
+    #[serde(rename = "lan-disallowed-ips", skip_serializing_if = "Option::is_none")]
+    pub lan_disallowed_ips: Option<Vec<String>>,
+
     /// Proxy mode: rule, global, direct
     pub mode: String,
 
@@ -134,12 +150,25 @@ pub struct RuleProvider {
 #[derive(Debug, Clone, Default)]
 pub struct RuleProviderOptions {
     pub proxy: Option<String>,
+    /// Per-behavior proxy (`domain` / `ipcidr` / `classical` -> group), overrides `proxy`
+    pub proxy_by_behavior: IndexMap<String, String>,
     pub header: Option<IndexMap<String, String>>,
     pub size_limit: Option<u32>,
     pub path_omit: bool,
     pub path_template: Option<String>,
 }
 
+/// Categories of default rules that can be omitted (e.g. when layering under another config)
+#[derive(Debug, Clone, Default)]
+pub struct DefaultRuleOptions {
+    /// Omit `GEOIP,CN` direct rule
+    pub skip_cn_direct: bool,
+    /// Omit local domain and private/LAN IP-CIDR direct rules
+    pub skip_private_direct: bool,
+    /// Omit the final `MATCH` rule
+    pub skip_match: bool,
+}
+
 /// Profile configuration for storing state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProfileConfig {
@@ -192,6 +221,13 @@ pub struct DnsConfig {
     pub ipv6: bool,
     #[serde(rename = "prefer-h3")]
     pub prefer_h3: bool,
+    /// Route DNS queries through the rules (needs `proxy-server-nameserver`)
+    #[serde(rename = "respect-rules", skip_serializing_if = "Option::is_none")]
+    pub respect_rules: Option<bool>,
+    #[serde(rename = "use-hosts", skip_serializing_if = "Option::is_none")]
+    pub use_hosts: Option<bool>,
+    #[serde(rename = "use-system-hosts", skip_serializing_if = "Option::is_none")]
+    pub use_system_hosts: Option<bool>,
     #[serde(rename = "enhanced-mode")]
     pub enhanced_mode: String,
     #[serde(rename = "fake-ip-range")]
@@ -232,6 +268,8 @@ impl Default for ClashConfig {
             mixed_port: 7890,
             allow_lan: true,
             bind_address: None,
+            lan_allowed_ips: None,
+            lan_disallowed_ips: None,
             mode: "rule".to_string(),
             log_level: "info".to_string(),
             ipv6: false,
@@ -298,6 +336,9 @@ impl Default for DnsConfig {
             listen: "0.0.0.0:1053".to_string(),
             ipv6: false,
             prefer_h3: false,
+            respect_rules: None,
+            use_hosts: None,
+            use_system_hosts: None,
             enhanced_mode: "fake-ip".to_string(),
             fake_ip_range: "198.18.0.1/16".to_string(),
             fake_ip_filter: Some(vec![
@@ -328,13 +369,45 @@ impl Default for DnsConfig {
 pub struct ClashConfigBuilder {
     config: ClashConfig,
     enable_tun: bool,
-    /// Global UDP switch for all nodes
+    /// Global UDP switch; when off, UDP-native protocols still keep `udp: true`
     enable_udp: bool,
     /// Global TCP Fast Open switch
     enable_tfo: bool,
     /// Global skip-cert-verify switch
     skip_cert_verify: bool,
     rule_provider_options: RuleProviderOptions,
+    default_rule_options: DefaultRuleOptions,
+    explicit_cert_verify: bool,
+    /// Multiplex options applied to compatible nodes without their own `smux`
+    global_smux: Option<SmuxOpts>,
+    /// Fixed SNI forced on nodes (optionally only those whose name matches the regex)
+    sni_override: Option<(String, Option<Regex>)>,
+    /// Timeout (ms) for url-test/fallback groups without their own
+    default_group_timeout: Option<u32>,
+    /// `lazy` for url-test/fallback groups without their own
+    default_group_lazy: Option<bool>,
+    /// `expected-status` for url-test/fallback groups without their own
+    default_expected_status: Option<String>,
+    /// Interval (s) for the default auto url-test group
+    auto_interval: Option<u32>,
+    /// Tolerance (ms) for the default auto url-test group
+    auto_tolerance: Option<u32>,
+    /// Prefix group names with `01 `, `02 `, ... in build order
+    number_groups: bool,
+    /// Drop repeated rule strings when building (on by default)
+    rule_dedup: bool,
+    /// Append `DIRECT` and `REJECT` to every select group when building
+    append_direct_reject: bool,
+    /// Omit comment headers and blank separator lines from YAML output
+    compact: bool,
+    /// Skip parsing the rendered YAML back (off by default)
+    skip_validation: bool,
+    /// Node latency in ms (by name) used to order group members fastest-first
+    latency_map: Option<IndexMap<String, u64>>,
+    /// Node-name regex per INI group, reused as the `filter` of provider-backed groups
+    group_filters: HashMap<String, String>,
+    /// Source subscription label per node name, emitted as a `# from:` comment
+    proxy_sources: HashMap<String, String>,
 }
 
 impl ClashConfigBuilder {
@@ -346,6 +419,23 @@ impl ClashConfigBuilder {
             enable_tfo: false,
             skip_cert_verify: false,
             rule_provider_options: RuleProviderOptions::default(),
+            default_rule_options: DefaultRuleOptions::default(),
+            explicit_cert_verify: false,
+            global_smux: None,
+            sni_override: None,
+            default_group_timeout: None,
+            default_group_lazy: None,
+            default_expected_status: None,
+            auto_interval: None,
+            auto_tolerance: None,
+            number_groups: false,
+            rule_dedup: true,
+            append_direct_reject: false,
+            compact: false,
+            skip_validation: false,
+            latency_map: None,
+            group_filters: HashMap::new(),
+            proxy_sources: HashMap::new(),
         }
     }
 
@@ -355,6 +445,18 @@ impl ClashConfigBuilder {
         self
     }
 
+    /// TUN-only variant: enable TUN, disable the mixed port and force fake-ip DNS
+    pub fn with_tun_only(mut self) -> Self {
+        self.enable_tun = true;
+        self.config.mixed_port = 0;
+        if let Some(dns) = self.config.dns.as_mut() {
+            dns.enhanced_mode = "fake-ip".to_string();
+        } else {
+            self.config.dns = Some(DnsConfig::default());
+        }
+        self
+    }
+
     /// Set global options for all nodes (UDP, TFO, skip-cert-verify)
     pub fn with_global_options(
         mut self,
@@ -368,6 +470,28 @@ impl ClashConfigBuilder {
         self
     }
 
+    /// Emit `skip-cert-verify: false` on TLS nodes that don't set it (for auditing).
+    /// Must be called before `with_nodes`.
+    pub fn with_explicit_cert_verify(mut self) -> Self {
+        self.explicit_cert_verify = true;
+        self
+    }
+
+    /// Apply `smux` to every VLESS/VMess/Trojan node lacking its own.
+    /// Must be called before `with_nodes`.
+    pub fn with_global_smux(mut self, smux: SmuxOpts) -> Self {
+        self.global_smux = Some(smux);
+        self
+    }
+
+    /// Force `servername`/`sni` to a fixed value on VLESS/VMess/Trojan/Hysteria2/TUIC
+    /// nodes, or only on those whose name matches `scope`.
+    /// Must be called before `with_nodes`.
+    pub fn with_sni_override(mut self, sni: String, scope: Option<Regex>) -> Self {
+        self.sni_override = Some((sni, scope));
+        self
+    }
+
     /// Set basic proxy settings
     pub fn with_basic_settings(mut self, mixed_port: u16, allow_lan: bool) -> Self {
         self.config.mixed_port = mixed_port;
@@ -375,6 +499,223 @@ impl ClashConfigBuilder {
         self
     }
 
+    /// Set LAN access: `allow-lan`, `bind-address` and the allowed/disallowed
+    /// client CIDRs (empty lists are omitted)
+    pub fn with_lan_options(
+        mut self,
+        allow_lan: bool,
+        bind_address: Option<String>,
+        allowed_ips: Vec<String>,
+        disallowed_ips: Vec<String>,
+    ) -> Self {
+        self.config.allow_lan = allow_lan;
+        self.config.bind_address = bind_address;
+        self.config.lan_allowed_ips = Some(allowed_ips).filter(|ips| !ips.is_empty());
+        self.config.lan_disallowed_ips = Some(disallowed_ips).filter(|ips| !ips.is_empty());
+        self
+    }
+
+    /// Annotate each proxy with a `# from: <source>` comment (source label by node name)
+    pub fn with_proxy_sources(mut self, proxy_sources: HashMap<String, String>) -> Self {
+        self.proxy_sources = proxy_sources;
+        self
+    }
+
+    /// Emit YAML without section comments and blank separator lines
+    pub fn with_compact_output(mut self) -> Self {
+        self.compact = true;
+        self
+    }
+
+    /// Don't parse the rendered YAML back before returning it. Saves time on very large
+    /// configs; only for callers that validate the output themselves.
+    pub fn without_validation(mut self) -> Self {
+        self.skip_validation = true;
+        self
+    }
+
+    /// Set `find-process-mode` (off, strict, always)
+    pub fn with_find_process_mode(mut self, mode: &str) -> Self {
+        self.config.find_process_mode = Some(mode.to_string());
+        self
+    }
+
+    /// Set geodata options (`geodata-mode`, `geo-auto-update`, `geo-update-interval` in hours)
+    pub fn with_geo_options(
+        mut self,
+        geodata_mode: Option<bool>,
+        auto_update: Option<bool>,
+        update_interval: Option<u32>,
+    ) -> Self {
+        self.config.geodata_mode = geodata_mode;
+        self.config.geo_auto_update = auto_update;
+        self.config.geo_update_interval = update_interval;
+        self
+    }
+
+    /// Set the `unified-delay` and `tcp-concurrent` toggles
+    pub fn with_connection_toggles(mut self, unified_delay: bool, tcp_concurrent: bool) -> Self {
+        self.config.unified_delay = unified_delay;
+        self.config.tcp_concurrent = tcp_concurrent;
+        self
+    }
+
+    /// Set proxy mode (rule, global, direct)
+    pub fn with_mode(mut self, mode: &str) -> Self {
+        self.config.mode = mode.to_string();
+        self
+    }
+
+    /// Append DIRECT-only select groups that just display information in their name
+    /// (e.g. per-subscription remaining traffic / expiry)
+    pub fn with_info_groups(mut self, names: &[String]) -> Self {
+        for name in names {
+            let mut group: IndexMap<String, serde_yaml::Value> = IndexMap::new();
+            group.insert("name".into(), serde_yaml::Value::String(name.clone()));
+            group.insert("type".into(), serde_yaml::Value::String("select".into()));
+            group.insert(
+                "proxies".into(),
+                serde_yaml::Value::Sequence(vec![serde_yaml::Value::String("DIRECT".into())]),
+            );
+            self.config
+                .proxy_groups
+                .push(serde_yaml::to_value(group).unwrap_or(serde_yaml::Value::Null));
+        }
+        self
+    }
+
+    /// Split the default groups by region: one `url-test` group per detected region
+    /// (`🇭🇰 香港`, ...), which `🔰 节点选择` lists ahead of the nodes no region matched.
+    /// Regions without nodes get no group; `table` decides each node's region.
+    /// Call after `with_default_groups`.
+    pub fn with_region_groups(mut self, nodes: &[Node], table: &RegionTable) -> Self {
+        let mut members: HashMap<&str, Vec<serde_yaml::Value>> = HashMap::new();
+        let mut unmatched = Vec::new();
+        for node in self.group_member_nodes(nodes).iter() {
+            let name = serde_yaml::Value::String(node.name().to_string());
+            match table.detect(node.name()) {
+                Some(region) => members.entry(region.code.as_str()).or_default().push(name),
+                None => unmatched.push(name),
+            }
+        }
+
+        let mut region_groups = Vec::new();
+        for region in table.regions() {
+            let Some(proxies) = members.remove(region.code.as_str()) else {
+                continue;
+            };
+            let name = if region.flag.is_empty() {
+                region.name.clone()
+            } else {
+                format!("{} {}", region.flag, region.name)
+            };
+            let mut group: IndexMap<String, serde_yaml::Value> = IndexMap::new();
+            group.insert("name".into(), serde_yaml::Value::String(name));
+            group.insert("type".into(), serde_yaml::Value::String("url-test".into()));
+            group.insert(
+                "url".into(),
+                serde_yaml::Value::String("http://www.gstatic.com/generate_204".into()),
+            );
+            group.insert(
+                "interval".into(),
+                serde_yaml::Value::Number(self.auto_interval.unwrap_or(300).into()),
+            );
+            if let Some(tolerance) = self.auto_tolerance {
+                group.insert(
+                    "tolerance".into(),
+                    serde_yaml::Value::Number(tolerance.into()),
+                );
+            }
+            group.insert("proxies".into(), serde_yaml::Value::Sequence(proxies));
+            region_groups.push(serde_yaml::to_value(group).unwrap_or(serde_yaml::Value::Null));
+        }
+
+        let mut select_members: Vec<serde_yaml::Value> =
+            vec![serde_yaml::Value::String("♻️ 自动选择".into())];
+        select_members.extend(region_groups.iter().filter_map(|g| g.get("name").cloned()));
+        select_members.push(serde_yaml::Value::String("🎯 全球直连".into()));
+        select_members.extend(unmatched);
+
+        let groups = &mut self.config.proxy_groups;
+        let Some(select_index) = groups
+            .iter()
+            .position(|g| g.get("name").and_then(|n| n.as_str()) == Some("🔰 节点选择"))
+        else {
+            groups.extend(region_groups);
+            return self;
+        };
+        if let Some(select) = groups[select_index].as_mapping_mut() {
+            select.insert(
+                "proxies".into(),
+                serde_yaml::Value::Sequence(select_members),
+            );
+        }
+        let insert_at = groups
+            .iter()
+            .position(|g| g.get("name").and_then(|n| n.as_str()) == Some("♻️ 自动选择"))
+            .unwrap_or(select_index)
+            + 1;
+        groups.splice(insert_at..insert_at, region_groups);
+        self
+    }
+
+    /// Add a select group per node tag (in order of first appearance) listing the
+    /// tagged nodes. A tag matching an existing group name joins that group.
+    pub fn with_tag_groups(mut self, nodes: &[Node]) -> Self {
+        let mut tag_members: IndexMap<String, Vec<serde_yaml::Value>> = IndexMap::new();
+        for node in self.group_member_nodes(nodes).iter() {
+            for tag in node.tags() {
+                tag_members
+                    .entry(tag.clone())
+                    .or_default()
+                    .push(serde_yaml::Value::String(node.name().to_string()));
+            }
+        }
+
+        for (tag, members) in tag_members {
+            let mut group: IndexMap<String, serde_yaml::Value> = IndexMap::new();
+            group.insert("name".into(), serde_yaml::Value::String(tag));
+            group.insert("type".into(), serde_yaml::Value::String("select".into()));
+            group.insert("proxies".into(), serde_yaml::Value::Sequence(members));
+            self.config
+                .proxy_groups
+                .push(serde_yaml::to_value(group).unwrap_or(serde_yaml::Value::Null));
+        }
+        self
+    }
+
+    /// Add a `GLOBAL` select group (used by mihomo in global mode) listing
+    /// all existing groups followed by all nodes. Call after groups are set.
+    pub fn with_global_group(mut self, nodes: &[Node]) -> Self {
+        let group_names: Vec<String> = self
+            .config
+            .proxy_groups
+            .iter()
+            .filter_map(|g| g.get("name").and_then(|n| n.as_str()).map(str::to_string))
+            .collect();
+        if group_names.iter().any(|n| n == "GLOBAL") {
+            return self;
+        }
+
+        let mut proxies: Vec<serde_yaml::Value> = group_names
+            .into_iter()
+            .map(serde_yaml::Value::String)
+            .collect();
+        proxies.push(serde_yaml::Value::String("DIRECT".into()));
+        for node in self.group_member_nodes(nodes).iter() {
+            proxies.push(serde_yaml::Value::String(node.name().to_string()));
+        }
+
+        let mut global_group: IndexMap<String, serde_yaml::Value> = IndexMap::new();
+        global_group.insert("name".into(), serde_yaml::Value::String("GLOBAL".into()));
+        global_group.insert("type".into(), serde_yaml::Value::String("select".into()));
+        global_group.insert("proxies".into(), serde_yaml::Value::Sequence(proxies));
+        self.config
+            .proxy_groups
+            .push(serde_yaml::to_value(global_group).unwrap_or(serde_yaml::Value::Null));
+        self
+    }
+
     /// Set API settings for external controller
     pub fn with_api_settings(
         mut self,
@@ -392,6 +733,78 @@ impl ClashConfigBuilder {
         self
     }
 
+    /// Set which default rule categories to omit (used by `with_default_rules`)
+    pub fn with_default_rule_options(mut self, options: DefaultRuleOptions) -> Self {
+        self.default_rule_options = options;
+        self
+    }
+
+    /// Set a default test timeout (ms) for url-test/fallback groups lacking their own
+    pub fn with_default_group_timeout(mut self, timeout: u32) -> Self {
+        self.default_group_timeout = Some(timeout);
+        self
+    }
+
+    /// Set default `lazy` and `expected-status` (e.g. `204`) for url-test/fallback
+    /// groups lacking their own, in both the default and INI paths
+    pub fn with_default_group_health(
+        mut self,
+        lazy: Option<bool>,
+        expected_status: Option<String>,
+    ) -> Self {
+        self.default_group_lazy = lazy;
+        self.default_expected_status = expected_status;
+        self
+    }
+
+    /// Tune the default auto url-test group (interval in s, tolerance in ms).
+    /// Must be set before `with_default_groups`.
+    pub fn with_auto_group_options(
+        mut self,
+        interval: Option<u32>,
+        tolerance: Option<u32>,
+    ) -> Self {
+        self.auto_interval = interval;
+        self.auto_tolerance = tolerance;
+        self
+    }
+
+    /// Prefix group names with a zero-padded index so dashboards that sort
+    /// alphabetically keep the build order. References are rewritten in `build()`.
+    pub fn with_numbered_groups(mut self) -> Self {
+        self.number_groups = true;
+        self
+    }
+
+    /// Turn the duplicate-rule pass of `build` on or off (on by default); turn it off
+    /// when repeated rules are intentional
+    pub fn with_rule_dedup(mut self, enabled: bool) -> Self {
+        self.rule_dedup = enabled;
+        self
+    }
+
+    /// Offer `DIRECT` and `REJECT` at the end of every select group (default and INI
+    /// groups alike). Applied in `build()`.
+    pub fn with_direct_reject_appended(mut self) -> Self {
+        self.append_direct_reject = true;
+        self
+    }
+
+    /// Order proxy-group members by ascending latency (unreachable nodes last).
+    /// Must be set before `with_ini_config` / `with_default_groups`.
+    pub fn with_latency_sort(mut self, latency_map: IndexMap<String, u64>) -> Self {
+        self.latency_map = Some(latency_map);
+        self
+    }
+
+    /// Nodes in the order group members should be listed
+    fn group_member_nodes<'a>(&self, nodes: &'a [Node]) -> Cow<'a, [Node]> {
+        match &self.latency_map {
+            Some(latency) => Cow::Owned(sort_by_latency(nodes.to_vec(), latency)),
+            None => Cow::Borrowed(nodes),
+        }
+    }
+
     /// Add proxy nodes with global options applied
     pub fn with_nodes(mut self, nodes: &[Node]) -> Self {
         self.config.proxies = nodes
@@ -401,8 +814,12 @@ impl ClashConfigBuilder {
                 // Apply global options
                 if self.enable_udp {
                     map.insert("udp".to_string(), serde_yaml::Value::Bool(true));
+                } else if !n.is_udp_native() {
+                    // UDP-native protocols keep `udp: true`, they can't work without it
+                    map.insert("udp".to_string(), serde_yaml::Value::Bool(false));
                 }
-                if self.enable_tfo {
+                // Per-node tfo from the link wins over the global switch
+                if self.enable_tfo && !map.contains_key("tfo") {
                     map.insert("tfo".to_string(), serde_yaml::Value::Bool(true));
                 }
                 if self.skip_cert_verify {
@@ -410,6 +827,26 @@ impl ClashConfigBuilder {
                         "skip-cert-verify".to_string(),
                         serde_yaml::Value::Bool(true),
                     );
+                } else if self.explicit_cert_verify
+                    && uses_tls_verification(n)
+                    && !map.contains_key("skip-cert-verify")
+                {
+                    map.insert(
+                        "skip-cert-verify".to_string(),
+                        serde_yaml::Value::Bool(false),
+                    );
+                }
+                if let Some(smux) = &self.global_smux {
+                    if n.supports_smux() && !map.contains_key("smux") {
+                        map.insert("smux".to_string(), smux.to_clash_value());
+                    }
+                }
+                if let Some((sni, scope)) = &self.sni_override {
+                    if let Some(key) = n.sni_key() {
+                        if scope.as_ref().is_none_or(|re| re.is_match(n.name())) {
+                            map.insert(key.to_string(), serde_yaml::Value::from(sni.as_str()));
+                        }
+                    }
                 }
                 match serde_yaml::to_value(map) {
                     Ok(value) => value,
@@ -423,14 +860,60 @@ impl ClashConfigBuilder {
         self
     }
 
+    /// Proxy names that appear more than once (mihomo rejects duplicates)
+    pub fn duplicate_proxy_names(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut duplicates: Vec<String> = Vec::new();
+        for name in self
+            .config
+            .proxies
+            .iter()
+            .filter_map(|p| p.get("name").and_then(|n| n.as_str()))
+        {
+            if !seen.insert(name) && !duplicates.iter().any(|d| d == name) {
+                duplicates.push(name.to_string());
+            }
+        }
+        duplicates
+    }
+
     /// Add proxy groups from parsed INI config
     pub fn with_ini_config(mut self, ini_config: &ParsedIniConfig, nodes: &[Node]) -> Self {
         // Convert proxy groups
-        let groups = to_clash_proxy_groups(&ini_config.proxy_groups, nodes);
+        let member_nodes = self.group_member_nodes(nodes);
+        let groups = to_clash_proxy_groups(
+            &ini_config.proxy_groups,
+            &member_nodes,
+            &ini_config.defaults,
+        );
         self.config.proxy_groups = groups
             .into_iter()
             .map(|g| serde_yaml::to_value(g).unwrap_or(serde_yaml::Value::Null))
             .collect();
+        self.group_filters = ini_config
+            .proxy_groups
+            .iter()
+            .filter_map(|g| {
+                let patterns: Vec<&str> = g
+                    .proxies
+                    .iter()
+                    .filter_map(|m| match m {
+                        ProxyMatcher::Pattern(p) => Some(p.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                let filter = match patterns.as_slice() {
+                    [] => return None,
+                    [single] => single.to_string(),
+                    many => many
+                        .iter()
+                        .map(|p| format!("(?:{})", p))
+                        .collect::<Vec<_>>()
+                        .join("|"),
+                };
+                Some((g.name.clone(), filter))
+            })
+            .collect();
 
         // Convert inline rules
         let mut rules = to_clash_rules(&ini_config.rules);
@@ -474,7 +957,12 @@ impl ClashConfigBuilder {
                 behavior: behavior.to_string(),
                 format,
                 path,
-                proxy: self.rule_provider_options.proxy.clone(),
+                proxy: self
+                    .rule_provider_options
+                    .proxy_by_behavior
+                    .get(behavior)
+                    .or(self.rule_provider_options.proxy.as_ref())
+                    .cloned(),
                 header: self.rule_provider_options.header.clone(),
                 size_limit: self.rule_provider_options.size_limit,
                 interval: 86400,
@@ -498,7 +986,11 @@ impl ClashConfigBuilder {
 
     /// Add default proxy groups if no INI config
     pub fn with_default_groups(mut self, nodes: &[Node]) -> Self {
-        let node_names: Vec<String> = nodes.iter().map(|n| n.name().to_string()).collect();
+        let node_names: Vec<String> = self
+            .group_member_nodes(nodes)
+            .iter()
+            .map(|n| n.name().to_string())
+            .collect();
 
         // Create default groups
         let mut groups = Vec::new();
@@ -531,7 +1023,16 @@ impl ClashConfigBuilder {
             "url".into(),
             serde_yaml::Value::String("http://www.gstatic.com/generate_204".into()),
         );
-        auto_group.insert("interval".into(), serde_yaml::Value::Number(300.into()));
+        auto_group.insert(
+            "interval".into(),
+            serde_yaml::Value::Number(self.auto_interval.unwrap_or(300).into()),
+        );
+        if let Some(tolerance) = self.auto_tolerance {
+            auto_group.insert(
+                "tolerance".into(),
+                serde_yaml::Value::Number(tolerance.into()),
+            );
+        }
         auto_group.insert(
             "proxies".into(),
             serde_yaml::Value::Sequence(
@@ -591,20 +1092,156 @@ impl ClashConfigBuilder {
         self
     }
 
-    /// Add default rules if no INI config
+    /// Add default rules if no INI config, honoring `DefaultRuleOptions`
     pub fn with_default_rules(mut self) -> Self {
-        self.config.rules = vec![
-            "DOMAIN-SUFFIX,local,🎯 全球直连".into(),
-            "IP-CIDR,192.168.0.0/16,🎯 全球直连,no-resolve".into(),
-            "IP-CIDR,10.0.0.0/8,🎯 全球直连,no-resolve".into(),
-            "IP-CIDR,172.16.0.0/12,🎯 全球直连,no-resolve".into(),
-            "IP-CIDR,127.0.0.0/8,🎯 全球直连,no-resolve".into(),
-            "GEOIP,CN,🎯 全球直连".into(),
-            "MATCH,🐟 漏网之鱼".into(),
-        ];
+        let options = &self.default_rule_options;
+        let mut rules: Vec<String> = Vec::new();
+        if !options.skip_private_direct {
+            rules.extend([
+                "DOMAIN-SUFFIX,local,🎯 全球直连".into(),
+                "IP-CIDR,192.168.0.0/16,🎯 全球直连,no-resolve".into(),
+                "IP-CIDR,10.0.0.0/8,🎯 全球直连,no-resolve".into(),
+                "IP-CIDR,172.16.0.0/12,🎯 全球直连,no-resolve".into(),
+                "IP-CIDR,127.0.0.0/8,🎯 全球直连,no-resolve".into(),
+            ]);
+        }
+        if !options.skip_cn_direct {
+            rules.push("GEOIP,CN,🎯 全球直连".into());
+        }
+        if !options.skip_match {
+            rules.push("MATCH,🐟 漏网之鱼".into());
+        }
+        self.config.rules = rules;
         self
     }
 
+    /// Number of proxy groups currently set
+    pub fn group_count(&self) -> usize {
+        self.config.proxy_groups.len()
+    }
+
+    /// Number of rules currently set
+    pub fn rule_count(&self) -> usize {
+        self.config.rules.len()
+    }
+
+    /// Remove repeated rules, keeping the first occurrence (the last one for `MATCH`,
+    /// so a trailing `MATCH` stays last). Returns the number of rules removed.
+    pub fn dedup_rules(&mut self) -> usize {
+        let rules = &mut self.config.rules;
+        let before = rules.len();
+        let key = |rule: &str| rule.split(',').map(str::trim).collect::<Vec<_>>().join(",");
+        // Position and key of the last MATCH; earlier copies of it are dropped instead
+        let last_match = rules
+            .iter()
+            .rposition(|r| r.starts_with("MATCH,"))
+            .map(|i| (i, key(&rules[i])));
+        let mut seen = HashSet::new();
+        let mut index = 0;
+        rules.retain(|rule| {
+            let k = key(rule);
+            let keep = match &last_match {
+                Some((pos, match_key)) if *match_key == k => index == *pos,
+                _ => seen.insert(k),
+            };
+            index += 1;
+            keep
+        });
+        before - rules.len()
+    }
+
+    /// Cap inline rules at `max`, dropping from the end but keeping a trailing
+    /// `MATCH` rule last. Returns the number of rules dropped.
+    pub fn truncate_rules(&mut self, max: usize) -> usize {
+        let rules = &mut self.config.rules;
+        if rules.len() <= max {
+            return 0;
+        }
+        let before = rules.len();
+        let match_rule = rules.last().filter(|r| r.starts_with("MATCH,")).cloned();
+        match match_rule {
+            Some(match_rule) if max > 0 => {
+                rules.truncate(max - 1);
+                rules.push(match_rule);
+            }
+            _ => rules.truncate(max),
+        }
+        before - rules.len()
+    }
+
+    /// Rewrite rule targets (`old → new`, e.g. a direct-connect group → `DIRECT`) in
+    /// rules, rule-providers and group member lists. Call once all groups and rules are
+    /// set. Returns the used rewrite targets that match no group, proxy or built-in policy.
+    pub fn rewrite_rule_targets(&mut self, rewrite: &IndexMap<String, String>) -> Vec<String> {
+        let mut used: Vec<&str> = Vec::new();
+
+        for rule in self.config.rules.iter_mut() {
+            let mut fields: Vec<&str> = rule.split(',').collect();
+            let Some(target) = rule_target_index(&fields) else {
+                continue;
+            };
+            if let Some(new_target) = rewrite.get(fields[target].trim()) {
+                fields[target] = new_target;
+                if !used.contains(&new_target.as_str()) {
+                    used.push(new_target);
+                }
+                *rule = fields.join(",");
+            }
+        }
+
+        for rp in self.config.rule_providers.iter_mut() {
+            if let Some(new_target) = rewrite.get(&rp.target) {
+                rp.target = new_target.clone();
+            }
+        }
+
+        for group in self.config.proxy_groups.iter_mut() {
+            let serde_yaml::Value::Mapping(map) = group else {
+                continue;
+            };
+            let own_name = map
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or_default()
+                .to_string();
+            let Some(serde_yaml::Value::Sequence(members)) = map.get_mut("proxies") else {
+                continue;
+            };
+            let mut seen = HashSet::new();
+            let rewritten: Vec<serde_yaml::Value> = members
+                .iter()
+                .map(
+                    |member| match member.as_str().and_then(|m| rewrite.get(m)) {
+                        Some(new_target) => {
+                            if !used.contains(&new_target.as_str()) {
+                                used.push(new_target);
+                            }
+                            serde_yaml::Value::String(new_target.clone())
+                        }
+                        None => member.clone(),
+                    },
+                )
+                // A group can't contain itself, and members must stay unique
+                .filter(|m| m.as_str() != Some(own_name.as_str()))
+                .filter(|m| seen.insert(m.clone()))
+                .collect();
+            *members = rewritten;
+        }
+
+        let known: HashSet<&str> = self
+            .config
+            .proxies
+            .iter()
+            .chain(self.config.proxy_groups.iter())
+            .filter_map(|v| v.get("name").and_then(|n| n.as_str()))
+            .chain(BUILTIN_POLICIES.iter().copied())
+            .collect();
+        used.into_iter()
+            .filter(|target| !known.contains(target))
+            .map(str::to_string)
+            .collect()
+    }
+
     /// Set proxy groups directly
     pub fn with_proxy_groups(mut self, groups: Vec<IndexMap<String, serde_yaml::Value>>) -> Self {
         self.config.proxy_groups = groups
@@ -614,12 +1251,49 @@ impl ClashConfigBuilder {
         self
     }
 
+    /// Set rule-providers directly
+    pub fn with_rule_providers(mut self, providers: Vec<RuleProvider>) -> Self {
+        self.config.rule_providers = providers;
+        self
+    }
+
     /// Set rules directly
     pub fn with_rules(mut self, rules: Vec<String>) -> Self {
         self.config.rules = rules;
         self
     }
 
+    /// Set `dns.respect-rules` (no effect when DNS is disabled)
+    pub fn with_dns_respect_rules(mut self, respect_rules: bool) -> Self {
+        if let Some(dns) = self.config.dns.as_mut() {
+            dns.respect_rules = Some(respect_rules);
+        }
+        self
+    }
+
+    /// Enable IPv6 globally and in DNS together, so AAAA answers aren't dropped while
+    /// IPv6 traffic is allowed (or the other way round)
+    pub fn with_ipv6(mut self) -> Self {
+        self.config.ipv6 = true;
+        if let Some(dns) = self.config.dns.as_mut() {
+            dns.ipv6 = true;
+        }
+        self
+    }
+
+    /// Set `dns.use-hosts` / `dns.use-system-hosts` (no effect when DNS is disabled)
+    pub fn with_dns_hosts(
+        mut self,
+        use_hosts: Option<bool>,
+        use_system_hosts: Option<bool>,
+    ) -> Self {
+        if let Some(dns) = self.config.dns.as_mut() {
+            dns.use_hosts = use_hosts;
+            dns.use_system_hosts = use_system_hosts;
+        }
+        self
+    }
+
     /// Disable DNS config
     pub fn without_dns(mut self) -> Self {
         self.config.dns = None;
@@ -627,277 +1301,736 @@ impl ClashConfigBuilder {
     }
 
     /// Build the final config
-    pub fn build(self) -> ClashConfig {
+    pub fn build(mut self) -> ClashConfig {
+        merge_duplicate_groups(&mut self.config.proxy_groups);
+        if self.append_direct_reject {
+            append_select_members(&mut self.config.proxy_groups, &["DIRECT", "REJECT"]);
+        }
+        if self.rule_dedup {
+            self.dedup_rules();
+        }
+        if let Some(timeout) = self.default_group_timeout {
+            apply_group_default(&mut self.config.proxy_groups, "timeout", timeout.into());
+        }
+        if let Some(lazy) = self.default_group_lazy {
+            apply_group_default(&mut self.config.proxy_groups, "lazy", lazy.into());
+        }
+        if let Some(status) = self.default_expected_status.take() {
+            apply_group_default(
+                &mut self.config.proxy_groups,
+                "expected-status",
+                status.into(),
+            );
+        }
+        if self.number_groups {
+            number_group_names(
+                &mut self.config.proxy_groups,
+                &mut self.config.rules,
+                &mut self.config.rule_providers,
+            );
+        }
         self.config
     }
 
-    /// Build and serialize to YAML string
-    /// Generates a simple, compatible config that works with all Mihomo/Clash Meta versions
-    pub fn build_yaml(self) -> Result<String, serde_yaml::Error> {
-        let enable_tun = self.enable_tun;
+    /// Build only the `proxies:` block (for embedding into another profile)
+    pub fn build_proxies_yaml(mut self) -> Result<String, serde_yaml::Error> {
+        let proxy_sources = std::mem::take(&mut self.proxy_sources);
+        let validate = !self.skip_validation;
         let config = self.build();
 
-        let mut output = String::new();
+        let mut output = String::from("proxies:\n");
+        write_proxies(&config.proxies, &proxy_sources, &mut output)?;
 
-        // Header comment
-        output.push_str("# Clash Meta Configuration\n");
-        output.push_str("# Generated by LocalSub\n\n");
-
-        // Basic settings
-        output.push_str("# 基础设置\n");
-        output.push_str(&format!("mixed-port: {}\n", config.mixed_port));
-        output.push_str(&format!("allow-lan: {}\n", config.allow_lan));
-        output.push_str(&format!("mode: {}\n", config.mode));
-        output.push_str(&format!("log-level: {}\n", config.log_level));
-        output.push_str(&format!("ipv6: {}\n", config.ipv6));
-        output.push_str(&format!("unified-delay: {}\n", config.unified_delay));
-        output.push_str(&format!("tcp-concurrent: {}\n", config.tcp_concurrent));
-        if let Some(fpm) = &config.find_process_mode {
-            output.push_str(&format!("find-process-mode: {}\n", fpm));
-        }
-        if let Some(ec) = &config.external_controller {
-            let v = serde_yaml::Value::String(ec.clone());
-            output.push_str(&format!(
-                "external-controller: {}\n",
-                format_yaml_value_simple(&v)
-            ));
+        if validate {
+            let _: serde_yaml::Value = serde_yaml::from_str(&output)?;
         }
-        if let Some(secret) = &config.secret {
-            let v = serde_yaml::Value::String(secret.clone());
-            output.push_str(&format!("secret: {}\n", format_yaml_value_simple(&v)));
-        }
-        output.push('\n');
 
-        // TUN settings (optional)
-        if enable_tun {
-            output.push_str("# TUN 模式 (系统代理)\n");
-            output.push_str("tun:\n");
-            output.push_str("  enable: true\n");
-            output.push_str("  stack: mixed\n");
-            output.push_str("  dns-hijack:\n");
-            output.push_str("    - any:53\n");
-            output.push_str("    - tcp://any:53\n");
-            output.push_str("  auto-route: true\n");
-            output.push_str("  auto-redirect: true\n");
-            output.push_str("  auto-detect-interface: true\n");
-            output.push('\n');
-        }
-
-        // Profile settings
-        if let Some(profile) = &config.profile {
-            output.push_str("# 缓存设置\n");
-            output.push_str("profile:\n");
-            output.push_str(&format!("  store-selected: {}\n", profile.store_selected));
-            output.push_str(&format!("  store-fake-ip: {}\n", profile.store_fake_ip));
-            output.push('\n');
-        }
-
-        // Sniffer settings
-        if let Some(sniffer) = &config.sniffer {
-            output.push_str("# 域名嗅探\n");
-            output.push_str("sniffer:\n");
-            output.push_str(&format!("  enable: {}\n", sniffer.enable));
-            output.push_str(&format!(
-                "  force-dns-mapping: {}\n",
-                sniffer.force_dns_mapping
-            ));
-            output.push_str(&format!("  parse-pure-ip: {}\n", sniffer.parse_pure_ip));
-            output.push_str(&format!(
-                "  override-destination: {}\n",
-                sniffer.override_destination
-            ));
-            output.push_str("  sniff:\n");
-            output.push_str("    HTTP:\n");
-            output.push_str(&format!(
-                "      ports: [{}]\n",
-                sniffer
-                    .sniff
-                    .http
-                    .ports
-                    .iter()
-                    .map(|p| format!("\"{}\"", p))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-            if let Some(od) = sniffer.sniff.http.override_destination {
-                output.push_str(&format!("      override-destination: {}\n", od));
-            }
-            output.push_str("    TLS:\n");
-            output.push_str(&format!(
-                "      ports: [{}]\n",
-                sniffer
-                    .sniff
-                    .tls
-                    .ports
-                    .iter()
-                    .map(|p| format!("\"{}\"", p))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-            if let Some(od) = sniffer.sniff.tls.override_destination {
-                output.push_str(&format!("      override-destination: {}\n", od));
-            }
-            output.push_str("    QUIC:\n");
-            output.push_str(&format!(
-                "      ports: [{}]\n",
-                sniffer
-                    .sniff
-                    .quic
-                    .ports
-                    .iter()
-                    .map(|p| format!("\"{}\"", p))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-            if let Some(od) = sniffer.sniff.quic.override_destination {
-                output.push_str(&format!("      override-destination: {}\n", od));
-            }
-            if let Some(skip) = &sniffer.skip_domain {
-                output.push_str("  skip-domain:\n");
-                for d in skip {
-                    output.push_str(&format!("    - \"{}\"\n", d));
-                }
-            }
-            output.push('\n');
-        }
-
-        // DNS settings
-        if let Some(dns) = &config.dns {
-            output.push_str("# DNS 设置\n");
-            output.push_str("dns:\n");
-            output.push_str(&format!("  enable: {}\n", dns.enable));
-            output.push_str(&format!("  listen: {}\n", dns.listen));
-            output.push_str(&format!("  ipv6: {}\n", dns.ipv6));
-            output.push_str(&format!("  prefer-h3: {}\n", dns.prefer_h3));
-            output.push_str(&format!("  enhanced-mode: {}\n", dns.enhanced_mode));
-            output.push_str(&format!("  fake-ip-range: {}\n", dns.fake_ip_range));
-            if let Some(filter) = &dns.fake_ip_filter {
-                output.push_str("  fake-ip-filter:\n");
-                for f in filter {
-                    output.push_str(&format!("    - \"{}\"\n", f));
-                }
-            }
-            output.push_str("  default-nameserver:\n");
-            for ns in &dns.default_nameserver {
-                output.push_str(&format!("    - {}\n", ns));
-            }
-            output.push_str("  nameserver:\n");
-            for ns in &dns.nameserver {
-                output.push_str(&format!("    - {}\n", ns));
-            }
-            if let Some(proxy_server_nameserver) = &dns.proxy_server_nameserver {
-                output.push_str("  proxy-server-nameserver:\n");
-                for ns in proxy_server_nameserver {
-                    output.push_str(&format!("    - {}\n", ns));
-                }
-            }
-            if let Some(fallback) = &dns.fallback {
-                output.push_str("  fallback:\n");
-                for ns in fallback {
-                    output.push_str(&format!("    - {}\n", ns));
-                }
-            }
-            if let Some(ff) = &dns.fallback_filter {
-                output.push_str("  fallback-filter:\n");
-                output.push_str(&format!("    geoip: {}\n", ff.geoip));
-                output.push_str(&format!("    geoip-code: {}\n", ff.geoip_code));
-                if let Some(geosite) = &ff.geosite {
-                    output.push_str("    geosite:\n");
-                    for gs in geosite {
-                        output.push_str(&format!("      - {}\n", gs));
-                    }
-                }
-                output.push_str("    ipcidr:\n");
-                for cidr in &ff.ipcidr {
-                    output.push_str(&format!("      - {}\n", cidr));
-                }
-                if let Some(domains) = &ff.domain {
-                    output.push_str("    domain:\n");
-                    for d in domains {
-                        output.push_str(&format!("      - \"{}\"\n", d));
-                    }
-                }
-            }
-            if let Some(policy) = &dns.nameserver_policy {
-                output.push_str("  nameserver-policy:\n");
-                for (domain, servers) in policy {
-                    output.push_str(&format!("    \"{}\":\n", domain));
-                    for s in servers {
-                        output.push_str(&format!("      - {}\n", s));
-                    }
-                }
-            }
-            output.push('\n');
-        }
+        Ok(output)
+    }
 
-        // Proxies section
-        output.push_str("# 代理节点\n");
-        output.push_str("proxies:\n");
-        for proxy in &config.proxies {
-            output.push_str(&format_proxy_yaml(proxy)?);
+    /// Build only the proxy-groups, rule-providers and rules sections
+    /// (a shared template without proxies or base settings)
+    pub fn build_groups_rules_yaml(self) -> Result<String, serde_yaml::Error> {
+        let compact = self.compact;
+        let validate = !self.skip_validation;
+        let config = self.build();
+
+        let mut output = String::new();
+        write_groups_and_rules(&config, &mut output)?;
+        if compact {
+            output = strip_comments_and_blank_lines(&output);
         }
-        output.push('\n');
 
-        // Proxy groups
-        output.push_str("# 策略组\n");
-        output.push_str("proxy-groups:\n");
-        for group in &config.proxy_groups {
-            output.push_str(&format_group_yaml(group)?);
+        if validate {
+            let _: serde_yaml::Value = serde_yaml::from_str(&output)?;
         }
-        output.push('\n');
 
-        // Rules - if rule-providers exist, output them but also add fallback inline rules
-        if !config.rule_providers.is_empty() {
-            output.push_str("# 规则集\n");
-            output.push_str("rule-providers:\n");
-            for rp in &config.rule_providers {
-                output.push_str(&format!("  {}:\n", rp.name));
-                output.push_str(&format!("    type: {}\n", rp.provider_type));
-                output.push_str(&format!("    behavior: {}\n", rp.behavior));
-                output.push_str(&format!("    url: \"{}\"\n", rp.url));
-                if let Some(fmt) = &rp.format {
-                    output.push_str(&format!("    format: {}\n", fmt));
-                }
-                if let Some(path) = &rp.path {
-                    output.push_str(&format!("    path: \"{}\"\n", path));
+        Ok(output)
+    }
+
+    /// Build and inject proxies, groups and rules into a user-supplied base config.
+    /// Every other key of the base (DNS, tun, sniffer, ...) is kept as-is.
+    pub fn build_into_base(self, base: &str) -> Result<String, serde_yaml::Error> {
+        let base = base.strip_prefix('\u{FEFF}').unwrap_or(base);
+        let mut base: serde_yaml::Value = serde_yaml::from_str(base)?;
+        let Some(base_map) = base.as_mapping_mut() else {
+            return Err(serde::de::Error::custom(
+                "base config must be a YAML mapping",
+            ));
+        };
+
+        let generated: serde_yaml::Value = serde_yaml::from_str(&self.build_yaml()?)?;
+        for key in ["proxies", "proxy-groups", "rule-providers", "rules"] {
+            if let Some(value) = generated.get(key) {
+                base_map.insert(serde_yaml::Value::from(key), value.clone());
+            }
+        }
+
+        serde_yaml::to_string(&base)
+    }
+
+    /// Build and serialize to YAML string
+    /// Generates a simple, compatible config that works with all Mihomo/Clash Meta versions
+    pub fn build_yaml(mut self) -> Result<String, serde_yaml::Error> {
+        let enable_tun = self.enable_tun;
+        let compact = self.compact;
+        let validate = !self.skip_validation;
+        let proxy_sources = std::mem::take(&mut self.proxy_sources);
+        let config = self.build();
+        render_config_yaml(&config, enable_tun, compact, validate, None, &proxy_sources)
+    }
+
+    /// Build a sing-box config from the same proxies and groups (see `singbox`)
+    pub fn build_singbox_json(self) -> Result<SingBoxOutput, serde_json::Error> {
+        build_singbox_config(&self.build())
+    }
+
+    /// Build the config split in two: a provider file holding the proxies, and a
+    /// main config that loads it via `proxy-providers` (groups reference it with `use:`)
+    pub fn build_split_yaml(
+        mut self,
+        provider_name: &str,
+        provider_path: &str,
+    ) -> Result<SplitOutput, serde_yaml::Error> {
+        let enable_tun = self.enable_tun;
+        let compact = self.compact;
+        let validate = !self.skip_validation;
+        let group_filters = std::mem::take(&mut self.group_filters);
+        let proxy_sources = std::mem::take(&mut self.proxy_sources);
+        // Number after the split: `group_filters` is keyed by the original group names
+        let number_groups = std::mem::take(&mut self.number_groups);
+        let mut config = self.build();
+
+        let mut providers_file = String::from("proxies:\n");
+        write_proxies(&config.proxies, &proxy_sources, &mut providers_file)?;
+        if validate {
+            let _: serde_yaml::Value = serde_yaml::from_str(&providers_file)?;
+        }
+
+        let node_names: Vec<String> = config
+            .proxies
+            .iter()
+            .filter_map(|p| p.get("name").and_then(|n| n.as_str()).map(str::to_string))
+            .collect();
+        use_provider_in_groups(
+            &mut config.proxy_groups,
+            &node_names,
+            provider_name,
+            &group_filters,
+        );
+        if number_groups {
+            number_group_names(
+                &mut config.proxy_groups,
+                &mut config.rules,
+                &mut config.rule_providers,
+            );
+        }
+        config.proxies.clear();
+
+        let main_config = render_config_yaml(
+            &config,
+            enable_tun,
+            compact,
+            validate,
+            Some((provider_name, provider_path)),
+            &proxy_sources,
+        )?;
+        Ok(SplitOutput {
+            main_config,
+            providers_file,
+        })
+    }
+}
+
+/// Config split into a main file and a proxy provider file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitOutput {
+    /// Main config, loading the proxies through `proxy-providers`
+    pub main_config: String,
+    /// Provider file with the `proxies:` list
+    pub providers_file: String,
+}
+
+/// Typed view of generated YAML used by `validate_yaml_strict`. Sections are optional
+/// so partial outputs (proxies only, groups/rules only, provider files) validate too.
+/// Most fields are never read; deserializing them is the type check.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[allow(dead_code)]
+struct StrictConfig {
+    mixed_port: Option<u16>,
+    allow_lan: Option<bool>,
+    mode: Option<String>,
+    ipv6: Option<bool>,
+    profile: Option<ProfileConfig>,
+    sniffer: Option<SnifferConfig>,
+    dns: Option<DnsConfig>,
+    #[serde(default)]
+    proxies: Vec<StrictProxy>,
+    #[serde(default)]
+    proxy_groups: Vec<StrictGroup>,
+    #[serde(default)]
+    rule_providers: IndexMap<String, StrictRuleProvider>,
+    #[serde(default)]
+    rules: Vec<String>,
+}
+
+/// Fields every proxy must carry, with their Clash types. `server`/`port` are only
+/// optional for WireGuard proxies with `peers`, which carry them per peer.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[allow(dead_code)]
+struct StrictProxy {
+    name: String,
+    #[serde(rename = "type")]
+    proxy_type: String,
+    server: Option<String>,
+    port: Option<u16>,
+    peers: Option<Vec<StrictPeer>>,
+    udp: Option<bool>,
+    tfo: Option<bool>,
+    tls: Option<bool>,
+    skip_cert_verify: Option<bool>,
+    alpn: Option<Vec<String>>,
+}
+
+/// WireGuard `peers` entry
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[allow(dead_code)]
+struct StrictPeer {
+    server: String,
+    port: u16,
+    public_key: String,
+    allowed_ips: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[allow(dead_code)]
+struct StrictGroup {
+    name: String,
+    #[serde(rename = "type")]
+    group_type: String,
+    proxies: Option<Vec<String>>,
+    #[serde(rename = "use")]
+    providers: Option<Vec<String>>,
+    url: Option<String>,
+    interval: Option<u32>,
+    timeout: Option<u32>,
+    tolerance: Option<u32>,
+    lazy: Option<bool>,
+    filter: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[allow(dead_code)]
+struct StrictRuleProvider {
+    #[serde(rename = "type")]
+    provider_type: String,
+    behavior: String,
+    url: Option<String>,
+    path: Option<String>,
+    interval: Option<u32>,
+    format: Option<String>,
+    proxy: Option<String>,
+    size_limit: Option<u64>,
+}
+
+/// Strictly validate generated YAML by deserializing it into typed config structs,
+/// catching type mismatches a plain `Value` parse accepts (e.g. a quoted `port`).
+/// Group and proxy names must also be unique.
+pub fn validate_yaml_strict(yaml: &str) -> Result<(), serde_yaml::Error> {
+    let config: StrictConfig = serde_yaml::from_str(yaml)?;
+
+    for proxy in &config.proxies {
+        let has_peers =
+            proxy.proxy_type == "wireguard" && proxy.peers.as_ref().is_some_and(|p| !p.is_empty());
+        if !has_peers {
+            for (field, missing) in [
+                ("server", proxy.server.is_none()),
+                ("port", proxy.port.is_none()),
+            ] {
+                if missing {
+                    return Err(serde::de::Error::custom(format!(
+                        "proxy {}: missing field `{}`",
+                        proxy.name, field
+                    )));
                 }
-                if let Some(proxy) = &rp.proxy {
-                    let v = serde_yaml::Value::String(proxy.clone());
-                    output.push_str(&format!("    proxy: {}\n", format_yaml_value_simple(&v)));
+            }
+        }
+    }
+
+    let mut names = HashSet::new();
+    for name in config
+        .proxies
+        .iter()
+        .map(|p| &p.name)
+        .chain(config.proxy_groups.iter().map(|g| &g.name))
+    {
+        if !names.insert(name.as_str()) {
+            return Err(serde::de::Error::custom(format!(
+                "duplicate proxy or group name: {}",
+                name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Render a complete config. With a `(name, path)` provider, a `proxy-providers`
+/// entry pointing at the file replaces the inline `proxies:` section.
+fn render_config_yaml(
+    config: &ClashConfig,
+    enable_tun: bool,
+    compact: bool,
+    validate: bool,
+    provider: Option<(&str, &str)>,
+    proxy_sources: &HashMap<String, String>,
+) -> Result<String, serde_yaml::Error> {
+    let mut output = String::new();
+
+    // Header comment
+    output.push_str("# Clash Meta Configuration\n");
+    output.push_str("# Generated by LocalSub\n\n");
+
+    // Basic settings
+    output.push_str("# 基础设置\n");
+    output.push_str(&format!("mixed-port: {}\n", config.mixed_port));
+    output.push_str(&format!("allow-lan: {}\n", config.allow_lan));
+    if let Some(bind_address) = &config.bind_address {
+        output.push_str(&format!("bind-address: \"{}\"\n", bind_address));
+    }
+    for (key, ips) in [
+        ("lan-allowed-ips", &config.lan_allowed_ips),
+        ("lan-disallowed-ips", &config.lan_disallowed_ips),
+    ] {
+        if let Some(ips) = ips {
+            output.push_str(&format!("{}:\n", key));
+            for ip in ips {
+                output.push_str(&format!("  - \"{}\"\n", ip));
+            }
+        }
+    }
+    output.push_str(&format!("mode: {}\n", config.mode));
+    output.push_str(&format!("log-level: {}\n", config.log_level));
+    output.push_str(&format!("ipv6: {}\n", config.ipv6));
+    output.push_str(&format!("unified-delay: {}\n", config.unified_delay));
+    output.push_str(&format!("tcp-concurrent: {}\n", config.tcp_concurrent));
+    if let Some(fpm) = &config.find_process_mode {
+        output.push_str(&format!("find-process-mode: {}\n", fpm));
+    }
+    if let Some(geodata_mode) = config.geodata_mode {
+        output.push_str(&format!("geodata-mode: {}\n", geodata_mode));
+    }
+    if let Some(auto_update) = config.geo_auto_update {
+        output.push_str(&format!("geo-auto-update: {}\n", auto_update));
+    }
+    if let Some(interval) = config.geo_update_interval {
+        output.push_str(&format!("geo-update-interval: {}\n", interval));
+    }
+    if let Some(ec) = &config.external_controller {
+        let v = serde_yaml::Value::String(ec.clone());
+        output.push_str(&format!(
+            "external-controller: {}\n",
+            format_yaml_value_simple(&v)
+        ));
+    }
+    if let Some(secret) = &config.secret {
+        let v = serde_yaml::Value::String(secret.clone());
+        output.push_str(&format!("secret: {}\n", format_yaml_value_simple(&v)));
+    }
+    output.push('\n');
+
+    // TUN settings (optional)
+    if enable_tun {
+        output.push_str("# TUN 模式 (系统代理)\n");
+        output.push_str("tun:\n");
+        output.push_str("  enable: true\n");
+        output.push_str("  stack: mixed\n");
+        output.push_str("  dns-hijack:\n");
+        output.push_str("    - any:53\n");
+        output.push_str("    - tcp://any:53\n");
+        output.push_str("  auto-route: true\n");
+        output.push_str("  auto-redirect: true\n");
+        output.push_str("  auto-detect-interface: true\n");
+        output.push('\n');
+    }
+
+    // Profile settings
+    if let Some(profile) = &config.profile {
+        output.push_str("# 缓存设置\n");
+        output.push_str("profile:\n");
+        output.push_str(&format!("  store-selected: {}\n", profile.store_selected));
+        output.push_str(&format!("  store-fake-ip: {}\n", profile.store_fake_ip));
+        output.push('\n');
+    }
+
+    // Sniffer settings
+    if let Some(sniffer) = &config.sniffer {
+        output.push_str("# 域名嗅探\n");
+        output.push_str("sniffer:\n");
+        output.push_str(&format!("  enable: {}\n", sniffer.enable));
+        output.push_str(&format!(
+            "  force-dns-mapping: {}\n",
+            sniffer.force_dns_mapping
+        ));
+        output.push_str(&format!("  parse-pure-ip: {}\n", sniffer.parse_pure_ip));
+        output.push_str(&format!(
+            "  override-destination: {}\n",
+            sniffer.override_destination
+        ));
+        output.push_str("  sniff:\n");
+        output.push_str("    HTTP:\n");
+        output.push_str(&format!(
+            "      ports: [{}]\n",
+            sniffer
+                .sniff
+                .http
+                .ports
+                .iter()
+                .map(|p| format!("\"{}\"", p))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        if let Some(od) = sniffer.sniff.http.override_destination {
+            output.push_str(&format!("      override-destination: {}\n", od));
+        }
+        output.push_str("    TLS:\n");
+        output.push_str(&format!(
+            "      ports: [{}]\n",
+            sniffer
+                .sniff
+                .tls
+                .ports
+                .iter()
+                .map(|p| format!("\"{}\"", p))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        if let Some(od) = sniffer.sniff.tls.override_destination {
+            output.push_str(&format!("      override-destination: {}\n", od));
+        }
+        output.push_str("    QUIC:\n");
+        output.push_str(&format!(
+            "      ports: [{}]\n",
+            sniffer
+                .sniff
+                .quic
+                .ports
+                .iter()
+                .map(|p| format!("\"{}\"", p))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        if let Some(od) = sniffer.sniff.quic.override_destination {
+            output.push_str(&format!("      override-destination: {}\n", od));
+        }
+        if let Some(skip) = &sniffer.skip_domain {
+            output.push_str("  skip-domain:\n");
+            for d in skip {
+                output.push_str(&format!("    - \"{}\"\n", d));
+            }
+        }
+        output.push('\n');
+    }
+
+    // DNS settings
+    if let Some(dns) = &config.dns {
+        output.push_str("# DNS 设置\n");
+        output.push_str("dns:\n");
+        output.push_str(&format!("  enable: {}\n", dns.enable));
+        output.push_str(&format!("  listen: {}\n", dns.listen));
+        output.push_str(&format!("  ipv6: {}\n", dns.ipv6));
+        output.push_str(&format!("  prefer-h3: {}\n", dns.prefer_h3));
+        if let Some(respect_rules) = dns.respect_rules {
+            output.push_str(&format!("  respect-rules: {}\n", respect_rules));
+        }
+        if let Some(use_hosts) = dns.use_hosts {
+            output.push_str(&format!("  use-hosts: {}\n", use_hosts));
+        }
+        if let Some(use_system_hosts) = dns.use_system_hosts {
+            output.push_str(&format!("  use-system-hosts: {}\n", use_system_hosts));
+        }
+        output.push_str(&format!("  enhanced-mode: {}\n", dns.enhanced_mode));
+        output.push_str(&format!("  fake-ip-range: {}\n", dns.fake_ip_range));
+        if let Some(filter) = &dns.fake_ip_filter {
+            output.push_str("  fake-ip-filter:\n");
+            for f in filter {
+                output.push_str(&format!("    - \"{}\"\n", f));
+            }
+        }
+        output.push_str("  default-nameserver:\n");
+        for ns in &dns.default_nameserver {
+            output.push_str(&format!("    - {}\n", ns));
+        }
+        output.push_str("  nameserver:\n");
+        for ns in &dns.nameserver {
+            output.push_str(&format!("    - {}\n", ns));
+        }
+        if let Some(proxy_server_nameserver) = &dns.proxy_server_nameserver {
+            output.push_str("  proxy-server-nameserver:\n");
+            for ns in proxy_server_nameserver {
+                output.push_str(&format!("    - {}\n", ns));
+            }
+        }
+        if let Some(fallback) = &dns.fallback {
+            output.push_str("  fallback:\n");
+            for ns in fallback {
+                output.push_str(&format!("    - {}\n", ns));
+            }
+        }
+        if let Some(ff) = &dns.fallback_filter {
+            output.push_str("  fallback-filter:\n");
+            output.push_str(&format!("    geoip: {}\n", ff.geoip));
+            output.push_str(&format!("    geoip-code: {}\n", ff.geoip_code));
+            if let Some(geosite) = &ff.geosite {
+                output.push_str("    geosite:\n");
+                for gs in geosite {
+                    output.push_str(&format!("      - {}\n", gs));
                 }
-                if let Some(header) = &rp.header {
-                    if !header.is_empty() {
-                        output.push_str("    header:\n");
-                        for (k, v) in header {
-                            let vv = serde_yaml::Value::String(v.clone());
-                            output.push_str(&format!(
-                                "      {}: {}\n",
-                                k,
-                                format_yaml_value_simple(&vv)
-                            ));
-                        }
-                    }
+            }
+            output.push_str("    ipcidr:\n");
+            for cidr in &ff.ipcidr {
+                output.push_str(&format!("      - {}\n", cidr));
+            }
+            if let Some(domains) = &ff.domain {
+                output.push_str("    domain:\n");
+                for d in domains {
+                    output.push_str(&format!("      - \"{}\"\n", d));
                 }
-                if let Some(size_limit) = rp.size_limit {
-                    output.push_str(&format!("    size-limit: {}\n", size_limit));
+            }
+        }
+        if let Some(policy) = &dns.nameserver_policy {
+            output.push_str("  nameserver-policy:\n");
+            for (domain, servers) in policy {
+                output.push_str(&format!("    \"{}\":\n", domain));
+                for s in servers {
+                    output.push_str(&format!("      - {}\n", s));
                 }
-                output.push_str(&format!("    interval: {}\n", rp.interval));
             }
-            output.push('\n');
         }
+        output.push('\n');
+    }
 
-        // Rules
-        output.push_str("# 分流规则\n");
-        output.push_str("rules:\n");
-        for rule in &config.rules {
-            output.push_str(&format!("  - {}\n", rule));
+    match provider {
+        // Proxies section
+        None => {
+            output.push_str("# 代理节点\n");
+            output.push_str("proxies:\n");
+            write_proxies(&config.proxies, proxy_sources, &mut output)?;
+        }
+        // Proxies loaded from a separate provider file
+        Some((name, path)) => {
+            output.push_str("# 代理集合\n");
+            output.push_str("proxy-providers:\n");
+            output.push_str(&format!("  {}:\n", name));
+            output.push_str("    type: file\n");
+            output.push_str(&format!("    path: \"{}\"\n", path));
+            output.push_str("    health-check:\n");
+            output.push_str("      enable: true\n");
+            output.push_str("      url: \"http://www.gstatic.com/generate_204\"\n");
+            output.push_str("      interval: 300\n");
         }
+    }
+    output.push('\n');
+
+    write_groups_and_rules(config, &mut output)?;
 
-        // Validate: parse the generated YAML back to catch any format errors
+    if compact {
+        output = strip_comments_and_blank_lines(&output);
+    }
+
+    // Validate: parse the generated YAML back to catch any format errors
+    if validate {
         let _: serde_yaml::Value = serde_yaml::from_str(&output)?;
+    }
 
-        Ok(output)
+    Ok(output)
+}
+
+/// Replace node members of each group with `use: [provider]`. Groups holding only
+/// some of the nodes also get a `filter` so membership is unchanged: the group's own
+/// regex (from `filters`, by group name) when it selects exactly the same nodes,
+/// otherwise an exact-name alternation.
+fn use_provider_in_groups(
+    groups: &mut [serde_yaml::Value],
+    node_names: &[String],
+    provider: &str,
+    filters: &HashMap<String, String>,
+) {
+    let node_set: HashSet<&str> = node_names.iter().map(String::as_str).collect();
+    for group in groups {
+        let serde_yaml::Value::Mapping(map) = group else {
+            continue;
+        };
+        let group_filter = map
+            .get("name")
+            .and_then(|n| n.as_str())
+            .and_then(|n| filters.get(n));
+        let Some(serde_yaml::Value::Sequence(members)) = map.get("proxies") else {
+            continue;
+        };
+        let (nodes, others): (Vec<_>, Vec<_>) = members
+            .iter()
+            .cloned()
+            .partition(|m| m.as_str().is_some_and(|n| node_set.contains(n)));
+        if nodes.is_empty() {
+            continue;
+        }
+
+        if others.is_empty() {
+            map.remove("proxies");
+        } else {
+            map.insert("proxies".into(), serde_yaml::Value::Sequence(others));
+        }
+        map.insert(
+            "use".into(),
+            serde_yaml::Value::Sequence(vec![provider.into()]),
+        );
+        if nodes.len() < node_set.len() {
+            let members: HashSet<&str> = nodes.iter().filter_map(|n| n.as_str()).collect();
+            let own_filter = group_filter.filter(|pattern| {
+                regex::Regex::new(pattern).is_ok_and(|re| {
+                    node_names
+                        .iter()
+                        .all(|n| re.is_match(n) == members.contains(n.as_str()))
+                })
+            });
+            let filter = match own_filter {
+                Some(pattern) => pattern.clone(),
+                None => {
+                    let names: Vec<String> = nodes
+                        .iter()
+                        .filter_map(|n| n.as_str())
+                        .map(regex::escape)
+                        .collect();
+                    format!("^(?:{})$", names.join("|"))
+                }
+            };
+            map.insert("filter".into(), filter.into());
+        }
+    }
+}
+
+/// Drop full-line `#` comments and blank lines (every emitted value is single-line)
+fn strip_comments_and_blank_lines(yaml: &str) -> String {
+    let mut output = String::with_capacity(yaml.len());
+    for line in yaml.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+    output
+}
+
+/// Write proxy list items, each preceded by a `# from: <source>` comment when its
+/// source subscription is known
+fn write_proxies(
+    proxies: &[serde_yaml::Value],
+    proxy_sources: &HashMap<String, String>,
+    output: &mut String,
+) -> Result<(), serde_yaml::Error> {
+    for proxy in proxies {
+        let source = proxy
+            .get("name")
+            .and_then(|n| n.as_str())
+            .and_then(|name| proxy_sources.get(name));
+        if let Some(source) = source {
+            output.push_str(&format!(
+                "  # from: {}\n",
+                source.replace(['\r', '\n'], " ")
+            ));
+        }
+        output.push_str(&format_proxy_yaml(proxy)?);
     }
+    Ok(())
+}
+
+/// Write the proxy-groups, rule-providers and rules sections
+fn write_groups_and_rules(
+    config: &ClashConfig,
+    output: &mut String,
+) -> Result<(), serde_yaml::Error> {
+    // Proxy groups
+    output.push_str("# 策略组\n");
+    output.push_str("proxy-groups:\n");
+    for group in &config.proxy_groups {
+        output.push_str(&format_group_yaml(group)?);
+    }
+    output.push('\n');
+
+    // Rules - if rule-providers exist, output them but also add fallback inline rules
+    if !config.rule_providers.is_empty() {
+        output.push_str("# 规则集\n");
+        output.push_str("rule-providers:\n");
+        for rp in &config.rule_providers {
+            output.push_str(&format!("  {}:\n", rp.name));
+            output.push_str(&format!("    type: {}\n", rp.provider_type));
+            output.push_str(&format!("    behavior: {}\n", rp.behavior));
+            output.push_str(&format!("    url: \"{}\"\n", rp.url));
+            if let Some(fmt) = &rp.format {
+                output.push_str(&format!("    format: {}\n", fmt));
+            }
+            if let Some(path) = &rp.path {
+                output.push_str(&format!("    path: \"{}\"\n", path));
+            }
+            if let Some(proxy) = &rp.proxy {
+                let v = serde_yaml::Value::String(proxy.clone());
+                output.push_str(&format!("    proxy: {}\n", format_yaml_value_simple(&v)));
+            }
+            if let Some(header) = &rp.header {
+                if !header.is_empty() {
+                    output.push_str("    header:\n");
+                    for (k, v) in header {
+                        let vv = serde_yaml::Value::String(v.clone());
+                        output.push_str(&format!(
+                            "      {}: {}\n",
+                            k,
+                            format_yaml_value_simple(&vv)
+                        ));
+                    }
+                }
+            }
+            if let Some(size_limit) = rp.size_limit {
+                output.push_str(&format!("    size-limit: {}\n", size_limit));
+            }
+            output.push_str(&format!("    interval: {}\n", rp.interval));
+        }
+        output.push('\n');
+    }
+
+    // Rules
+    output.push_str("# 分流规则\n");
+    output.push_str("rules:\n");
+    for rule in &config.rules {
+        output.push_str(&format!("  - {}\n", rule));
+    }
+
+    Ok(())
 }
 
 /// Derive a rule-provider name from a URL
@@ -966,6 +2099,12 @@ fn apply_rule_provider_path_template(template: &str, name: &str, ext: &str) -> S
     format!("{}{}.{}", base, name, ext)
 }
 
+/// YAML fragment (`  - name: ...`) for a single node, exactly as emitted under `proxies:`
+pub fn node_proxy_yaml(node: &Node) -> Result<String, serde_yaml::Error> {
+    let proxy = serde_yaml::to_value(node.to_clash_proxy())?;
+    format_proxy_yaml(&proxy)
+}
+
 /// Format a single proxy node to YAML with proper indentation and quoting
 fn format_proxy_yaml(proxy: &serde_yaml::Value) -> Result<String, serde_yaml::Error> {
     let mut output = String::new();
@@ -1074,6 +2213,39 @@ fn format_proxy_yaml(proxy: &serde_yaml::Value) -> Result<String, serde_yaml::Er
                         }
                     }
                 }
+                "smux" | "amnezia-wg-option" | "obfs-opts" | "xhttp-opts" => {
+                    output.push_str(&format!("{}{}:\n", indent, key_str));
+                    if let serde_yaml::Value::Mapping(opts) = value {
+                        for (k, v) in opts {
+                            output.push_str(&format!(
+                                "      {}: {}\n",
+                                k.as_str().unwrap_or(""),
+                                format_yaml_value(v)
+                            ));
+                        }
+                    }
+                }
+                "peers" => {
+                    // WireGuard multi-peer list: one mapping per peer
+                    output.push_str(&format!("{}peers:\n", indent));
+                    if let serde_yaml::Value::Sequence(peers) = value {
+                        for peer in peers {
+                            if let serde_yaml::Value::Mapping(fields) = peer {
+                                let mut first_field = true;
+                                for (k, v) in fields {
+                                    let prefix = if first_field { "      - " } else { "        " };
+                                    first_field = false;
+                                    output.push_str(&format!(
+                                        "{}{}: {}\n",
+                                        prefix,
+                                        k.as_str().unwrap_or(""),
+                                        format_yaml_value(v)
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                }
                 "alpn" => {
                     output.push_str(&format!("{}alpn:\n", indent));
                     if let serde_yaml::Value::Sequence(seq) = value {
@@ -1111,9 +2283,12 @@ fn format_group_yaml(group: &serde_yaml::Value) -> Result<String, serde_yaml::Er
         let interval = map.get(serde_yaml::Value::String("interval".to_string()));
         let timeout = map.get(serde_yaml::Value::String("timeout".to_string()));
         let tolerance = map.get(serde_yaml::Value::String("tolerance".to_string()));
+        let lazy = map.get(serde_yaml::Value::String("lazy".to_string()));
+        let expected_status = map.get(serde_yaml::Value::String("expected-status".to_string()));
         let proxies = map.get(serde_yaml::Value::String("proxies".to_string()));
 
-        // Output in correct order: name, type, url, interval, timeout, tolerance, proxies
+        // Output in correct order: name, type, url, interval, timeout, tolerance, lazy,
+        // expected-status, proxies
         if let Some(n) = name {
             output.push_str(&format!("  - name: {}\n", format_yaml_value_simple(n)));
         }
@@ -1133,6 +2308,13 @@ fn format_group_yaml(group: &serde_yaml::Value) -> Result<String, serde_yaml::Er
         if let Some(t) = tolerance {
             output.push_str(&format!("    tolerance: {}\n", format_yaml_value_simple(t)));
         }
+        if let Some(l) = lazy {
+            output.push_str(&format!("    lazy: {}\n", format_yaml_value_simple(l)));
+        }
+        // Always quoted: mihomo expects a string even for a single code like 204
+        if let Some(serde_yaml::Value::String(status)) = expected_status {
+            output.push_str(&format!("    expected-status: \"{}\"\n", status));
+        }
         // Proxies list
         if let Some(serde_yaml::Value::Sequence(seq)) = proxies {
             output.push_str("    proxies:\n");
@@ -1141,11 +2323,213 @@ fn format_group_yaml(group: &serde_yaml::Value) -> Result<String, serde_yaml::Er
                 output.push_str(&format!("      - {}\n", format_yaml_value_simple(item)));
             }
         }
+        // Proxy providers and the node-name filter applied to them
+        if let Some(serde_yaml::Value::Sequence(seq)) = map.get("use") {
+            output.push_str("    use:\n");
+            for item in seq {
+                output.push_str(&format!("      - {}\n", format_yaml_value_simple(item)));
+            }
+        }
+        if let Some(filter) = map.get("filter") {
+            output.push_str(&format!("    filter: {}\n", format_yaml_value(filter)));
+        }
     }
 
     Ok(output)
 }
 
+/// Whether a node type performs TLS certificate verification
+fn uses_tls_verification(node: &Node) -> bool {
+    match node {
+        Node::Shadowsocks(_) | Node::Ssr(_) | Node::WireGuard(_) | Node::Snell(_) => false,
+        Node::Socks5(_) | Node::Http(_) => node.tls(),
+        _ => true,
+    }
+}
+
+/// Health-check settings reconciled when same-name groups are merged
+const GROUP_TEST_KEYS: &[&str] = &[
+    "url",
+    "interval",
+    "timeout",
+    "tolerance",
+    "lazy",
+    "expected-status",
+];
+
+/// Merge proxy groups sharing a name (mihomo rejects duplicates).
+/// The first group keeps its position and settings; member lists are unioned in order.
+/// Test settings follow the same precedence: the first explicit value wins (INI groups
+/// are added before generated ones), and keys the first group lacks are taken from later
+/// duplicates. Request-level defaults such as `default_group_timeout` apply afterwards.
+fn merge_duplicate_groups(groups: &mut Vec<serde_yaml::Value>) {
+    let mut merged: IndexMap<String, serde_yaml::Value> = IndexMap::new();
+    let mut unnamed = Vec::new();
+
+    for group in groups.drain(..) {
+        let Some(name) = group
+            .get("name")
+            .and_then(|n| n.as_str())
+            .map(str::to_string)
+        else {
+            unnamed.push(group);
+            continue;
+        };
+        let Some(existing) = merged.get_mut(&name) else {
+            merged.insert(name, group);
+            continue;
+        };
+        let Some(map) = existing.as_mapping_mut() else {
+            continue;
+        };
+        for key in GROUP_TEST_KEYS {
+            if let Some(value) = group.get(*key) {
+                if !map.contains_key(*key) {
+                    map.insert((*key).into(), value.clone());
+                }
+            }
+        }
+        let extra = match group.get("proxies") {
+            Some(serde_yaml::Value::Sequence(seq)) => seq.clone(),
+            _ => continue,
+        };
+        let proxies = map
+            .entry("proxies".into())
+            .or_insert_with(|| serde_yaml::Value::Sequence(Vec::new()));
+        if let serde_yaml::Value::Sequence(seq) = proxies {
+            for member in extra {
+                if !seq.contains(&member) {
+                    seq.push(member);
+                }
+            }
+        }
+    }
+
+    groups.extend(merged.into_values());
+    groups.extend(unnamed);
+}
+
+/// Prefix every group name with its 1-based index (`01 `, `02 `, ...) and rewrite
+/// references in other groups' member lists, rule targets and rule-provider
+/// `target`/`proxy` fields to match. `GLOBAL` keeps its name since mihomo looks it up literally.
+fn number_group_names(
+    groups: &mut [serde_yaml::Value],
+    rules: &mut [String],
+    rule_providers: &mut [RuleProvider],
+) {
+    let width = groups.len().to_string().len().max(2);
+    let mut renames: HashMap<String, String> = HashMap::new();
+    for (i, group) in groups.iter().enumerate() {
+        let Some(name) = group.get("name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+        if name != "GLOBAL" {
+            renames.insert(name.to_string(), format!("{:0width$} {}", i + 1, name));
+        }
+    }
+    if renames.is_empty() {
+        return;
+    }
+
+    for group in groups.iter_mut() {
+        let serde_yaml::Value::Mapping(map) = group else {
+            continue;
+        };
+        if let Some(new_name) = map
+            .get("name")
+            .and_then(|n| n.as_str())
+            .and_then(|n| renames.get(n))
+        {
+            map.insert("name".into(), serde_yaml::Value::String(new_name.clone()));
+        }
+        if let Some(serde_yaml::Value::Sequence(members)) = map.get_mut("proxies") {
+            for member in members.iter_mut() {
+                if let Some(new_name) = member.as_str().and_then(|m| renames.get(m)) {
+                    *member = serde_yaml::Value::String(new_name.clone());
+                }
+            }
+        }
+    }
+
+    // Only the target is renamed; a payload that happens to equal a group name stays
+    for rule in rules.iter_mut() {
+        let mut fields: Vec<&str> = rule.split(',').collect();
+        let Some(target) = rule_target_index(&fields) else {
+            continue;
+        };
+        if let Some(new_name) = renames.get(fields[target].trim()) {
+            fields[target] = new_name;
+            *rule = fields.join(",");
+        }
+    }
+
+    for rp in rule_providers.iter_mut() {
+        if let Some(new_name) = renames.get(&rp.target) {
+            rp.target = new_name.clone();
+        }
+        if let Some(new_name) = rp.proxy.as_deref().and_then(|p| renames.get(p)) {
+            rp.proxy = Some(new_name.clone());
+        }
+    }
+}
+
+/// Index of the policy in a comma-split rule: 1 for `MATCH,target`, otherwise the
+/// last field before trailing options (`TYPE,payload,target[,no-resolve]`, which also
+/// covers logical rules whose payload contains commas)
+fn rule_target_index(fields: &[&str]) -> Option<usize> {
+    match fields {
+        [kind, _, ..] if kind.trim() == "MATCH" => Some(1),
+        [] | [_] | [_, _] => None,
+        _ => {
+            let last = fields.len() - 1;
+            if matches!(fields[last].trim(), "no-resolve" | "src") {
+                Some(last - 1)
+            } else {
+                Some(last)
+            }
+        }
+    }
+}
+
+/// Set `key` on url-test/fallback groups that don't already have it
+fn apply_group_default(groups: &mut [serde_yaml::Value], key: &str, value: serde_yaml::Value) {
+    for group in groups {
+        let serde_yaml::Value::Mapping(map) = group else {
+            continue;
+        };
+        let is_health_checked = matches!(
+            map.get("type").and_then(|t| t.as_str()),
+            Some("url-test" | "fallback")
+        );
+        if is_health_checked && !map.contains_key(key) {
+            map.insert(key.into(), value.clone());
+        }
+    }
+}
+
+/// Append `members` to every select group that doesn't already list them
+fn append_select_members(groups: &mut [serde_yaml::Value], members: &[&str]) {
+    for group in groups {
+        let serde_yaml::Value::Mapping(map) = group else {
+            continue;
+        };
+        if map.get("type").and_then(|t| t.as_str()) != Some("select") {
+            continue;
+        }
+        let proxies = map
+            .entry("proxies".into())
+            .or_insert_with(|| serde_yaml::Value::Sequence(Vec::new()));
+        let serde_yaml::Value::Sequence(proxies) = proxies else {
+            continue;
+        };
+        for member in members {
+            if !proxies.iter().any(|p| p.as_str() == Some(member)) {
+                proxies.push((*member).into());
+            }
+        }
+    }
+}
+
 /// Format a YAML value - simple version with minimal quoting
 fn format_yaml_value_simple(value: &serde_yaml::Value) -> String {
     match value {
@@ -1217,6 +2601,14 @@ fn format_yaml_value(value: &serde_yaml::Value) -> String {
         serde_yaml::Value::Number(n) => n.to_string(),
         serde_yaml::Value::Bool(b) => b.to_string(),
         serde_yaml::Value::Null => "null".to_string(),
+        // Flow style keeps lists (allowed-ips, dns, reserved) on the key's line
+        serde_yaml::Value::Sequence(seq) => format!(
+            "[{}]",
+            seq.iter()
+                .map(format_yaml_value)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
         _ => serde_yaml::to_string(value)
             .unwrap_or_default()
             .trim()
@@ -1229,3 +2621,907 @@ impl Default for ClashConfigBuilder {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_single_link;
+
+    fn nodes(names: &[&str]) -> Vec<Node> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                parse_single_link(&format!("trojan://pw@10.0.0.{}:443#{}", i + 1, name)).unwrap()
+            })
+            .collect()
+    }
+
+    fn group<'a>(config: &'a ClashConfig, name: &str) -> &'a serde_yaml::Value {
+        config
+            .proxy_groups
+            .iter()
+            .find(|g| g.get("name").and_then(|n| n.as_str()) == Some(name))
+            .unwrap_or_else(|| panic!("no group {}", name))
+    }
+
+    fn members<'a>(config: &'a ClashConfig, name: &str) -> Vec<&'a str> {
+        group(config, name)["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|p| p.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn latency_map_orders_group_members() {
+        let nodes = nodes(&["A", "B", "C"]);
+        let latency: IndexMap<String, u64> = [("A".to_string(), 300), ("C".to_string(), 50)]
+            .into_iter()
+            .collect();
+        let config = ClashConfigBuilder::new()
+            .with_latency_sort(latency)
+            .with_nodes(&nodes)
+            .with_default_groups(&nodes)
+            .build();
+
+        // B has no measurement (unreachable) and goes last
+        assert_eq!(members(&config, "♻️ 自动选择"), ["C", "A", "B"]);
+        let proxies: Vec<&str> = config
+            .proxies
+            .iter()
+            .filter_map(|p| p["name"].as_str())
+            .collect();
+        assert_eq!(proxies, ["A", "B", "C"]);
+    }
+
+    #[test]
+    fn link_tfo_overrides_global_switch() {
+        let uuid = "11111111-2222-3333-4444-555555555555";
+        let nodes: Vec<Node> = [
+            format!("vless://{}@1.1.1.1:443?security=tls&tfo=0#off", uuid),
+            format!("vless://{}@1.1.1.2:443?security=tls#unset", uuid),
+        ]
+        .iter()
+        .map(|link| parse_single_link(link).unwrap())
+        .collect();
+        let config = ClashConfigBuilder::new()
+            .with_global_options(true, true, false)
+            .with_nodes(&nodes)
+            .build();
+        assert_eq!(config.proxies[0]["tfo"], false);
+        assert_eq!(config.proxies[1]["tfo"], true);
+    }
+
+    #[test]
+    fn default_rule_skip_flags() {
+        let rules = |options: DefaultRuleOptions| {
+            ClashConfigBuilder::new()
+                .with_default_rule_options(options)
+                .with_default_rules()
+                .build()
+                .rules
+        };
+        let all = rules(DefaultRuleOptions::default());
+        let without = |rules: Vec<String>| -> Vec<String> {
+            all.iter().filter(|r| !rules.contains(r)).cloned().collect()
+        };
+
+        let no_cn = rules(DefaultRuleOptions {
+            skip_cn_direct: true,
+            ..Default::default()
+        });
+        assert_eq!(no_cn, without(vec!["GEOIP,CN,🎯 全球直连".into()]));
+
+        let no_private = rules(DefaultRuleOptions {
+            skip_private_direct: true,
+            ..Default::default()
+        });
+        assert_eq!(no_private, ["GEOIP,CN,🎯 全球直连", "MATCH,🐟 漏网之鱼"]);
+
+        let no_match = rules(DefaultRuleOptions {
+            skip_match: true,
+            ..Default::default()
+        });
+        assert_eq!(no_match, without(vec!["MATCH,🐟 漏网之鱼".into()]));
+    }
+
+    #[test]
+    fn duplicate_proxy_names_are_reported() {
+        let duplicated = nodes(&["A", "B", "A", "A", "C"]);
+        let builder = ClashConfigBuilder::new().with_nodes(&duplicated);
+        assert_eq!(builder.duplicate_proxy_names(), ["A"]);
+
+        let yaml = builder.build_yaml().unwrap();
+        let err = validate_yaml_strict(&yaml).unwrap_err();
+        assert!(err.to_string().contains("duplicate proxy or group name: A"));
+
+        let unique = nodes(&["A", "B"]);
+        assert!(ClashConfigBuilder::new()
+            .with_nodes(&unique)
+            .duplicate_proxy_names()
+            .is_empty());
+    }
+
+    #[test]
+    fn compact_output_has_no_comments_and_round_trips() {
+        let nodes = nodes(&["A", "B"]);
+        let build = |compact: bool| {
+            let mut builder = ClashConfigBuilder::new().with_tun();
+            if compact {
+                builder = builder.with_compact_output();
+            }
+            builder
+                .with_nodes(&nodes)
+                .with_default_groups(&nodes)
+                .with_default_rules()
+                .build_yaml()
+                .unwrap()
+        };
+        let compact = build(true);
+        let full = build(false);
+        assert!(!compact.lines().any(|l| l.trim_start().starts_with('#')));
+        assert!(!compact.lines().any(|l| l.trim().is_empty()));
+        assert!(compact.len() < full.len());
+
+        let compact: serde_yaml::Value = serde_yaml::from_str(&compact).unwrap();
+        let full: serde_yaml::Value = serde_yaml::from_str(&full).unwrap();
+        assert_eq!(compact, full);
+    }
+
+    #[test]
+    fn rule_provider_proxy_follows_behavior() {
+        let nodes = nodes(&["A"]);
+        let ini = crate::ini_parser::parse_ini_config(
+            "[custom]\n\
+             custom_proxy_group=Proxy`select`.*\n\
+             ruleset=Proxy,clash-ipcidr:https://example.com/telegram.yaml\n\
+             ruleset=DIRECT,clash-domain:https://example.com/direct.yaml\n\
+             ruleset=Proxy,clash-classic:https://example.com/misc.yaml\n\
+             ruleset=Proxy,[]FINAL\n",
+        )
+        .unwrap();
+        let proxies = |options: RuleProviderOptions| -> Vec<Option<String>> {
+            ClashConfigBuilder::new()
+                .with_rule_provider_options(options)
+                .with_ini_config(&ini, &nodes)
+                .build()
+                .rule_providers
+                .into_iter()
+                .map(|p| p.proxy)
+                .collect()
+        };
+        let by_behavior: IndexMap<String, String> = [("ipcidr".to_string(), "Proxy".to_string())]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            proxies(RuleProviderOptions {
+                proxy_by_behavior: by_behavior.clone(),
+                ..Default::default()
+            }),
+            [Some("Proxy".to_string()), None, None]
+        );
+        assert_eq!(
+            proxies(RuleProviderOptions {
+                proxy: Some("DIRECT".into()),
+                proxy_by_behavior: by_behavior,
+                ..Default::default()
+            }),
+            [
+                Some("Proxy".to_string()),
+                Some("DIRECT".to_string()),
+                Some("DIRECT".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn auto_group_takes_interval_and_tolerance() {
+        let nodes = nodes(&["A", "B"]);
+        let yaml = ClashConfigBuilder::new()
+            .with_auto_group_options(Some(120), Some(50))
+            .with_nodes(&nodes)
+            .with_default_groups(&nodes)
+            .build_yaml()
+            .unwrap();
+        let config: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let auto = config["proxy-groups"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .find(|g| g["name"] == "♻️ 自动选择")
+            .unwrap();
+        assert_eq!(auto["type"], "url-test");
+        assert_eq!(auto["tolerance"], 50);
+        assert_eq!(auto["interval"], 120);
+        assert!(yaml.contains("tolerance: 50"));
+
+        let default = ClashConfigBuilder::new()
+            .with_default_groups(&nodes)
+            .build();
+        let auto = group(&default, "♻️ 自动选择");
+        assert_eq!(auto["interval"], 300);
+        assert!(auto.get("tolerance").is_none());
+    }
+
+    #[test]
+    fn rule_cap_keeps_match_last() {
+        let rules: Vec<String> = (1..=10)
+            .map(|i| format!("DOMAIN-SUFFIX,site{}.com,DIRECT", i))
+            .chain(["MATCH,Proxy".to_string()])
+            .collect();
+        let mut builder = ClashConfigBuilder::new().with_rules(rules.clone());
+        assert_eq!(builder.truncate_rules(4), 7);
+        assert_eq!(
+            builder.build().rules,
+            [
+                "DOMAIN-SUFFIX,site1.com,DIRECT",
+                "DOMAIN-SUFFIX,site2.com,DIRECT",
+                "DOMAIN-SUFFIX,site3.com,DIRECT",
+                "MATCH,Proxy",
+            ]
+        );
+
+        let mut builder = ClashConfigBuilder::new().with_rules(rules[..10].to_vec());
+        assert_eq!(builder.truncate_rules(4), 6);
+        assert_eq!(
+            builder.build().rules.last().unwrap(),
+            "DOMAIN-SUFFIX,site4.com,DIRECT"
+        );
+
+        let mut builder = ClashConfigBuilder::new().with_rules(rules);
+        assert_eq!(builder.truncate_rules(20), 0);
+    }
+
+    #[test]
+    fn rule_target_rewrite_maps_a_group_to_direct() {
+        let nodes = nodes(&["A", "B"]);
+        let builder = || {
+            ClashConfigBuilder::new()
+                .with_nodes(&nodes)
+                .with_default_groups(&nodes)
+                .with_default_rules()
+        };
+        let mut rewrite = IndexMap::new();
+        rewrite.insert("🎯 全球直连".to_string(), "DIRECT".to_string());
+
+        let mut direct = builder();
+        assert!(direct.rewrite_rule_targets(&rewrite).is_empty());
+        let config = direct.build();
+        assert!(config.rules.iter().all(|r| !r.contains("全球直连")));
+        assert!(config
+            .rules
+            .contains(&"IP-CIDR,10.0.0.0/8,DIRECT,no-resolve".to_string()));
+        assert!(config.rules.contains(&"GEOIP,CN,DIRECT".to_string()));
+        assert_eq!(
+            members(&config, "🐟 漏网之鱼"),
+            ["🔰 节点选择", "DIRECT", "♻️ 自动选择"]
+        );
+
+        rewrite.insert("🎯 全球直连".to_string(), "Missing".to_string());
+        assert_eq!(builder().rewrite_rule_targets(&rewrite), ["Missing"]);
+    }
+
+    #[test]
+    fn select_groups_gain_direct_and_reject_once() {
+        let nodes = nodes(&["A", "B"]);
+        let config = ClashConfigBuilder::new()
+            .with_nodes(&nodes)
+            .with_default_groups(&nodes)
+            .with_direct_reject_appended()
+            .build();
+        assert_eq!(
+            members(&config, "🔰 节点选择"),
+            ["♻️ 自动选择", "🎯 全球直连", "A", "B", "DIRECT", "REJECT"]
+        );
+        // Already has both, in its own order
+        assert_eq!(members(&config, "🛑 全球拦截"), ["REJECT", "DIRECT"]);
+        // url-test groups are left alone
+        assert_eq!(members(&config, "♻️ 自动选择"), ["A", "B"]);
+
+        let config = ClashConfigBuilder::new()
+            .with_nodes(&nodes)
+            .with_default_groups(&nodes)
+            .build();
+        assert!(!members(&config, "🔰 节点选择").contains(&"REJECT"));
+    }
+
+    #[test]
+    fn skipping_validation_keeps_output_identical() {
+        let nodes: Vec<Node> = (0..5000)
+            .map(|i| {
+                parse_single_link(&format!(
+                    "trojan://pw@10.{}.{}.1:443#Node-{}",
+                    i / 250,
+                    i % 250,
+                    i
+                ))
+                .unwrap()
+            })
+            .collect();
+        let builder = || {
+            ClashConfigBuilder::new()
+                .with_nodes(&nodes)
+                .with_default_groups(&nodes)
+                .with_default_rules()
+        };
+
+        let validated = builder().build_yaml().unwrap();
+        let fast = builder().without_validation().build_yaml().unwrap();
+        assert_eq!(fast, validated);
+    }
+
+    #[test]
+    fn duplicate_rules_collapse_to_first() {
+        let rules: Vec<String> = [
+            "DOMAIN-SUFFIX,example.com,Proxy",
+            "DOMAIN-SUFFIX, example.com ,Proxy",
+            "MATCH,DIRECT",
+            "DOMAIN-SUFFIX,example.com,Proxy",
+            "DOMAIN-SUFFIX,example.com,DIRECT",
+            "MATCH,DIRECT",
+        ]
+        .map(String::from)
+        .to_vec();
+        let config = ClashConfigBuilder::new().with_rules(rules.clone()).build();
+        assert_eq!(
+            config.rules,
+            [
+                "DOMAIN-SUFFIX,example.com,Proxy",
+                "DOMAIN-SUFFIX,example.com,DIRECT",
+                "MATCH,DIRECT",
+            ]
+        );
+
+        let config = ClashConfigBuilder::new()
+            .with_rules(rules.clone())
+            .with_rule_dedup(false)
+            .build();
+        assert_eq!(config.rules, rules);
+    }
+
+    #[test]
+    fn reality_node_yaml_field_order() {
+        let node = parse_single_link(
+            "vless://11111111-2222-3333-4444-555555555555@1.2.3.4:443?security=reality\
+             &sni=www.microsoft.com&fp=chrome&pbk=cHVibGljS2V5&sid=abcd&type=tcp\
+             &flow=xtls-rprx-vision#Reality",
+        )
+        .unwrap();
+        assert_eq!(
+            node_proxy_yaml(&node).unwrap(),
+            "  - name: Reality\n\
+             \x20   type: vless\n\
+             \x20   server: 1.2.3.4\n\
+             \x20   port: 443\n\
+             \x20   uuid: 11111111-2222-3333-4444-555555555555\n\
+             \x20   udp: true\n\
+             \x20   tls: true\n\
+             \x20   network: tcp\n\
+             \x20   flow: xtls-rprx-vision\n\
+             \x20   servername: www.microsoft.com\n\
+             \x20   reality-opts:\n\
+             \x20     public-key: cHVibGljS2V5\n\
+             \x20     short-id: abcd\n\
+             \x20   client-fingerprint: chrome\n"
+        );
+    }
+
+    #[test]
+    fn xhttp_reality_node_yaml_field_order() {
+        let node = parse_single_link(
+            "vless://11111111-2222-3333-4444-555555555555@1.2.3.4:443?security=reality\
+             &sni=www.microsoft.com&fp=chrome&pbk=cHVibGljS2V5&sid=abcd&type=xhttp\
+             &path=%2Fxh&host=cdn.example.com&mode=packet-up#XHTTP",
+        )
+        .unwrap();
+        assert_eq!(
+            node_proxy_yaml(&node).unwrap(),
+            "  - name: XHTTP\n\
+             \x20   type: vless\n\
+             \x20   server: 1.2.3.4\n\
+             \x20   port: 443\n\
+             \x20   uuid: 11111111-2222-3333-4444-555555555555\n\
+             \x20   udp: true\n\
+             \x20   tls: true\n\
+             \x20   network: xhttp\n\
+             \x20   servername: www.microsoft.com\n\
+             \x20   reality-opts:\n\
+             \x20     public-key: cHVibGljS2V5\n\
+             \x20     short-id: abcd\n\
+             \x20   client-fingerprint: chrome\n\
+             \x20   xhttp-opts:\n\
+             \x20     path: /xh\n\
+             \x20     host: cdn.example.com\n\
+             \x20     mode: packet-up\n"
+        );
+
+        let splithttp = parse_single_link(
+            "vless://11111111-2222-3333-4444-555555555555@1.2.3.4:443?security=tls&type=splithttp&path=%2Fxh#S",
+        )
+        .unwrap();
+        assert_eq!(splithttp.to_clash_proxy()["network"], "xhttp");
+        assert_eq!(splithttp.to_clash_proxy()["xhttp-opts"]["path"], "/xh");
+    }
+
+    #[test]
+    fn lan_options_emit_bind_address_and_cidrs() {
+        let nodes = nodes(&["A"]);
+        let yaml = ClashConfigBuilder::new()
+            .with_lan_options(
+                true,
+                Some("*".into()),
+                vec!["192.168.0.0/16".into(), "10.0.0.0/8".into()],
+                vec!["192.168.1.99/32".into()],
+            )
+            .with_nodes(&nodes)
+            .build_yaml()
+            .unwrap();
+        assert!(yaml.contains("bind-address: \"*\"\n"));
+        let config: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(config["allow-lan"], true);
+        assert_eq!(config["bind-address"], "*");
+        assert_eq!(
+            config["lan-allowed-ips"],
+            serde_yaml::from_str::<serde_yaml::Value>("[192.168.0.0/16, 10.0.0.0/8]").unwrap()
+        );
+        assert_eq!(config["lan-disallowed-ips"][0], "192.168.1.99/32");
+
+        let yaml = ClashConfigBuilder::new()
+            .with_nodes(&nodes)
+            .build_yaml()
+            .unwrap();
+        let config: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        for key in ["bind-address", "lan-allowed-ips", "lan-disallowed-ips"] {
+            assert!(config.get(key).is_none(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn sni_override_applies_to_matching_nodes() {
+        let uuid = "11111111-2222-3333-4444-555555555555";
+        let nodes: Vec<Node> = [
+            format!(
+                "vless://{}@1.1.1.1:443?security=tls&sni=old.example.com#CDN vless",
+                uuid
+            ),
+            "trojan://pw@1.1.1.2:443?sni=old.example.com#CDN trojan".to_string(),
+            "hysteria2://pw@1.1.1.3:443#Direct hy2".to_string(),
+            "ss://YWVzLTEyOC1nY206c2VjcmV0@1.1.1.4:8388#CDN ss".to_string(),
+        ]
+        .iter()
+        .map(|l| parse_single_link(l).unwrap())
+        .collect();
+        let sni = |config: &ClashConfig, i: usize, key: &str| {
+            config.proxies[i]
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+
+        let config = ClashConfigBuilder::new()
+            .with_sni_override(
+                "front.example.com".into(),
+                Some(Regex::new("^CDN").unwrap()),
+            )
+            .with_nodes(&nodes)
+            .build();
+        assert_eq!(
+            sni(&config, 0, "servername").as_deref(),
+            Some("front.example.com")
+        );
+        assert_eq!(sni(&config, 1, "sni").as_deref(), Some("front.example.com"));
+        assert_eq!(sni(&config, 2, "sni"), None);
+        assert_eq!(sni(&config, 3, "sni"), None);
+
+        let config = ClashConfigBuilder::new()
+            .with_sni_override("front.example.com".into(), None)
+            .with_nodes(&nodes)
+            .build();
+        assert_eq!(sni(&config, 2, "sni").as_deref(), Some("front.example.com"));
+    }
+
+    #[test]
+    fn dns_respect_rules_and_hosts_are_emitted() {
+        let nodes = nodes(&["A"]);
+        let yaml = ClashConfigBuilder::new()
+            .with_dns_respect_rules(true)
+            .with_dns_hosts(Some(true), Some(false))
+            .with_nodes(&nodes)
+            .with_default_groups(&nodes)
+            .build_yaml()
+            .unwrap();
+        assert!(yaml.contains("  respect-rules: true\n"));
+        assert!(yaml.contains("  use-hosts: true\n"));
+        let config: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(config["dns"]["use-system-hosts"], false);
+
+        let yaml = ClashConfigBuilder::new()
+            .with_nodes(&nodes)
+            .build_yaml()
+            .unwrap();
+        let config: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        for key in ["respect-rules", "use-hosts", "use-system-hosts"] {
+            assert!(config["dns"].get(key).is_none(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn udp_off_spares_udp_native_nodes() {
+        let nodes: Vec<Node> = [
+            "tuic://11111111-2222-3333-4444-555555555555:pw@1.1.1.1:443?sni=t.example.com#tuic",
+            "hysteria2://pw@1.1.1.2:443#hy2",
+            "trojan://pw@1.1.1.3:443#trojan",
+        ]
+        .iter()
+        .map(|l| parse_single_link(l).unwrap())
+        .collect();
+        let config = ClashConfigBuilder::new()
+            .with_global_options(false, false, false)
+            .with_nodes(&nodes)
+            .build();
+
+        let udp: Vec<Option<bool>> = config
+            .proxies
+            .iter()
+            .map(|p| p.get("udp").and_then(|v| v.as_bool()))
+            .collect();
+        assert_ne!(udp[0], Some(false));
+        assert_ne!(udp[1], Some(false));
+        assert_eq!(udp[2], Some(false));
+        assert_eq!(config.proxies[0]["type"], "tuic");
+    }
+
+    #[test]
+    fn global_smux_applies_to_compatible_nodes() {
+        let uuid = "11111111-2222-3333-4444-555555555555";
+        let nodes: Vec<Node> = [
+            "trojan://pw@1.1.1.1:443#trojan".to_string(),
+            format!("vless://{}@1.1.1.2:443?security=tls#vless", uuid),
+            format!(
+                "vless://{}@1.1.1.3:443?security=tls&flow=xtls-rprx-vision#vision",
+                uuid
+            ),
+            "ss://YWVzLTEyOC1nY206c2VjcmV0@1.1.1.4:8388#ss".to_string(),
+        ]
+        .iter()
+        .map(|l| parse_single_link(l).unwrap())
+        .collect();
+        let config = ClashConfigBuilder::new()
+            .with_global_smux(SmuxOpts {
+                protocol: Some("h2mux".into()),
+                max_connections: Some(4),
+                ..Default::default()
+            })
+            .with_nodes(&nodes)
+            .build();
+
+        let smux: Vec<bool> = config
+            .proxies
+            .iter()
+            .map(|p| p.get("smux").is_some())
+            .collect();
+        assert_eq!(smux, [true, true, false, false]);
+        let smux = &config.proxies[0]["smux"];
+        assert_eq!(smux["enabled"], true);
+        assert_eq!(smux["protocol"], "h2mux");
+        assert_eq!(smux["max-connections"], 4);
+    }
+
+    #[test]
+    fn explicit_cert_verify_marks_unset_tls_nodes() {
+        let nodes = vec![
+            parse_single_link("trojan://pw@1.1.1.1:443#plain").unwrap(),
+            parse_single_link("trojan://pw@1.1.1.2:443?allowInsecure=1#insecure").unwrap(),
+            parse_single_link("ss://YWVzLTEyOC1nY206c2VjcmV0@1.1.1.3:8388#ss").unwrap(),
+        ];
+        let skip =
+            |config: &ClashConfig, i: usize| config.proxies[i].get("skip-cert-verify").cloned();
+
+        let config = ClashConfigBuilder::new()
+            .with_explicit_cert_verify()
+            .with_nodes(&nodes)
+            .build();
+        assert_eq!(skip(&config, 0), Some(serde_yaml::Value::Bool(false)));
+        assert_eq!(skip(&config, 1), Some(serde_yaml::Value::Bool(true)));
+        assert_eq!(skip(&config, 2), None);
+
+        let config = ClashConfigBuilder::new().with_nodes(&nodes).build();
+        assert_eq!(skip(&config, 0), None);
+    }
+
+    #[test]
+    fn ini_and_region_groups_merge_by_name() {
+        let nodes = nodes(&["HK 01", "香港 02", "US 01"]);
+        let ini = crate::ini_parser::parse_ini_config(
+            "[custom]\n\
+             custom_proxy_group=🇭🇰 香港`select`01$`[]DIRECT\n\
+             ruleset=DIRECT,[]FINAL\n",
+        )
+        .unwrap();
+        let config = ClashConfigBuilder::new()
+            .with_nodes(&nodes)
+            .with_ini_config(&ini, &nodes)
+            .with_region_groups(&nodes, RegionTable::builtin())
+            .build();
+
+        let named: Vec<&serde_yaml::Value> = config
+            .proxy_groups
+            .iter()
+            .filter(|g| g["name"] == "🇭🇰 香港")
+            .collect();
+        assert_eq!(named.len(), 1);
+        assert_eq!(named[0]["type"], "select");
+        assert_eq!(
+            members(&config, "🇭🇰 香港"),
+            ["HK 01", "US 01", "DIRECT", "香港 02"]
+        );
+        assert_eq!(members(&config, "🇺🇸 美国"), ["US 01"]);
+    }
+
+    #[test]
+    fn merged_groups_keep_the_first_explicit_test_url() {
+        let mut groups: Vec<serde_yaml::Value> = serde_yaml::from_str(
+            "- { name: HK, type: url-test, url: 'https://ini.example.com/204', interval: 600, proxies: [A] }\n\
+             - { name: US, type: select, proxies: [B] }\n\
+             - { name: HK, type: url-test, url: 'http://www.gstatic.com/generate_204', interval: 300, timeout: 2000, proxies: [C] }\n\
+             - { name: US, type: url-test, url: 'http://www.gstatic.com/generate_204', interval: 300, proxies: [B, D] }",
+        )
+        .unwrap();
+        merge_duplicate_groups(&mut groups);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0]["url"], "https://ini.example.com/204");
+        assert_eq!(groups[0]["interval"], 600);
+        assert_eq!(groups[0]["timeout"], 2000);
+        assert_eq!(
+            groups[0]["proxies"],
+            serde_yaml::from_str::<serde_yaml::Value>("[A, C]").unwrap()
+        );
+        assert_eq!(groups[1]["type"], "select");
+        assert_eq!(groups[1]["url"], "http://www.gstatic.com/generate_204");
+        assert_eq!(
+            groups[1]["proxies"],
+            serde_yaml::from_str::<serde_yaml::Value>("[B, D]").unwrap()
+        );
+    }
+
+    #[test]
+    fn two_peer_wireguard_validates() {
+        let node = parse_single_link(
+            "wireguard://10.0.0.1:51820?pk=cHJpdmF0ZQ&local_address=172.16.0.2/32\
+             &peer=a.example.com:51820%3BcGVlckE%3B0.0.0.0%2F0\
+             &peer=b.example.com:2408%3BcGVlckI%3B10.0.0.0%2F8%3B1%2C2%2C3#wg",
+        )
+        .unwrap();
+        let yaml = ClashConfigBuilder::new()
+            .with_nodes(std::slice::from_ref(&node))
+            .with_default_groups(std::slice::from_ref(&node))
+            .build_yaml()
+            .unwrap();
+        validate_yaml_strict(&yaml).unwrap();
+
+        let config: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let proxy = &config["proxies"][0];
+        assert!(proxy.get("server").is_none());
+        let peers = proxy["peers"].as_sequence().unwrap();
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[0]["server"], "a.example.com");
+        assert_eq!(peers[0]["public-key"], "cGVlckE");
+        assert_eq!(peers[1]["port"], 2408);
+        assert_eq!(peers[1]["allowed-ips"][0], "10.0.0.0/8");
+        assert_eq!(peers[1]["reserved"].as_sequence().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn strict_validation_requires_server_without_peers() {
+        let yaml = "proxies:\n  - name: a\n    type: wireguard\n    port: 51820\n";
+        let err = validate_yaml_strict(yaml).unwrap_err();
+        assert!(err.to_string().contains("server"));
+    }
+
+    #[test]
+    fn strict_validation_catches_malformed_proxy() {
+        let nodes = nodes(&["A", "B"]);
+        let yaml = ClashConfigBuilder::new()
+            .with_nodes(&nodes)
+            .with_default_groups(&nodes)
+            .with_default_rules()
+            .build_yaml()
+            .unwrap();
+        validate_yaml_strict(&yaml).unwrap();
+
+        // A port that became a string still parses as a plain `Value`
+        let malformed = yaml.replacen("port: 443", "port: \"443\"", 1);
+        assert_ne!(malformed, yaml);
+        serde_yaml::from_str::<serde_yaml::Value>(&malformed).unwrap();
+        let err = validate_yaml_strict(&malformed).unwrap_err();
+        assert!(err.to_string().contains("invalid type"), "{}", err);
+    }
+
+    #[test]
+    fn numbering_rewrites_rule_targets_only() {
+        let mut groups: Vec<serde_yaml::Value> = serde_yaml::from_str(
+            "- { name: Proxy, type: select, proxies: [Netflix, DIRECT] }\n\
+             - { name: Netflix, type: select, proxies: [DIRECT] }",
+        )
+        .unwrap();
+        let mut rules: Vec<String> = [
+            "DOMAIN-KEYWORD,Netflix,Netflix",
+            "IP-CIDR,1.1.1.1/32,Proxy,no-resolve",
+            "AND,((DOMAIN,Proxy),(NETWORK,UDP)),Proxy",
+            "MATCH,Proxy",
+        ]
+        .map(String::from)
+        .to_vec();
+        let mut providers = vec![RuleProvider {
+            name: "netflix".into(),
+            url: "https://example.com/netflix.yaml".into(),
+            target: "Netflix".into(),
+            provider_type: "http".into(),
+            behavior: "domain".into(),
+            format: None,
+            path: None,
+            proxy: Some("Proxy".into()),
+            header: None,
+            size_limit: None,
+            interval: 86400,
+        }];
+        number_group_names(&mut groups, &mut rules, &mut providers);
+
+        assert_eq!(groups[0]["name"], "01 Proxy");
+        assert_eq!(groups[0]["proxies"][0], "02 Netflix");
+        assert_eq!(
+            rules,
+            [
+                "DOMAIN-KEYWORD,Netflix,02 Netflix",
+                "IP-CIDR,1.1.1.1/32,01 Proxy,no-resolve",
+                "AND,((DOMAIN,Proxy),(NETWORK,UDP)),01 Proxy",
+                "MATCH,01 Proxy",
+            ]
+        );
+        assert_eq!(providers[0].target, "02 Netflix");
+        assert_eq!(providers[0].proxy.as_deref(), Some("01 Proxy"));
+    }
+
+    #[test]
+    fn numbered_default_config_keeps_references_resolvable() {
+        let nodes = nodes(&["A", "B"]);
+        let yaml = ClashConfigBuilder::new()
+            .with_numbered_groups()
+            .with_nodes(&nodes)
+            .with_default_groups(&nodes)
+            .with_default_rules()
+            .build_yaml()
+            .unwrap();
+        let config: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let groups = config["proxy-groups"].as_sequence().unwrap();
+        let group_names: Vec<&str> = groups.iter().filter_map(|g| g["name"].as_str()).collect();
+        for (i, name) in group_names.iter().enumerate() {
+            assert!(name.starts_with(&format!("{:02} ", i + 1)), "{}", name);
+        }
+
+        let known = |name: &str| {
+            group_names.contains(&name) || ["A", "B", "DIRECT", "REJECT"].contains(&name)
+        };
+        for member in groups
+            .iter()
+            .flat_map(|g| g["proxies"].as_sequence().unwrap())
+            .filter_map(|m| m.as_str())
+        {
+            assert!(known(member), "unresolved group member {}", member);
+        }
+        for rule in config["rules"].as_sequence().unwrap() {
+            let fields: Vec<&str> = rule.as_str().unwrap().split(',').collect();
+            let target = fields[rule_target_index(&fields).unwrap()];
+            assert!(known(target), "unresolved rule target in {:?}", rule);
+        }
+    }
+
+    #[test]
+    fn split_output_keeps_ini_filters_with_numbered_groups() {
+        let ini = crate::ini_parser::parse_ini_config(
+            "[custom]\n\
+             custom_proxy_group=Select`select`[]HK`[]DIRECT\n\
+             custom_proxy_group=HK`url-test`(港|HK)`http://www.gstatic.com/generate_204`300\n\
+             ruleset=Select,[]MATCH\n",
+        )
+        .unwrap();
+        let nodes = nodes(&["HK 01", "港 02", "US 01"]);
+        let output = ClashConfigBuilder::new()
+            .with_nodes(&nodes)
+            .with_ini_config(&ini, &nodes)
+            .with_numbered_groups()
+            .build_split_yaml("nodes", "./nodes.yaml")
+            .unwrap();
+
+        let config: serde_yaml::Value = serde_yaml::from_str(&output.main_config).unwrap();
+        assert!(config.get("proxies").is_none());
+        assert!(config["proxy-providers"].get("nodes").is_some());
+        let hk = &config["proxy-groups"][1];
+        assert_eq!(hk["name"], "02 HK");
+        assert_eq!(hk["use"][0], "nodes");
+        assert_eq!(hk["filter"], "(港|HK)");
+        assert_eq!(config["proxy-groups"][0]["proxies"][0], "02 HK");
+    }
+
+    #[test]
+    fn tagged_nodes_form_tag_groups() {
+        let nodes: Vec<Node> = [
+            "trojan://pw@1.1.1.1:443#HK 01@@stream,game",
+            "trojan://pw@1.1.1.2:443#JP 01@@stream",
+            "trojan://pw@1.1.1.3:443#US 01",
+        ]
+        .iter()
+        .map(|l| parse_single_link(l).unwrap())
+        .collect();
+        let names: Vec<&str> = nodes.iter().map(|n| n.name()).collect();
+        assert_eq!(names, ["HK 01", "JP 01", "US 01"]);
+
+        let config = ClashConfigBuilder::new()
+            .with_nodes(&nodes)
+            .with_default_groups(&nodes)
+            .with_tag_groups(&nodes)
+            .build();
+        assert_eq!(group(&config, "stream")["type"], "select");
+        assert_eq!(members(&config, "stream"), ["HK 01", "JP 01"]);
+        assert_eq!(members(&config, "game"), ["HK 01"]);
+    }
+
+    #[test]
+    fn provider_groups_use_provider_with_filter() {
+        let mut groups: Vec<serde_yaml::Value> = serde_yaml::from_str(
+            "- { name: All, type: select, proxies: [HK 01, HK.02, US 01, DIRECT] }\n\
+             - { name: HK, type: url-test, proxies: [HK 01, HK.02] }\n\
+             - { name: Picked, type: select, proxies: [HK.02, US 01] }\n\
+             - { name: Outer, type: select, proxies: [All, HK] }",
+        )
+        .unwrap();
+        let node_names: Vec<String> = ["HK 01", "HK.02", "US 01"].map(String::from).to_vec();
+        let filters: HashMap<String, String> = [("HK".to_string(), "^HK".to_string())]
+            .into_iter()
+            .collect();
+        use_provider_in_groups(&mut groups, &node_names, "provider1", &filters);
+
+        let expected: Vec<serde_yaml::Value> = serde_yaml::from_str(
+            "- { name: All, type: select, proxies: [DIRECT], use: [provider1] }\n\
+             - { name: HK, type: url-test, use: [provider1], filter: '^HK' }\n\
+             - { name: Picked, type: select, use: [provider1], filter: '^(?:HK\\.02|US 01)$' }\n\
+             - { name: Outer, type: select, proxies: [All, HK] }",
+        )
+        .unwrap();
+        assert_eq!(groups, expected);
+    }
+
+    #[test]
+    fn region_groups_split_mixed_nodes() {
+        let nodes = nodes(&["香港 01", "HK 02", "日本 Tokyo", "US Seattle", "Mystery"]);
+        let config = ClashConfigBuilder::new()
+            .with_nodes(&nodes)
+            .with_default_groups(&nodes)
+            .with_region_groups(&nodes, RegionTable::builtin())
+            .build();
+
+        assert_eq!(members(&config, "🇭🇰 香港"), ["香港 01", "HK 02"]);
+        assert_eq!(members(&config, "🇯🇵 日本"), ["日本 Tokyo"]);
+        assert_eq!(members(&config, "🇺🇸 美国"), ["US Seattle"]);
+        assert_eq!(group(&config, "🇭🇰 香港")["type"], "url-test");
+        assert!(!config
+            .proxy_groups
+            .iter()
+            .any(|g| g["name"].as_str() == Some("🇸🇬 新加坡")));
+        assert_eq!(
+            members(&config, "🔰 节点选择"),
+            [
+                "♻️ 自动选择",
+                "🇭🇰 香港",
+                "🇯🇵 日本",
+                "🇺🇸 美国",
+                "🎯 全球直连",
+                "Mystery"
+            ]
+        );
+    }
+}
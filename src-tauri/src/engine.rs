@@ -2,26 +2,42 @@
 //! Orchestrates fetching, parsing, filtering, and YAML generation
 
 use regex::Regex;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::OnceLock;
 use url::Url;
 
 use indexmap::IndexMap;
 
-use crate::clash_config::{ClashConfigBuilder, RuleProviderOptions};
+use crate::clash_config::{
+    validate_yaml_strict, ClashConfigBuilder, DefaultRuleOptions, RuleProviderOptions,
+};
 use crate::error::{ConvertError, Result};
-use crate::filter::{deduplicate_nodes, filter_nodes, rename_nodes};
-use crate::http_client::{HttpClient, SubscriptionInfo};
-use crate::ini_parser::parse_ini_config;
-use crate::node::Node;
-use crate::parser::parse_subscription_content;
+use crate::filter::{
+    compile_optional_regex, dedup_node_names, deduplicate_nodes, deduplicate_nodes_by_endpoint,
+    drop_info_nodes, filter_by_server_allowlist, filter_nodes, find_credential_conflicts,
+    limit_nodes, rename_by_map, rename_nodes, truncate_node_names, DEFAULT_INFO_NODE_PATTERNS,
+};
+use crate::http_client::{HttpClient, SubscriptionInfo, DEFAULT_MAX_BODY_BYTES, DEFAULT_RETRIES};
+use crate::ini_parser::{parse_ini_config, IniPreview, ParsedIniConfig, ProxyMatcher};
+use crate::node::{nodes_to_csv, Node, SmuxOpts};
+use crate::parser::{is_clash_yaml, is_http_proxy_link, parse_subscription_content_filtered};
+use crate::passthrough::{parse_clash_document, rewrite_clash_document};
+use crate::region::RegionTable;
+use crate::surge_config::build_surge_proxies;
 
 /// Conversion request from frontend
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ConvertRequest {
     /// Subscription content (links or URLs, one per line)
     pub subscription: String,
 
+    /// Structured subscription URLs; disabled ones are skipped (not fetched).
+    /// Combined with `subscription`.
+    #[serde(default)]
+    pub subscription_sources: Vec<SubscriptionSource>,
+
     /// Remote INI config URL (optional)
     #[serde(default)]
     pub ini_url: Option<String>,
@@ -30,6 +46,32 @@ pub struct ConvertRequest {
     #[serde(default)]
     pub ini_content: Option<String>,
 
+    /// Assume TLS for VLESS/VMess on 443/8443 with an SNI/Host but no TLS setting
+    #[serde(default)]
+    pub infer_tls_on_443: bool,
+
+    /// How nodes from multiple sources are ordered (sequential or round-robin)
+    #[serde(default)]
+    pub merge_order: MergeOrder,
+
+    /// Treat nodes sharing protocol, server and port as duplicates even when their
+    /// credentials or transport differ (keeps the first)
+    #[serde(default)]
+    pub dedup_ignore_transport: bool,
+
+    /// Protocols to skip at parse time (e.g. `vmess`, `hy2`); their links are never parsed
+    #[serde(default)]
+    pub disabled_protocols: Vec<String>,
+
+    /// Keep only nodes whose server matches one of these host globs (`*.trusted.com`)
+    /// or regexes; empty keeps all nodes
+    #[serde(default)]
+    pub server_allowlist: Vec<String>,
+
+    /// Emit a `# from: <source>` comment (URL host or `inline`) above each proxy
+    #[serde(default)]
+    pub source_comments: bool,
+
     /// Include nodes matching this regex
     #[serde(default)]
     pub include_regex: Option<String>,
@@ -46,6 +88,24 @@ pub struct ConvertRequest {
     #[serde(default)]
     pub rename_replacement: Option<String>,
 
+    /// Exact-name rename table (`old -> new`), applied before the regex rename
+    #[serde(default)]
+    #[schemars(with = "Option<std::collections::HashMap<String, String>>")]
+    pub rename_map: Option<IndexMap<String, String>>,
+
+    /// Shorten node names longer than this many characters (ending in `…`, kept unique)
+    #[serde(default)]
+    pub max_name_len: Option<usize>,
+
+    /// Drop nodes whose name repeats an earlier node's instead of renaming them
+    /// with a ` 2`, ` 3`, ... suffix
+    #[serde(default)]
+    pub dedup_by_name: bool,
+
+    /// Keep only the first N nodes after filtering, dedup and renaming
+    #[serde(default)]
+    pub max_nodes: Option<usize>,
+
     /// Request timeout in seconds
     #[serde(default = "default_timeout")]
     pub timeout_secs: u64,
@@ -54,11 +114,74 @@ pub struct ConvertRequest {
     #[serde(default)]
     pub enable_tun: bool,
 
+    /// If the input is a full Clash YAML config, only filter/rename its proxies
+    /// and keep the original groups, rules and DNS
+    #[serde(default)]
+    pub keep_original_structure: bool,
+
+    /// Emit `skip-cert-verify: false` explicitly on TLS nodes that don't set it
+    #[serde(default)]
+    pub explicit_cert_verify: bool,
+
+    /// Drop airport info entries (remaining traffic, expiry, website) that aren't real proxies
+    #[serde(default = "default_true")]
+    pub drop_info_nodes: bool,
+
+    /// Regex patterns identifying info entries (defaults to `DEFAULT_INFO_NODE_PATTERNS`)
+    #[serde(default)]
+    pub info_node_patterns: Option<Vec<String>>,
+
+    /// Omit comment headers and blank separator lines from the YAML
+    #[serde(default)]
+    pub compact: bool,
+
+    /// Skip the final parse of the generated YAML (faster on very large configs)
+    #[serde(default)]
+    pub skip_validation: bool,
+
+    /// Re-read the generated YAML into typed config structs and fail on any type
+    /// mismatch (e.g. a port that became a string)
+    #[serde(default)]
+    pub strict_validate: bool,
+
+    /// Add one DIRECT-only group per fetched subscription, named with its
+    /// remaining traffic / expiry (from the `subscription-userinfo` header)
+    #[serde(default)]
+    pub subscription_info_groups: bool,
+
+    /// Output shape: full config (default), proxies only, groups + rules only, CSV,
+    /// split provider files, sing-box JSON or a Surge `[Proxy]` section
+    #[serde(default)]
+    pub output_format: OutputFormat,
+
+    /// Base config YAML to inject proxies, groups and rules into (full output only)
+    #[serde(default)]
+    pub base_config: Option<String>,
+
+    /// Proxy mode: rule (default), global, direct
+    #[serde(default)]
+    pub mode: Option<String>,
+
+    /// TUN-only output: `mixed-port: 0` and fake-ip DNS (requires `enable_tun`)
+    #[serde(default)]
+    pub tun_only: bool,
+
+    /// Extra fetch attempts on transient failures (applies to subscriptions and INI, default 2;
+    /// 0 disables retrying). Also accepted as `retries`: the retry-with-backoff option and
+    /// this setting drive the same client, so they share one field and one default.
+    #[serde(default = "default_fetch_retries", alias = "retries")]
+    pub fetch_retries: u32,
+
+    /// Largest accepted response body in MiB (default 10); bigger fetches are aborted
+    #[serde(default)]
+    pub max_response_mb: Option<u32>,
+
     /// Custom User-Agent for fetching subscriptions
     #[serde(default)]
     pub custom_user_agent: Option<String>,
 
-    /// Enable UDP for all nodes (global switch)
+    /// Enable UDP for all nodes (global switch). When off, `udp: false` is set on TCP-based
+    /// nodes only; Hysteria/Hysteria2/TUIC/WireGuard keep UDP
     #[serde(default = "default_true")]
     pub enable_udp: bool,
 
@@ -70,6 +193,75 @@ pub struct ConvertRequest {
     #[serde(default)]
     pub skip_cert_verify: bool,
 
+    /// `find-process-mode` for process-based rules: off, strict, always (omitted if unset)
+    #[serde(default)]
+    pub find_process_mode: Option<String>,
+
+    /// `dns.respect-rules`: resolve through the proxy rules (omitted if unset)
+    #[serde(default)]
+    pub dns_respect_rules: Option<bool>,
+
+    /// Set both `ipv6` and `dns.ipv6`
+    #[serde(default)]
+    pub enable_ipv6: bool,
+
+    /// `dns.use-hosts`: answer from the config's `hosts` (omitted if unset)
+    #[serde(default)]
+    pub dns_use_hosts: Option<bool>,
+
+    /// `dns.use-system-hosts`: answer from the OS hosts file (omitted if unset)
+    #[serde(default)]
+    pub dns_use_system_hosts: Option<bool>,
+
+    /// `geodata-mode`: true uses geoip.dat/geosite.dat instead of mmdb (omitted if unset)
+    #[serde(default)]
+    pub geodata_mode: Option<bool>,
+
+    /// `geo-auto-update`: periodically refresh geo databases (omitted if unset)
+    #[serde(default)]
+    pub geo_auto_update: Option<bool>,
+
+    /// `geo-update-interval` in hours (omitted if unset)
+    #[serde(default)]
+    pub geo_update_interval: Option<u32>,
+
+    /// `allow-lan`: accept connections from other devices
+    #[serde(default = "default_true")]
+    pub allow_lan: bool,
+
+    /// `bind-address` for LAN access, e.g. `*` or a local IP (omitted if unset)
+    #[serde(default)]
+    pub bind_address: Option<String>,
+
+    /// `lan-allowed-ips`: client CIDRs allowed when LAN access is on
+    #[serde(default)]
+    pub lan_allowed_ips: Vec<String>,
+
+    /// `lan-disallowed-ips`: client CIDRs always refused
+    #[serde(default)]
+    pub lan_disallowed_ips: Vec<String>,
+
+    /// Emit `unified-delay: true` (latency excludes handshake overhead)
+    #[serde(default = "default_true")]
+    pub unified_delay: bool,
+
+    /// Emit `tcp-concurrent: true` (race all resolved IPs)
+    #[serde(default = "default_true")]
+    pub tcp_concurrent: bool,
+
+    /// Multiplex (`smux`) applied to VLESS/VMess/Trojan nodes without their own (optional)
+    #[serde(default)]
+    pub global_smux: Option<SmuxOpts>,
+
+    /// Force this SNI (`servername`/`sni`) on VLESS/VMess/Trojan/Hysteria2/TUIC nodes,
+    /// e.g. for domain fronting (optional)
+    #[serde(default)]
+    pub sni_override: Option<String>,
+
+    /// Only override the SNI of nodes whose name matches this regex (optional)
+    #[serde(default)]
+    pub sni_override_regex: Option<String>,
+
     /// Override VLESS Reality short-id for all parsed nodes (optional)
     #[serde(default)]
     pub vless_reality_short_id_override: Option<String>,
@@ -86,6 +278,12 @@ pub struct ConvertRequest {
     #[serde(default)]
     pub rule_provider_proxy: Option<String>,
 
+    /// Per-behavior proxy for rule-providers download (`domain`/`ipcidr`/`classical` -> group).
+    /// Behaviors not listed fall back to `rule_provider_proxy`.
+    #[serde(default)]
+    #[schemars(with = "Option<std::collections::HashMap<String, String>>")]
+    pub rule_provider_proxy_by_behavior: Option<IndexMap<String, String>>,
+
     /// Extra headers for rule-providers download (optional)
     /// Format: "Header: value" per line
     #[serde(default)]
@@ -102,20 +300,252 @@ pub struct ConvertRequest {
     /// Custom rule-providers path template (supports {name}, {ext})
     #[serde(default)]
     pub rule_provider_path_template: Option<String>,
+
+    /// Node latency in ms keyed by node name (from `test_node_latency`).
+    /// When present, proxy-group members are ordered fastest-first.
+    #[serde(default)]
+    #[schemars(with = "Option<std::collections::HashMap<String, u64>>")]
+    pub latency_map: Option<IndexMap<String, u64>>,
+
+    /// Default test timeout (ms) for url-test/fallback groups without their own
+    #[serde(default)]
+    pub default_group_timeout: Option<u32>,
+
+    /// Default `lazy` for url-test/fallback groups without their own (omitted if unset)
+    #[serde(default)]
+    pub default_group_lazy: Option<bool>,
+
+    /// Default `expected-status` (e.g. `204`, `200/204`, `200-299`) for url-test/fallback
+    /// groups without their own
+    #[serde(default)]
+    pub default_expected_status: Option<String>,
+
+    /// Test interval (s) for the default auto url-test group (no-INI mode)
+    #[serde(default)]
+    pub auto_interval: Option<u32>,
+
+    /// Tolerance (ms) for the default auto url-test group (no-INI mode)
+    #[serde(default)]
+    pub auto_tolerance: Option<u32>,
+
+    /// Drop repeated rules (first occurrence kept, MATCH kept last); turn off when
+    /// duplicates are intentional
+    #[serde(default = "default_true")]
+    pub dedup_rules: bool,
+
+    /// Cap on inline rules; extra rules are dropped from the end, keeping MATCH last
+    #[serde(default)]
+    pub max_rules: Option<usize>,
+
+    /// Rule target rewrite table (`old → new`, e.g. `🎯 全球直连 → DIRECT`), applied to
+    /// rules and group members after the config is built
+    #[serde(default)]
+    #[schemars(with = "Option<std::collections::HashMap<String, String>>")]
+    pub rule_target_rewrite: Option<IndexMap<String, String>>,
+
+    /// Default groups (no INI): one list of all nodes, or split into per-region
+    /// `url-test` groups
+    #[serde(default)]
+    pub group_mode: GroupMode,
+
+    /// Extra region keywords (region code → keywords) for `group_mode: region`,
+    /// checked before the built-in table
+    #[serde(default)]
+    #[schemars(with = "Option<std::collections::HashMap<String, Vec<String>>>")]
+    pub region_keywords: Option<IndexMap<String, Vec<String>>>,
+
+    /// Prefix group names with `01 `, `02 `, ... to keep their order in dashboards
+    #[serde(default)]
+    pub number_groups: bool,
+
+    /// Append `DIRECT` and `REJECT` to every select group that lacks them
+    #[serde(default)]
+    pub append_direct_reject: bool,
+
+    /// Omit the default `GEOIP,CN` direct rule (no-INI mode)
+    #[serde(default)]
+    pub skip_cn_direct: bool,
+
+    /// Omit the default local/private network direct rules (no-INI mode)
+    #[serde(default)]
+    pub skip_private_direct: bool,
+
+    /// Omit the default final `MATCH` rule (no-INI mode)
+    #[serde(default)]
+    pub skip_match: bool,
 }
 
 fn default_timeout() -> u64 {
     30
 }
 
+fn default_fetch_retries() -> u32 {
+    DEFAULT_RETRIES
+}
+
 fn default_true() -> bool {
     true
 }
 
+/// Proxy provider name and file path used by `OutputFormat::Split`
+const SPLIT_PROVIDER_NAME: &str = "subscription";
+const SPLIT_PROVIDER_PATH: &str = "./providers/subscription.yaml";
+
+/// How nodes from several subscription sources are combined. Sources are the
+/// fetched URLs in input order, then pasted links; dedup keeps the first occurrence.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeOrder {
+    /// All nodes of the first source, then the second, ...
+    #[default]
+    Sequential,
+    /// One node from each source in turn
+    RoundRobin,
+}
+
+/// How the default groups (no INI config) arrange the nodes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupMode {
+    /// `🔰 节点选择` and `♻️ 自动选择` over all nodes
+    #[default]
+    Default,
+    /// One `url-test` group per detected region, selected from `🔰 节点选择`
+    Region,
+}
+
+/// Shape of the generated YAML
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Complete config (settings, DNS, proxies, groups, rules); also accepted as `clash`
+    #[default]
+    #[serde(alias = "clash")]
+    Full,
+    /// Only the `proxies:` block
+    ProxiesOnly,
+    /// Only the `proxy-groups:`, `rule-providers:` and `rules:` sections
+    GroupsRulesOnly,
+    /// Node list as CSV (name, protocol, server, port, tls, network)
+    Csv,
+    /// Main config in `yaml` loading the proxies from `providers_file` via `proxy-providers`
+    Split,
+    /// sing-box JSON: proxies and groups as `outbounds`, `MATCH` as `route.final`
+    #[serde(rename = "singbox")]
+    SingBox,
+    /// Surge `[Proxy]` section (nodes Surge can't represent are skipped)
+    Surge,
+}
+
+impl ConvertRequest {
+    /// JSON Schema describing the request shape
+    pub fn json_schema() -> serde_json::Value {
+        serde_json::to_value(schemars::schema_for!(ConvertRequest)).unwrap_or_default()
+    }
+
+    /// `subscription` followed by the URLs of enabled `subscription_sources`, one per line
+    pub fn effective_subscription(&self) -> String {
+        let mut lines = vec![self.subscription.trim().to_string()];
+        lines.extend(
+            self.subscription_sources
+                .iter()
+                .filter(|source| source.enabled)
+                .map(|source| source.url.trim().to_string()),
+        );
+        lines.retain(|line| !line.is_empty());
+        lines.join("\n")
+    }
+}
+
+/// A subscription URL that can be muted without removing it
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SubscriptionSource {
+    pub url: String,
+    /// Disabled sources are skipped entirely
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Label used instead of the URL host (subscription info groups, source comments)
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Keep only this source's nodes whose name matches this regex
+    #[serde(default)]
+    pub include: Option<String>,
+    /// Drop this source's nodes whose name matches this regex
+    #[serde(default)]
+    pub exclude: Option<String>,
+}
+
+/// Shared subscription list: sources with their own filters plus conversion settings
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscriptionManifest {
+    pub sources: Vec<SubscriptionSource>,
+    /// `ConvertRequest` fields applied to the whole conversion
+    #[serde(default)]
+    pub settings: serde_json::Map<String, serde_json::Value>,
+}
+
+impl SubscriptionManifest {
+    /// Parse a JSON or YAML manifest
+    pub fn parse(manifest: &str) -> Result<Self> {
+        serde_yaml::from_str(manifest)
+            .map_err(|e| ConvertError::Internal(format!("Invalid manifest: {}", e)))
+    }
+
+    /// Conversion request for the manifest: its settings, with the manifest sources
+    /// appended to any `subscription_sources` the settings already list
+    pub fn into_request(self) -> Result<ConvertRequest> {
+        let mut settings = self.settings;
+        settings
+            .entry("subscription")
+            .or_insert_with(|| serde_json::Value::from(""));
+        let mut request: ConvertRequest =
+            serde_json::from_value(serde_json::Value::Object(settings))
+                .map_err(|e| ConvertError::Internal(format!("Invalid manifest settings: {}", e)))?;
+        request.subscription_sources.extend(self.sources);
+        Ok(request)
+    }
+}
+
+/// Per-source options of a fetched subscription URL
+#[derive(Debug, Default, Clone)]
+struct SourceOptions {
+    name: Option<String>,
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+}
+
+impl SourceOptions {
+    /// Options of every enabled source that sets any, keyed by trimmed URL
+    fn from_sources(sources: &[SubscriptionSource]) -> Result<HashMap<String, SourceOptions>> {
+        let mut options = HashMap::new();
+        for source in sources.iter().filter(|s| s.enabled) {
+            let opts = SourceOptions {
+                name: normalize_non_empty(source.name.as_deref()),
+                include: compile_optional_regex(source.include.as_deref())?,
+                exclude: compile_optional_regex(source.exclude.as_deref())?,
+            };
+            if opts.name.is_some() || opts.include.is_some() || opts.exclude.is_some() {
+                options.insert(source.url.trim().to_string(), opts);
+            }
+        }
+        Ok(options)
+    }
+
+    fn has_filters(&self) -> bool {
+        self.include.is_some() || self.exclude.is_some()
+    }
+
+    /// Whether a node name passes the include/exclude filters
+    fn keeps(&self, name: &str) -> bool {
+        self.include.as_ref().is_none_or(|re| re.is_match(name))
+            && !self.exclude.as_ref().is_some_and(|re| re.is_match(name))
+    }
+}
+
 /// Conversion result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConvertResult {
-    /// Generated YAML config
+    /// Generated YAML config (CSV text for `OutputFormat::Csv`, JSON for `OutputFormat::SingBox`)
     pub yaml: String,
 
     /// Number of nodes parsed
@@ -136,41 +566,439 @@ pub struct ConvertResult {
     /// Subscription traffic/expiry info (if available from header)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub subscription_info: Option<SubscriptionInfo>,
+
+    /// Proxy provider file referenced by the main config (`OutputFormat::Split`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub providers_file: Option<String>,
+}
+
+/// Conversion result with the generated config as a JSON structure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClashConfigJson {
+    #[serde(flatten)]
+    pub result: ConvertResult,
+
+    /// The generated config (proxies, proxy-groups, rules, rule-providers, ...)
+    pub config: serde_json::Value,
+}
+
+/// Interleave or concatenate the lines of several sources
+fn merge_sources<T>(sources: Vec<Vec<T>>, order: MergeOrder) -> Vec<T> {
+    match order {
+        MergeOrder::Sequential => sources.into_iter().flatten().collect(),
+        MergeOrder::RoundRobin => {
+            let total = sources.iter().map(Vec::len).sum();
+            let mut iters: Vec<_> = sources.into_iter().map(Vec::into_iter).collect();
+            let mut merged = Vec::with_capacity(total);
+            while merged.len() < total {
+                for iter in &mut iters {
+                    merged.extend(iter.next());
+                }
+            }
+            merged
+        }
+    }
+}
+
+/// Content lines of one subscription source (a fetched URL or the pasted links)
+struct ResolvedSource {
+    label: String,
+    lines: Vec<String>,
+    /// Include/exclude filters applied to the parsed nodes
+    filters: Option<SourceOptions>,
+}
+
+/// Subscription content after fetching and merging all sources
+struct ResolvedSubscription {
+    content: String,
+    /// Subscription info of each fetched source that sent one, labelled by URL host
+    source_infos: Vec<(String, SubscriptionInfo)>,
+    /// The sources merged into `content`, in input order
+    sources: Vec<ResolvedSource>,
+    /// URLs that could not be fetched, with the reason
+    failed_sources: Vec<(String, String)>,
+    warnings: Vec<String>,
+}
+
+/// Short reason of a failed subscription fetch (without the repeated URL)
+fn fetch_failure_reason(error: &ConvertError) -> String {
+    match error {
+        ConvertError::FetchError { reason, .. } => reason.clone(),
+        ConvertError::Timeout(_) => "request timed out".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Label of pasted (non-URL) subscription content in source comments
+const INLINE_SOURCE_LABEL: &str = "inline";
+
+impl ResolvedSubscription {
+    /// Parse each source on its own, apply its filters and merge the nodes in
+    /// `merge_order`, each with its source label. A source that fails to parse becomes
+    /// a warning unless no source yields any node, in which case its error is returned.
+    fn parse_nodes(
+        &self,
+        merge_order: MergeOrder,
+        disabled_protocols: &[String],
+        warnings: &mut Vec<String>,
+    ) -> Result<Vec<(Node, String)>> {
+        let mut parsed = Vec::new();
+        let mut errors = Vec::new();
+        for source in self.sources.iter().filter(|s| !s.lines.is_empty()) {
+            match parse_subscription_content_filtered(&source.lines.join("\n"), disabled_protocols)
+            {
+                Ok((mut nodes, parse_warnings)) => {
+                    warnings.extend(parse_warnings);
+                    if let Some(filters) = &source.filters {
+                        let before = nodes.len();
+                        nodes.retain(|n| filters.keeps(n.name()));
+                        let dropped = before - nodes.len();
+                        if dropped > 0 {
+                            warnings.push(format!(
+                                "Source '{}': filtered out {} node(s)",
+                                source.label, dropped
+                            ));
+                        }
+                    }
+                    parsed.push(
+                        nodes
+                            .into_iter()
+                            .map(|node| (node, source.label.clone()))
+                            .collect(),
+                    );
+                }
+                Err(e) => errors.push((source.label.as_str(), e)),
+            }
+        }
+        let nodes = merge_sources(parsed, merge_order);
+        if nodes.is_empty() {
+            if let Some((_, e)) = errors.into_iter().next() {
+                return Err(e);
+            }
+        } else {
+            for (label, e) in errors {
+                warnings.push(format!("Source '{}': {}", label, e));
+            }
+        }
+        Ok(nodes)
+    }
+}
+
+/// Label a subscription source by its URL host (falls back to the URL itself)
+fn source_label(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_else(|| url.to_string())
+}
+
+/// Steps 2-4 of a conversion: deduplicate, drop info/unlisted/filtered nodes, rename,
+/// make names unique, apply the node cap and shorten long names. Returns the nodes and,
+/// when names were shortened, the full names from before.
+fn select_nodes(
+    mut nodes: Vec<Node>,
+    request: &ConvertRequest,
+    warnings: &mut Vec<String>,
+) -> Result<(Vec<Node>, Vec<String>)> {
+    // Step 2: Deduplicate nodes
+    let before_dedup = nodes.len();
+    nodes = if request.dedup_ignore_transport {
+        deduplicate_nodes_by_endpoint(nodes)
+    } else {
+        deduplicate_nodes(nodes)
+    };
+    if nodes.len() < before_dedup {
+        warnings.push(format!(
+            "Removed {} duplicate nodes",
+            before_dedup - nodes.len()
+        ));
+    }
+
+    // Step 3: Drop info entries, then apply node filtering
+    if request.drop_info_nodes {
+        let patterns = request.info_node_patterns.clone().unwrap_or_else(|| {
+            DEFAULT_INFO_NODE_PATTERNS
+                .iter()
+                .map(|p| p.to_string())
+                .collect()
+        });
+        let (kept, dropped) = drop_info_nodes(nodes, &patterns)?;
+        nodes = kept;
+        if dropped > 0 {
+            warnings.push(format!("Dropped {} info node(s)", dropped));
+        }
+    }
+
+    let (kept, dropped) = filter_by_server_allowlist(nodes, &request.server_allowlist)?;
+    nodes = kept;
+    if dropped > 0 {
+        warnings.push(format!(
+            "Dropped {} node(s) whose server is not in the allowlist",
+            dropped
+        ));
+    }
+
+    nodes = filter_nodes(
+        nodes,
+        request.include_regex.as_deref(),
+        request.exclude_regex.as_deref(),
+    )?;
+
+    if nodes.is_empty() {
+        return Err(ConvertError::Internal(
+            "All nodes were filtered out. Check your filter patterns.".into(),
+        ));
+    }
+
+    // Step 4: Apply node renaming (exact-name table first, then regex)
+    if let Some(rename_map) = &request.rename_map {
+        let (renamed, unused) = rename_by_map(nodes, rename_map);
+        nodes = renamed;
+        for name in unused {
+            warnings.push(format!("Rename map entry '{}' matched no node", name));
+        }
+    }
+    if let (Some(pattern), Some(replacement)) =
+        (&request.rename_pattern, &request.rename_replacement)
+    {
+        if !pattern.is_empty() {
+            nodes = rename_nodes(nodes, pattern, replacement)?;
+        }
+    }
+    // Names must be unique before groups match them
+    let (unique, changed) = dedup_node_names(nodes, request.dedup_by_name);
+    nodes = unique;
+    if changed > 0 {
+        warnings.push(if request.dedup_by_name {
+            format!("Dropped {} node(s) with a duplicate name", changed)
+        } else {
+            format!("Renamed {} node(s) with a duplicate name", changed)
+        });
+    }
+    if let Some(max_nodes) = request.max_nodes.filter(|m| *m > 0) {
+        let total = nodes.len();
+        let (kept, dropped) = limit_nodes(nodes, max_nodes);
+        nodes = kept;
+        if dropped > 0 {
+            warnings.push(format!("Truncated to {} of {} nodes", max_nodes, total));
+        }
+    }
+    // Full names of shortened nodes are kept to check INI group patterns against
+    let mut untruncated_names = Vec::new();
+    if let Some(max_len) = request.max_name_len.filter(|l| *l > 0) {
+        let names: Vec<String> = nodes.iter().map(|n| n.name().to_string()).collect();
+        let (shortened, count) = truncate_node_names(nodes, max_len);
+        nodes = shortened;
+        if count > 0 {
+            warnings.push(format!(
+                "Shortened {} node name(s) to {} characters",
+                count, max_len
+            ));
+            untruncated_names = names;
+        }
+    }
+    Ok((nodes, untruncated_names))
 }
 
 /// Main conversion engine
 pub struct SubscriptionEngine {
     http_client: HttpClient,
+    /// Known INI presets; only these get mirror fallbacks
+    presets: Vec<PresetConfig>,
 }
 
 impl SubscriptionEngine {
     pub fn new(timeout_secs: u64) -> Result<Self> {
         Ok(Self {
             http_client: HttpClient::new(timeout_secs)?,
+            presets: Self::get_preset_configs(),
         })
     }
 
     pub fn with_user_agent(timeout_secs: u64, user_agent: &str) -> Result<Self> {
         Ok(Self {
             http_client: HttpClient::with_user_agent(timeout_secs, user_agent)?,
+            presets: Self::get_preset_configs(),
         })
     }
 
+    /// Retry transient fetch failures (subscriptions and INI) up to `retries` extra times
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.http_client = self.http_client.with_retries(retries);
+        self
+    }
+
+    /// Replace the built-in INI presets
+    pub fn with_presets(mut self, presets: Vec<PresetConfig>) -> Self {
+        self.presets = presets;
+        self
+    }
+
+    /// Abort fetches whose body exceeds `mb` MiB (10 MiB when `None` or 0)
+    pub fn with_max_response_mb(mut self, mb: Option<u32>) -> Self {
+        let bytes = mb
+            .filter(|mb| *mb > 0)
+            .map_or(DEFAULT_MAX_BODY_BYTES, |mb| mb as usize * 1024 * 1024);
+        self.http_client = self.http_client.with_max_body_size(bytes);
+        self
+    }
+
     /// Main conversion function
     pub async fn convert(&self, request: ConvertRequest) -> Result<ConvertResult> {
         let mut warnings = Vec::new();
 
+        let mode = normalize_non_empty(request.mode.as_deref())
+            .map(|m| m.to_ascii_lowercase())
+            .unwrap_or_else(|| "rule".to_string());
+        if !matches!(mode.as_str(), "rule" | "global" | "direct") {
+            return Err(ConvertError::Internal(format!(
+                "Invalid mode: {} (expected rule, global or direct)",
+                mode
+            )));
+        }
+
+        let find_process_mode = normalize_non_empty(request.find_process_mode.as_deref())
+            .map(|m| m.to_ascii_lowercase());
+        if let Some(fpm) = &find_process_mode {
+            if !matches!(fpm.as_str(), "off" | "strict" | "always") {
+                return Err(ConvertError::Internal(format!(
+                    "Invalid find-process-mode: {} (expected off, strict or always)",
+                    fpm
+                )));
+            }
+        }
+
+        let expected_status = normalize_non_empty(request.default_expected_status.as_deref());
+        if let Some(status) = &expected_status {
+            if !status
+                .chars()
+                .all(|c| c.is_ascii_digit() || matches!(c, '/' | '-' | '*'))
+            {
+                return Err(ConvertError::Internal(format!(
+                    "Invalid expected-status: {} (expected codes like 204, 200/204 or 200-299)",
+                    status
+                )));
+            }
+        }
+
+        let sni_override = normalize_non_empty(request.sni_override.as_deref());
+        if let Some(sni) = &sni_override {
+            if sni.chars().any(|c| c.is_whitespace() || c == ',') {
+                return Err(ConvertError::Internal(format!(
+                    "Invalid SNI override: {} (expected a host name)",
+                    sni
+                )));
+            }
+        }
+        let sni_override_scope = compile_optional_regex(request.sni_override_regex.as_deref())?;
+
+        let lan_ips = |ips: &[String]| -> Vec<String> {
+            ips.iter()
+                .map(|ip| ip.trim().to_string())
+                .filter(|ip| !ip.is_empty())
+                .collect()
+        };
+        let lan_allowed_ips = lan_ips(&request.lan_allowed_ips);
+        let lan_disallowed_ips = lan_ips(&request.lan_disallowed_ips);
+        if let Some(ip) = lan_allowed_ips
+            .iter()
+            .chain(&lan_disallowed_ips)
+            .find(|ip| !is_cidr(ip))
+        {
+            return Err(ConvertError::Internal(format!(
+                "Invalid LAN CIDR: {} (expected e.g. 192.168.0.0/16)",
+                ip
+            )));
+        }
+
+        if request.tun_only && !request.enable_tun {
+            return Err(ConvertError::Internal(
+                "TUN-only mode requires TUN to be enabled".into(),
+            ));
+        }
+
+        let subscription = request.effective_subscription();
+
+        if request.keep_original_structure {
+            let (document, subscription_info) = self.resolve_raw_document(&subscription).await?;
+            if let Some(doc) = parse_clash_document(&document) {
+                let rename = match (&request.rename_pattern, &request.rename_replacement) {
+                    (Some(pattern), Some(replacement)) if !pattern.is_empty() => {
+                        Some((pattern.as_str(), replacement.as_str()))
+                    }
+                    _ => None,
+                };
+                let output = rewrite_clash_document(
+                    doc,
+                    request.include_regex.as_deref(),
+                    request.exclude_regex.as_deref(),
+                    rename,
+                )?;
+                warnings.extend(output.warnings);
+                return Ok(ConvertResult {
+                    yaml: output.yaml,
+                    node_count: output.node_count,
+                    filtered_count: output.filtered_count,
+                    group_count: output.group_count,
+                    rule_count: output.rule_count,
+                    warnings,
+                    subscription_info,
+                    providers_file: None,
+                });
+            }
+            warnings.push(
+                "Input is not a full Clash config, original structure cannot be kept".to_string(),
+            );
+        }
+
         // Step 1: Parse subscription content
-        let (raw_content, subscription_info, fetch_warnings) =
-            self.resolve_subscription(&request.subscription).await?;
-        warnings.extend(fetch_warnings);
-        let mut nodes = parse_subscription_content(&raw_content)?;
+        let source_options = SourceOptions::from_sources(&request.subscription_sources)?;
+        let resolved = self
+            .resolve_subscription(&subscription, request.merge_order, &source_options)
+            .await?;
+        // Failed sources are reported, and the ones that did load are still converted
+        for (url, reason) in &resolved.failed_sources {
+            warnings.push(format!("source {} failed: {}", url, reason));
+        }
+        warnings.extend_from_slice(&resolved.warnings);
+        // The first source's info is reported in the result
+        let subscription_info = resolved.source_infos.first().map(|(_, info)| info.clone());
+        // Appended to "no nodes" errors so failed fetches aren't mistaken for an empty
+        // subscription
+        let failed_note = if resolved.failed_sources.is_empty() {
+            String::new()
+        } else {
+            let failures: Vec<String> = resolved
+                .failed_sources
+                .iter()
+                .map(|(url, reason)| format!("{} ({})", url, reason))
+                .collect();
+            format!("; failed sources: {}", failures.join(", "))
+        };
+        let (mut nodes, node_labels): (Vec<Node>, Vec<String>) = resolved
+            .parse_nodes(
+                request.merge_order,
+                &request.disabled_protocols,
+                &mut warnings,
+            )
+            .map_err(|e| match e {
+                ConvertError::Internal(msg) if !failed_note.is_empty() => {
+                    ConvertError::Internal(format!("{}{}", msg, failed_note))
+                }
+                ConvertError::NoSupportedProxies(msg) if !failed_note.is_empty() => {
+                    ConvertError::NoSupportedProxies(format!("{}{}", msg, failed_note))
+                }
+                other => other,
+            })?
+            .into_iter()
+            .unzip();
         let initial_count = nodes.len();
 
         if nodes.is_empty() {
-            return Err(ConvertError::Internal(
-                "No valid nodes found in subscription".into(),
-            ));
+            return Err(ConvertError::Internal(format!(
+                "No valid nodes found in subscription{}",
+                failed_note
+            )));
         }
 
         let (reality_short_id_override, override_invalid) =
@@ -198,44 +1026,90 @@ impl SubscriptionEngine {
             }
         }
 
-        // Step 2: Deduplicate nodes
-        let before_dedup = nodes.len();
-        nodes = deduplicate_nodes(nodes);
-        if nodes.len() < before_dedup {
-            warnings.push(format!(
-                "Removed {} duplicate nodes",
-                before_dedup - nodes.len()
-            ));
+        // Source of each node, keyed by its dedup key so it survives dedup and renaming
+        let mut node_origins: HashMap<String, String> = HashMap::new();
+        if request.source_comments {
+            for (node, label) in nodes.iter().zip(node_labels) {
+                node_origins.entry(node.dedup_key()).or_insert(label);
+            }
         }
 
-        // Step 3: Apply node filtering
-        nodes = filter_nodes(
-            nodes,
-            request.include_regex.as_deref(),
-            request.exclude_regex.as_deref(),
-        )?;
+        // Steps 2-4: deduplicate, filter, rename and cap
+        let (mut nodes, untruncated_names) = select_nodes(nodes, &request, &mut warnings)?;
 
-        if nodes.is_empty() {
-            return Err(ConvertError::Internal(
-                "All nodes were filtered out. Check your filter patterns.".into(),
+        let filtered_count = nodes.len();
+        let proxy_sources: HashMap<String, String> = nodes
+            .iter()
+            .filter_map(|n| {
+                node_origins
+                    .get(&n.dedup_key())
+                    .map(|label| (n.name().to_string(), label.clone()))
+            })
+            .collect();
+
+        for conflict in find_credential_conflicts(&nodes) {
+            warnings.push(format!(
+                "Endpoint {} has {} different credentials (possibly stale nodes): {}",
+                conflict.endpoint,
+                conflict.credential_count,
+                conflict.node_names.join(", ")
             ));
         }
 
-        // Step 4: Apply node renaming
-        if let (Some(pattern), Some(replacement)) =
-            (&request.rename_pattern, &request.rename_replacement)
-        {
-            if !pattern.is_empty() {
-                nodes = rename_nodes(nodes, pattern, replacement)?;
+        if request.infer_tls_on_443 {
+            for node in &mut nodes {
+                if node.infer_tls_on_443() {
+                    warnings.push(format!(
+                        "Node '{}': TLS not set on port {} with an SNI/Host, assuming tls: true",
+                        node.name(),
+                        node.port()
+                    ));
+                }
             }
         }
 
-        let filtered_count = nodes.len();
+        for node in &nodes {
+            if let Some(host) = node.sni_host_fallback() {
+                warnings.push(format!(
+                    "Node '{}': ws+tls without SNI, using Host header '{}' as SNI",
+                    node.name(),
+                    host
+                ));
+            }
+        }
+
+        // CSV and Surge export need only the node list
+        if request.output_format == OutputFormat::Csv {
+            return Ok(ConvertResult {
+                yaml: nodes_to_csv(&nodes),
+                node_count: initial_count,
+                filtered_count,
+                group_count: 0,
+                rule_count: 0,
+                warnings,
+                subscription_info,
+                providers_file: None,
+            });
+        }
+        if request.output_format == OutputFormat::Surge {
+            let output = build_surge_proxies(&nodes);
+            warnings.extend(output.warnings);
+            return Ok(ConvertResult {
+                yaml: output.text,
+                node_count: initial_count,
+                filtered_count,
+                group_count: 0,
+                rule_count: 0,
+                warnings,
+                subscription_info,
+                providers_file: None,
+            });
+        }
 
         // Step 5: Load INI config (if provided)
         let ini_config = if let Some(url) = &request.ini_url {
             if !url.is_empty() {
-                match self.http_client.fetch(url).await {
+                match self.fetch_ini(url, &mut warnings).await {
                     Ok(content) => match parse_ini_config(&content) {
                         Ok(config) => Some(config),
                         Err(e) => {
@@ -267,14 +1141,67 @@ impl SubscriptionEngine {
             None
         };
 
+        if let Some(ref ini) = ini_config {
+            if !untruncated_names.is_empty() {
+                warnings.extend(shortened_name_pattern_warnings(
+                    ini,
+                    &untruncated_names,
+                    &nodes,
+                ));
+            }
+        }
+
+        // INI template defaults: TFO can be switched on, UDP can be switched off
+        let mut enable_udp = request.enable_udp;
+        let mut enable_tfo = request.enable_tfo;
+        if let Some(ref ini) = ini_config {
+            if ini.defaults.udp == Some(false) && enable_udp {
+                enable_udp = false;
+                warnings.push("UDP disabled by INI template defaults".to_string());
+            }
+            if ini.defaults.tfo == Some(true) && !enable_tfo {
+                enable_tfo = true;
+                warnings.push("TFO enabled by INI template defaults".to_string());
+            }
+        }
+
         // Step 6: Build Clash config
-        let mut builder = ClashConfigBuilder::new()
-            .with_nodes(&nodes)
-            .with_global_options(
-                request.enable_udp,
-                request.enable_tfo,
-                request.skip_cert_verify,
-            );
+        // Global options must be set before nodes so they are applied to each proxy
+        let mut builder = ClashConfigBuilder::new().with_global_options(
+            enable_udp,
+            enable_tfo,
+            request.skip_cert_verify,
+        );
+        if request.explicit_cert_verify {
+            builder = builder.with_explicit_cert_verify();
+        }
+        if let Some(smux) = request.global_smux.clone() {
+            builder = builder.with_global_smux(smux);
+        }
+        if let Some(sni) = sni_override {
+            let overridden = nodes
+                .iter()
+                .filter(|n| n.sni_key().is_some())
+                .filter(|n| {
+                    sni_override_scope
+                        .as_ref()
+                        .is_none_or(|re| re.is_match(n.name()))
+                })
+                .count();
+            if overridden > 0 {
+                warnings.push(format!(
+                    "SNI overridden to '{}' on {} node(s); a mismatched SNI can break TLS handshakes",
+                    sni, overridden
+                ));
+            }
+            builder = builder.with_sni_override(sni, sni_override_scope);
+        }
+        builder = builder.with_nodes(&nodes).with_proxy_sources(proxy_sources);
+
+        let duplicates = builder.duplicate_proxy_names();
+        if !duplicates.is_empty() {
+            return Err(ConvertError::DuplicateProxyNames(duplicates.join(", ")));
+        }
 
         // API settings (external-controller + secret)
         let external_controller = if request.api_listen_lan {
@@ -293,6 +1220,15 @@ impl SubscriptionEngine {
         // Rule-provider download options
         let rule_provider_options = RuleProviderOptions {
             proxy: normalize_non_empty(request.rule_provider_proxy.as_deref()),
+            proxy_by_behavior: request
+                .rule_provider_proxy_by_behavior
+                .iter()
+                .flatten()
+                .filter_map(|(behavior, proxy)| {
+                    normalize_non_empty(Some(proxy))
+                        .map(|proxy| (behavior.trim().to_ascii_lowercase(), proxy))
+                })
+                .collect(),
             header: parse_rule_provider_header(request.rule_provider_header.as_deref()),
             size_limit: request.rule_provider_size_limit.filter(|v| *v > 0),
             path_omit: request.rule_provider_path_omit,
@@ -300,11 +1236,45 @@ impl SubscriptionEngine {
         };
         builder = builder.with_rule_provider_options(rule_provider_options);
 
-        if request.enable_tun {
+        if request.tun_only {
+            builder = builder.with_tun_only();
+        } else if request.enable_tun {
             builder = builder.with_tun();
         }
 
-        let (builder, group_count, rule_count) = if let Some(ref ini) = ini_config {
+        if let Some(timeout) = request.default_group_timeout.filter(|t| *t > 0) {
+            builder = builder.with_default_group_timeout(timeout);
+        }
+
+        builder = builder.with_default_group_health(request.default_group_lazy, expected_status);
+
+        builder = builder.with_auto_group_options(
+            request.auto_interval.filter(|i| *i > 0),
+            request.auto_tolerance,
+        );
+        if request.number_groups {
+            builder = builder.with_numbered_groups();
+        }
+        if request.append_direct_reject {
+            builder = builder.with_direct_reject_appended();
+        }
+        if request.compact {
+            builder = builder.with_compact_output();
+        }
+        if request.skip_validation {
+            builder = builder.without_validation();
+        }
+
+        if let Some(latency_map) = request.latency_map.clone() {
+            if !latency_map.is_empty() {
+                builder = builder.with_latency_sort(latency_map);
+            }
+        }
+
+        if ini_config.is_some() && request.group_mode == GroupMode::Region {
+            warnings.push("Region groups ignored: the INI config defines the groups".to_string());
+        }
+        let (mut builder, mut group_count, mut rule_count) = if let Some(ref ini) = ini_config {
             let group_count = ini.proxy_groups.len();
             let rule_count = ini.rules.len() + ini.ruleset_urls.len();
             (
@@ -313,14 +1283,171 @@ impl SubscriptionEngine {
                 rule_count,
             )
         } else {
-            let builder = builder.with_default_groups(&nodes).with_default_rules();
-            (builder, 5, 7) // Default has 5 groups and 7 rules
+            if request.skip_match {
+                warnings.push(
+                    "Default MATCH rule omitted: unmatched traffic falls through to the core's default"
+                        .to_string(),
+                );
+            }
+            let mut builder = builder
+                .with_default_rule_options(DefaultRuleOptions {
+                    skip_cn_direct: request.skip_cn_direct,
+                    skip_private_direct: request.skip_private_direct,
+                    skip_match: request.skip_match,
+                })
+                .with_default_groups(&nodes)
+                .with_default_rules();
+            if request.group_mode == GroupMode::Region {
+                let custom = request
+                    .region_keywords
+                    .as_ref()
+                    .map(RegionTable::with_custom)
+                    .transpose()?;
+                builder = builder
+                    .with_region_groups(&nodes, custom.as_ref().unwrap_or(RegionTable::builtin()));
+            }
+            let rule_count = builder.rule_count();
+            let group_count = builder.group_count();
+            (builder, group_count, rule_count)
+        };
+
+        if request.dedup_rules {
+            let removed = builder.dedup_rules();
+            if removed > 0 {
+                rule_count = rule_count.saturating_sub(removed);
+                warnings.push(format!("Removed {} duplicate rule(s)", removed));
+            }
+        } else {
+            builder = builder.with_rule_dedup(false);
+        }
+
+        if let Some(max_rules) = request.max_rules.filter(|m| *m > 0) {
+            let dropped = builder.truncate_rules(max_rules);
+            if dropped > 0 {
+                rule_count = rule_count.saturating_sub(dropped);
+                warnings.push(format!(
+                    "Rules capped at {}: dropped {} inline rule(s) before the final MATCH",
+                    max_rules, dropped
+                ));
+            }
+        }
+
+        let mut builder = builder
+            .with_mode(&mode)
+            .with_connection_toggles(request.unified_delay, request.tcp_concurrent);
+        if let Some(fpm) = &find_process_mode {
+            builder = builder.with_find_process_mode(fpm);
+        }
+        if let Some(respect_rules) = request.dns_respect_rules {
+            builder = builder.with_dns_respect_rules(respect_rules);
+        }
+        if request.enable_ipv6 {
+            builder = builder.with_ipv6();
+            let ipv4_only = nodes.iter().filter(|n| n.is_ipv4_only()).count();
+            if ipv4_only > 0 {
+                warnings.push(format!(
+                    "IPv6 enabled, but {} WireGuard node(s) have no IPv6 address and can't carry IPv6 traffic",
+                    ipv4_only
+                ));
+            }
+        }
+        builder = builder.with_lan_options(
+            request.allow_lan,
+            normalize_non_empty(request.bind_address.as_deref()),
+            lan_allowed_ips,
+            lan_disallowed_ips,
+        );
+        builder = builder.with_dns_hosts(request.dns_use_hosts, request.dns_use_system_hosts);
+        builder = builder.with_geo_options(
+            request.geodata_mode,
+            request.geo_auto_update,
+            request.geo_update_interval.filter(|i| *i > 0),
+        );
+        let tag_count = nodes
+            .iter()
+            .flat_map(|n| n.tags())
+            .collect::<std::collections::HashSet<_>>()
+            .len();
+        if tag_count > 0 {
+            group_count += tag_count;
+            builder = builder.with_tag_groups(&nodes);
+        }
+        if request.subscription_info_groups && !resolved.source_infos.is_empty() {
+            let names: Vec<String> = resolved
+                .source_infos
+                .iter()
+                .map(|(source, info)| format!("ℹ️ {} {}", source, info.summary()))
+                .collect();
+            group_count += names.len();
+            builder = builder.with_info_groups(&names);
+        }
+        let (mut builder, group_count) = if mode == "global" {
+            let builder = builder.with_global_group(&nodes);
+            let group_count = builder.group_count();
+            (builder, group_count)
+        } else {
+            (builder, group_count)
         };
 
+        if let Some(rewrite) = request
+            .rule_target_rewrite
+            .as_ref()
+            .filter(|r| !r.is_empty())
+        {
+            let unresolved = builder.rewrite_rule_targets(rewrite);
+            if let Some(target) = unresolved.first() {
+                return Err(ConvertError::Internal(format!(
+                    "Rule target rewrite to '{}' does not match any group, proxy or built-in policy",
+                    target
+                )));
+            }
+        }
+
         // Step 7: Generate YAML
-        let yaml = builder
-            .build_yaml()
-            .map_err(|e| ConvertError::YamlSerializeError(e.to_string()))?;
+        let base_config = request
+            .base_config
+            .as_deref()
+            .filter(|b| !b.trim().is_empty());
+        if base_config.is_some() && request.output_format != OutputFormat::Full {
+            warnings.push("Base config ignored: only applies to full output".to_string());
+        }
+        let mut providers_file = None;
+        let (yaml, group_count, rule_count) = match request.output_format {
+            OutputFormat::Full => match base_config {
+                Some(base) => (builder.build_into_base(base), group_count, rule_count),
+                None => (builder.build_yaml(), group_count, rule_count),
+            },
+            OutputFormat::ProxiesOnly => (builder.build_proxies_yaml(), 0, 0),
+            OutputFormat::GroupsRulesOnly => {
+                (builder.build_groups_rules_yaml(), group_count, rule_count)
+            }
+            OutputFormat::Split => {
+                let split = builder
+                    .build_split_yaml(SPLIT_PROVIDER_NAME, SPLIT_PROVIDER_PATH)
+                    .map(|split| {
+                        providers_file = Some(split.providers_file);
+                        split.main_config
+                    });
+                (split, group_count, rule_count)
+            }
+            OutputFormat::SingBox => {
+                let output = builder.build_singbox_json().map_err(|e| {
+                    ConvertError::Internal(format!("Failed to serialize sing-box JSON: {}", e))
+                })?;
+                warnings.extend(output.warnings);
+                (Ok(output.json), group_count, 0)
+            }
+            OutputFormat::Csv | OutputFormat::Surge => {
+                unreachable!("CSV and Surge output return before building the config")
+            }
+        };
+        let yaml = yaml.map_err(|e| ConvertError::YamlSerializeError(e.to_string()))?;
+        if request.strict_validate && request.output_format != OutputFormat::SingBox {
+            for output in std::iter::once(&yaml).chain(providers_file.as_ref()) {
+                validate_yaml_strict(output)
+                    .map_err(|e| ConvertError::StrictValidation(e.to_string()))?;
+            }
+        }
 
         Ok(ConvertResult {
             yaml,
@@ -330,14 +1457,89 @@ impl SubscriptionEngine {
             rule_count,
             warnings,
             subscription_info,
+            providers_file,
         })
     }
 
+    /// Convert and also return the generated config as structured JSON
+    pub async fn convert_structured(&self, request: ConvertRequest) -> Result<ClashConfigJson> {
+        let result = self.convert(request).await?;
+        let config = serde_yaml::from_str(&result.yaml)
+            .map_err(|e| ConvertError::YamlSerializeError(e.to_string()))?;
+        Ok(ClashConfigJson { result, config })
+    }
+
+    /// Fetch an INI config. Preset URLs fall back to the preset's mirrors;
+    /// other URLs are fetched directly so they are never sent to a third party.
+    /// Returns the primary URL's error if every mirror also fails.
+    async fn fetch_ini(&self, url: &str, warnings: &mut Vec<String>) -> Result<String> {
+        let mirrors = self
+            .presets
+            .iter()
+            .find(|preset| preset.url == url.trim())
+            .map_or(&[][..], |preset| preset.mirrors.as_slice());
+        self.fetch_with_mirrors(url, mirrors, warnings).await
+    }
+
+    /// Fetch `url`, then each of `mirrors` in order until one succeeds
+    async fn fetch_with_mirrors(
+        &self,
+        url: &str,
+        mirrors: &[String],
+        warnings: &mut Vec<String>,
+    ) -> Result<String> {
+        let primary_err = match self.http_client.fetch(url).await {
+            Ok(content) => return Ok(content),
+            Err(e) => e,
+        };
+
+        for mirror in mirrors {
+            if let Ok(content) = self.http_client.fetch(mirror).await {
+                warnings.push(format!("Fetched INI config from mirror: {}", mirror));
+                return Ok(content);
+            }
+        }
+
+        Err(primary_err)
+    }
+
+    /// Fetch, parse and select nodes the way `convert` does (filters, renames, node cap)
+    /// without building a config, so latency tests cover the nodes and names the
+    /// conversion will keep
+    pub async fn resolve_nodes(&self, request: &ConvertRequest) -> Result<Vec<Node>> {
+        let source_options = SourceOptions::from_sources(&request.subscription_sources)?;
+        let resolved = self
+            .resolve_subscription(
+                &request.effective_subscription(),
+                request.merge_order,
+                &source_options,
+            )
+            .await?;
+        let nodes: Vec<Node> = resolved
+            .parse_nodes(
+                request.merge_order,
+                &request.disabled_protocols,
+                &mut Vec::new(),
+            )?
+            .into_iter()
+            .map(|(node, _)| node)
+            .collect();
+        if nodes.is_empty() {
+            return Err(ConvertError::Internal(
+                "No valid nodes found in subscription".into(),
+            ));
+        }
+        let (nodes, _) = select_nodes(nodes, request, &mut Vec::new())?;
+        Ok(nodes)
+    }
+
     /// Resolve subscription content only (for node preview, no conversion).
     /// Fetches URLs and decodes base64 if needed.
     pub async fn resolve_content(&self, content: &str) -> Result<String> {
-        let (raw, _, _) = self.resolve_subscription(content).await?;
-        Ok(raw)
+        let resolved = self
+            .resolve_subscription(content, MergeOrder::default(), &HashMap::new())
+            .await?;
+        Ok(resolved.content)
     }
 
     /// Resolve subscription content with subscription info (for node preview).
@@ -346,31 +1548,80 @@ impl SubscriptionEngine {
         &self,
         content: &str,
     ) -> Result<(String, Option<SubscriptionInfo>)> {
-        let (raw, info, _) = self.resolve_subscription(content).await?;
-        Ok((raw, info))
+        let resolved = self
+            .resolve_subscription(content, MergeOrder::default(), &HashMap::new())
+            .await?;
+        let info = resolved
+            .source_infos
+            .into_iter()
+            .next()
+            .map(|(_, info)| info);
+        Ok((resolved.content, info))
+    }
+
+    /// Fetch a subscription URL for its `subscription-userinfo` and file name only;
+    /// the body is not parsed
+    pub async fn fetch_subscription_info(
+        &self,
+        url: &str,
+    ) -> Result<(Option<SubscriptionInfo>, Option<String>)> {
+        let url = url.trim();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(ConvertError::UrlParseError(format!(
+                "Not a subscription URL: {}",
+                url
+            )));
+        }
+        let fetched = self.http_client.fetch_with_info(url).await?;
+        Ok((fetched.subscription_info, fetched.file_name))
     }
 
     /// Resolve subscription content (fetch URLs, decode base64, etc.)
-    /// Returns the content body and subscription info if available from HTTP headers.
+    /// Returns the content body, the labelled sources it was merged from, and the
+    /// subscription info of each fetched source (labelled by URL host) that sent one.
     /// Supports multiple input formats:
     /// - Single URL
     /// - Multiple URLs separated by `|` or newlines
     /// - Direct links (vless://, vmess://, etc.)
     /// - Base64 encoded subscription content
+    ///
+    /// Fetched URLs listed in `source_options` get their label, and their node filters
+    /// are kept for `ResolvedSubscription::parse_nodes`.
     async fn resolve_subscription(
         &self,
         content: &str,
-    ) -> Result<(String, Option<SubscriptionInfo>, Vec<String>)> {
+        merge_order: MergeOrder,
+        source_options: &HashMap<String, SourceOptions>,
+    ) -> Result<ResolvedSubscription> {
         // Step 1: Clean input - remove BOM, normalize line endings, trim whitespace
         let content = clean_input(content);
 
+        // Pasted Clash YAML must keep its indentation, so skip line splitting
+        if is_clash_yaml(&content) {
+            return Ok(ResolvedSubscription {
+                sources: vec![ResolvedSource {
+                    label: INLINE_SOURCE_LABEL.to_string(),
+                    lines: vec![content.clone()],
+                    filters: None,
+                }],
+                content,
+                source_infos: Vec::new(),
+                failed_sources: Vec::new(),
+                warnings: Vec::new(),
+            });
+        }
+
         // Step 2: Split by separators and scheme prefixes (supports concatenated links)
         let items = split_input_items(&content);
 
-        // Step 3: Process each item
-        let mut result_lines = Vec::new();
-        let mut first_sub_info: Option<SubscriptionInfo> = None;
+        // Step 3: Process each item. Each fetched URL is one source (in input order),
+        // followed by all direct content as a final source.
+        let mut sources: Vec<ResolvedSource> = Vec::new();
+        // Subscription info per fetched source (URL host), in input order
+        let mut source_infos: Vec<(String, SubscriptionInfo)> = Vec::new();
         let mut fetch_warnings = Vec::new();
+        let mut failed_sources = Vec::new();
+        let mut yaml_bodies = Vec::new();
 
         // Separate URLs from direct content
         let mut urls = Vec::new();
@@ -382,7 +1633,9 @@ impl SubscriptionEngine {
                 continue;
             }
 
-            if item.starts_with("http://") || item.starts_with("https://") {
+            if (item.starts_with("http://") || item.starts_with("https://"))
+                && !is_http_proxy_link(item)
+            {
                 urls.push(item.to_string());
             } else {
                 direct_content.push(item.to_string());
@@ -398,96 +1651,202 @@ impl SubscriptionEngine {
 
             let results = futures::future::join_all(fetch_futures).await;
 
-            for result in results {
+            for (url, result) in urls.iter().zip(results) {
+                let options = source_options.get(url.as_str());
+                let label = options
+                    .and_then(|o| o.name.clone())
+                    .unwrap_or_else(|| source_label(url));
+                let filters = options.filter(|o| o.has_filters()).cloned();
                 match result {
                     Ok(fetched) => {
-                        // Keep the first subscription info we encounter
-                        if first_sub_info.is_none() {
-                            first_sub_info = fetched.subscription_info;
+                        if let Some(info) = fetched.subscription_info {
+                            source_infos.push((label.clone(), info));
+                        }
+                        // Clash YAML (full config or proxy-provider file) is kept verbatim;
+                        // it can't be merged line-by-line with other sources
+                        if is_clash_yaml(&fetched.body) {
+                            yaml_bodies.push(ResolvedSource {
+                                label,
+                                lines: vec![clean_input(&fetched.body)],
+                                filters,
+                            });
+                            continue;
                         }
                         // The fetched content might be base64 encoded, decode it
                         let decoded_content = decode_subscription_body(&fetched.body);
-                        // Append fetched content
-                        for sub_line in decoded_content.lines() {
-                            let sub_line = sub_line.trim();
-                            if !sub_line.is_empty() {
-                                result_lines.push(sub_line.to_string());
-                            }
-                        }
+                        let lines: Vec<String> = decoded_content
+                            .lines()
+                            .map(str::trim)
+                            .filter(|l| !l.is_empty())
+                            .map(str::to_string)
+                            .collect();
+                        sources.push(ResolvedSource {
+                            label,
+                            lines,
+                            filters,
+                        });
                     }
                     Err(e) => {
-                        fetch_warnings.push(format!("Failed to fetch URL: {}", e));
+                        failed_sources.push((url.clone(), fetch_failure_reason(&e)));
                     }
                 }
             }
         }
 
         // Add direct content
-        result_lines.extend(direct_content);
+        sources.push(ResolvedSource {
+            label: INLINE_SOURCE_LABEL.to_string(),
+            lines: direct_content,
+            filters: None,
+        });
+        let result_lines = merge_sources(
+            sources.iter().map(|s| s.lines.clone()).collect(),
+            merge_order,
+        );
+
+        if !yaml_bodies.is_empty() {
+            if yaml_bodies.len() == 1 && result_lines.is_empty() {
+                return Ok(ResolvedSubscription {
+                    content: yaml_bodies[0].lines.concat(),
+                    source_infos,
+                    sources: yaml_bodies,
+                    failed_sources,
+                    warnings: fetch_warnings,
+                });
+            }
+            fetch_warnings.push(format!(
+                "Skipped {} Clash YAML subscription(s): YAML can't be combined with other sources",
+                yaml_bodies.len()
+            ));
+        }
+
+        Ok(ResolvedSubscription {
+            content: result_lines.join("\n"),
+            source_infos,
+            sources,
+            failed_sources,
+            warnings: fetch_warnings,
+        })
+    }
+
+    /// Fetch (if a URL) and parse an INI config, returning its groups and rule counts
+    pub async fn preview_ini(&self, url_or_content: &str) -> Result<IniPreview> {
+        let trimmed = url_or_content.trim();
+        let content = if trimmed.starts_with("http://") || trimmed.starts_with("https://") {
+            self.fetch_ini(trimmed, &mut Vec::new()).await?
+        } else {
+            url_or_content.to_string()
+        };
+        Ok(parse_ini_config(&content)?.preview())
+    }
+
+    /// Resolve a single document without line splitting (keeps YAML indentation).
+    /// Fetches the content if it is a single URL, otherwise returns it as-is.
+    async fn resolve_raw_document(
+        &self,
+        content: &str,
+    ) -> Result<(String, Option<SubscriptionInfo>)> {
+        let trimmed = content.trim();
+        let is_single_url = (trimmed.starts_with("http://") || trimmed.starts_with("https://"))
+            && !trimmed.contains(char::is_whitespace)
+            && !is_http_proxy_link(trimmed);
+        if is_single_url {
+            let fetched = self.http_client.fetch_with_info(trimmed).await?;
+            Ok((fetched.body, fetched.subscription_info))
+        } else {
+            Ok((content.to_string(), None))
+        }
+    }
 
-        Ok((result_lines.join("\n"), first_sub_info, fetch_warnings))
+    /// Check which preset INI URLs are reachable (keyed by preset name).
+    /// A preset counts as reachable if its primary URL or any mirror responds.
+    /// All presets are probed concurrently.
+    pub async fn check_preset_availability(&self) -> IndexMap<String, bool> {
+        self.check_availability(self.presets.clone()).await
+    }
+
+    /// Reachability of each of `presets`, keyed by name
+    async fn check_availability(&self, presets: Vec<PresetConfig>) -> IndexMap<String, bool> {
+        let checks: Vec<_> = presets
+            .iter()
+            .map(|preset| async move {
+                for url in std::iter::once(&preset.url).chain(preset.mirrors.iter()) {
+                    if self.http_client.is_reachable(url).await {
+                        return true;
+                    }
+                }
+                false
+            })
+            .collect();
+        let results = futures::future::join_all(checks).await;
+
+        presets
+            .into_iter()
+            .zip(results)
+            .map(|(preset, reachable)| (preset.name, reachable))
+            .collect()
     }
 
     /// Get predefined INI config URLs
     pub fn get_preset_configs() -> Vec<PresetConfig> {
         vec![
             // ==================== ACL4SSR 系列 ====================
-            PresetConfig {
-                name: "ACL4SSR_Online".to_string(),
-                url: "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online.ini".to_string(),
-                description: "ACL4SSR 默认版 分组比较全".to_string(),
-            },
-            PresetConfig {
-                name: "ACL4SSR_Online_Mini".to_string(),
-                url: "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Mini.ini".to_string(),
-                description: "ACL4SSR 精简版 少量规则".to_string(),
-            },
-            PresetConfig {
-                name: "ACL4SSR_Online_Mini_NoAuto".to_string(),
-                url: "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Mini_NoAuto.ini".to_string(),
-                description: "ACL4SSR 精简版 无自动测速".to_string(),
-            },
-            PresetConfig {
-                name: "ACL4SSR_Online_Full".to_string(),
-                url: "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Full.ini".to_string(),
-                description: "ACL4SSR 全分组版 带测试分组".to_string(),
-            },
-            PresetConfig {
-                name: "ACL4SSR_Online_Full_NoAuto".to_string(),
-                url: "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Full_NoAuto.ini".to_string(),
-                description: "ACL4SSR 全分组版 无自动测速".to_string(),
-            },
-            PresetConfig {
-                name: "ACL4SSR_Online_AdblockPlus".to_string(),
-                url: "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Full_AdblockPlus.ini".to_string(),
-                description: "ACL4SSR 全分组版 带去广告".to_string(),
-            },
-            PresetConfig {
-                name: "ACL4SSR_Online_MultiMode".to_string(),
-                url: "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Full_MultiMode.ini".to_string(),
-                description: "ACL4SSR 全分组版 多模式".to_string(),
-            },
-            PresetConfig {
-                name: "ACL4SSR_Online_NoReject".to_string(),
-                url: "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_NoReject.ini".to_string(),
-                description: "ACL4SSR 无广告拦截规则".to_string(),
-            },
+            PresetConfig::new(
+                "ACL4SSR_Online",
+                "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online.ini",
+                "ACL4SSR 默认版 分组比较全",
+            ),
+            PresetConfig::new(
+                "ACL4SSR_Online_Mini",
+                "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Mini.ini",
+                "ACL4SSR 精简版 少量规则",
+            ),
+            PresetConfig::new(
+                "ACL4SSR_Online_Mini_NoAuto",
+                "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Mini_NoAuto.ini",
+                "ACL4SSR 精简版 无自动测速",
+            ),
+            PresetConfig::new(
+                "ACL4SSR_Online_Full",
+                "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Full.ini",
+                "ACL4SSR 全分组版 带测试分组",
+            ),
+            PresetConfig::new(
+                "ACL4SSR_Online_Full_NoAuto",
+                "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Full_NoAuto.ini",
+                "ACL4SSR 全分组版 无自动测速",
+            ),
+            PresetConfig::new(
+                "ACL4SSR_Online_AdblockPlus",
+                "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Full_AdblockPlus.ini",
+                "ACL4SSR 全分组版 带去广告",
+            ),
+            PresetConfig::new(
+                "ACL4SSR_Online_MultiMode",
+                "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Full_MultiMode.ini",
+                "ACL4SSR 全分组版 多模式",
+            ),
+            PresetConfig::new(
+                "ACL4SSR_Online_NoReject",
+                "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_NoReject.ini",
+                "ACL4SSR 无广告拦截规则",
+            ),
             // ==================== 其他规则集 ====================
-            PresetConfig {
-                name: "全网搜集规则".to_string(),
-                url: "https://raw.githubusercontent.com/cutethotw/ClashRule/main/GeneralClashRule.ini".to_string(),
-                description: "全网搜集 分流较细致".to_string(),
-            },
-            PresetConfig {
-                name: "分区域故障转移".to_string(),
-                url: "https://raw.githubusercontent.com/cutethotw/ClashRule/main/GeneralClashRule-Fallback.ini".to_string(),
-                description: "按区域故障转移".to_string(),
-            },
-            PresetConfig {
-                name: "NeteaseUnblock".to_string(),
-                url: "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Full_Netflix.ini".to_string(),
-                description: "ACL4SSR Netflix 优化版".to_string(),
-            },
+            PresetConfig::new(
+                "全网搜集规则",
+                "https://raw.githubusercontent.com/cutethotw/ClashRule/main/GeneralClashRule.ini",
+                "全网搜集 分流较细致",
+            ),
+            PresetConfig::new(
+                "分区域故障转移",
+                "https://raw.githubusercontent.com/cutethotw/ClashRule/main/GeneralClashRule-Fallback.ini",
+                "按区域故障转移",
+            ),
+            PresetConfig::new(
+                "NeteaseUnblock",
+                "https://raw.githubusercontent.com/ACL4SSR/ACL4SSR/master/Clash/config/ACL4SSR_Online_Full_Netflix.ini",
+                "ACL4SSR Netflix 优化版",
+            ),
         ]
     }
 }
@@ -498,6 +1857,41 @@ pub struct PresetConfig {
     pub name: String,
     pub url: String,
     pub description: String,
+    /// Alternate URLs tried in order when the primary URL is unreachable
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+}
+
+impl PresetConfig {
+    fn new(name: &str, url: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            description: description.to_string(),
+            mirrors: github_raw_mirrors(url),
+        }
+    }
+}
+
+/// Derive mirror URLs for a raw.githubusercontent.com link (often blocked in China).
+/// Returns an empty list for other hosts.
+pub fn github_raw_mirrors(url: &str) -> Vec<String> {
+    let Some(path) = url.strip_prefix("https://raw.githubusercontent.com/") else {
+        return Vec::new();
+    };
+
+    let mut mirrors = vec![format!("https://ghfast.top/{}", url)];
+
+    // jsDelivr: /gh/{owner}/{repo}@{branch}/{file}
+    let parts: Vec<&str> = path.splitn(4, '/').collect();
+    if let [owner, repo, branch, file] = parts.as_slice() {
+        mirrors.push(format!(
+            "https://cdn.jsdelivr.net/gh/{}/{}@{}/{}",
+            owner, repo, branch, file
+        ));
+    }
+
+    mirrors
 }
 
 // ============================================================================
@@ -592,6 +1986,19 @@ fn decode_subscription_body(body: &str) -> String {
     body
 }
 
+/// Whether `s` is an `address/prefix` CIDR (IPv4 or IPv6)
+fn is_cidr(s: &str) -> bool {
+    let Some((addr, prefix)) = s.split_once('/') else {
+        return false;
+    };
+    let max_prefix = match addr.parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V4(_)) => 32,
+        Ok(std::net::IpAddr::V6(_)) => 128,
+        Err(_) => return false,
+    };
+    prefix.parse::<u8>().is_ok_and(|p| p <= max_prefix)
+}
+
 fn normalize_non_empty(input: Option<&str>) -> Option<String> {
     input
         .map(|s| s.trim().to_string())
@@ -629,6 +2036,34 @@ fn apply_reality_short_id_override(nodes: &mut [Node], short_id: &str) -> usize
     patched
 }
 
+/// Warn about INI group regexes that match fewer nodes once names are shortened
+fn shortened_name_pattern_warnings(
+    ini: &ParsedIniConfig,
+    full_names: &[String],
+    nodes: &[Node],
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for group in &ini.proxy_groups {
+        for matcher in &group.proxies {
+            let ProxyMatcher::Pattern(pattern) = matcher else {
+                continue;
+            };
+            let Ok(re) = Regex::new(pattern) else {
+                continue;
+            };
+            let before = full_names.iter().filter(|n| re.is_match(n)).count();
+            let after = nodes.iter().filter(|n| re.is_match(n.name())).count();
+            if after < before {
+                warnings.push(format!(
+                    "Group '{}': pattern '{}' matches {} of {} node(s) after shortening names",
+                    group.name, pattern, after, before
+                ));
+            }
+        }
+    }
+    warnings
+}
+
 fn count_suspicious_reality_short_ids(nodes: &[Node]) -> usize {
     nodes
         .iter()
@@ -699,13 +2134,13 @@ fn split_by_schemes(item: &str) -> Vec<String> {
 
     let re = if starts_with_http {
         SCHEME_WITH_HTTP_RE.get_or_init(|| {
-            Regex::new(r"(?i)(?:https?|vless|vmess|ssr|ss|trojan|hysteria2|hy2|hysteria|hy|tuic|wireguard|wg)://")
+            Regex::new(r"(?i)(?:https?|vless|vmess|ssr|ss|trojan|hysteria2|hy2|hysteria|hy|tuic|wireguard|wg|socks5|socks|snell)://")
                 .expect("valid scheme regex")
         })
     } else {
         SCHEME_PROXY_RE.get_or_init(|| {
             Regex::new(
-                r"(?i)(?:vless|vmess|ssr|ss|trojan|hysteria2|hy2|hysteria|hy|tuic|wireguard|wg)://",
+                r"(?i)(?:vless|vmess|ssr|ss|trojan|hysteria2|hy2|hysteria|hy|tuic|wireguard|wg|socks5|socks|snell)://",
             )
             .expect("valid scheme regex")
         })
@@ -763,4 +2198,1066 @@ fn parse_rule_provider_header(raw: Option<&str>) -> Option<IndexMap<String, Stri
     } else {
         Some(headers)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_client::mock_server::{serve, Reply};
+
+    fn request(json: serde_json::Value) -> ConvertRequest {
+        let mut value = serde_json::json!({ "subscription": "" });
+        value
+            .as_object_mut()
+            .unwrap()
+            .extend(json.as_object().unwrap().clone());
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[tokio::test]
+    async fn preset_availability_tries_mirrors() {
+        let (base, _) = serve(vec![
+            ("/up.ini", vec![Reply::ok("[custom]")]),
+            ("/mirror.ini", vec![Reply::ok("[custom]")]),
+        ])
+        .await;
+        let preset = |name: &str, url: &str, mirrors: &[&str]| PresetConfig {
+            name: name.to_string(),
+            url: format!("{}{}", base, url),
+            description: String::new(),
+            mirrors: mirrors.iter().map(|m| format!("{}{}", base, m)).collect(),
+        };
+        let engine = SubscriptionEngine::new(5).unwrap();
+        let availability = engine
+            .check_availability(vec![
+                preset("up", "/up.ini", &[]),
+                preset("mirrored", "/gone.ini", &["/mirror.ini"]),
+                preset("down", "/gone.ini", &["/also-gone.ini"]),
+            ])
+            .await;
+
+        let expected: IndexMap<String, bool> = [("up", true), ("mirrored", true), ("down", false)]
+            .into_iter()
+            .map(|(name, reachable)| (name.to_string(), reachable))
+            .collect();
+        assert_eq!(availability, expected);
+    }
+
+    #[tokio::test]
+    async fn ini_fetch_falls_back_to_mirror() {
+        let (base, hits) = serve(vec![
+            ("/primary.ini", vec![Reply::status(404)]),
+            (
+                "/mirror.ini",
+                vec![Reply::ok("[custom]\nruleset=DIRECT,[]MATCH")],
+            ),
+        ])
+        .await;
+        let engine = SubscriptionEngine::new(5).unwrap();
+        let mirror = format!("{}/mirror.ini", base);
+        let mut warnings = Vec::new();
+        let content = engine
+            .fetch_with_mirrors(
+                &format!("{}/primary.ini", base),
+                std::slice::from_ref(&mirror),
+                &mut warnings,
+            )
+            .await
+            .unwrap();
+
+        assert!(content.starts_with("[custom]"));
+        assert_eq!(
+            warnings,
+            [format!("Fetched INI config from mirror: {}", mirror)]
+        );
+        assert_eq!(hits.lock().unwrap()["/primary.ini"], 1);
+    }
+
+    #[tokio::test]
+    async fn only_preset_ini_urls_use_mirrors() {
+        let ini = "[custom]\ncustom_proxy_group=Mirrored`select`.*\nruleset=Mirrored,[]FINAL";
+        let (base, hits) = serve(vec![
+            ("/preset.ini", vec![Reply::status(404)]),
+            ("/custom.ini", vec![Reply::status(404)]),
+            ("/mirror.ini", vec![Reply::ok(ini), Reply::ok(ini)]),
+        ])
+        .await;
+        let mirror = format!("{}/mirror.ini", base);
+        let engine = SubscriptionEngine::new(5)
+            .unwrap()
+            .with_presets(vec![PresetConfig {
+                name: "preset".to_string(),
+                url: format!("{}/preset.ini", base),
+                description: String::new(),
+                mirrors: vec![mirror.clone()],
+            }]);
+        let convert = |path: &str| {
+            engine.convert(request(serde_json::json!({
+                "subscription": "trojan://pw@1.1.1.1:443#A",
+                "ini_url": format!("{}{}", base, path),
+            })))
+        };
+
+        let result = convert("/preset.ini").await.unwrap();
+        assert!(result
+            .warnings
+            .contains(&format!("Fetched INI config from mirror: {}", mirror)));
+        assert_eq!(yaml(&result)["proxy-groups"][0]["name"], "Mirrored");
+
+        let result = convert("/custom.ini").await.unwrap();
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| w.starts_with("Failed to fetch INI config")));
+        assert_eq!(hits.lock().unwrap()["/mirror.ini"], 1);
+    }
+
+    #[tokio::test]
+    async fn ini_fetch_retries_transient_failures() {
+        let ini = "[custom]\ncustom_proxy_group=Only`select`.*\nruleset=Only,[]FINAL";
+        for retries in [1, 0] {
+            let (base, hits) = serve(vec![(
+                "/flaky.ini",
+                vec![Reply::status(502), Reply::ok(ini)],
+            )])
+            .await;
+            let result = SubscriptionEngine::new(5)
+                .unwrap()
+                .with_retries(retries)
+                .convert(request(serde_json::json!({
+                    "subscription": "trojan://pw@1.1.1.1:443#A",
+                    "ini_url": format!("{}/flaky.ini", base),
+                })))
+                .await
+                .unwrap();
+            assert_eq!(hits.lock().unwrap()["/flaky.ini"], retries as usize + 1);
+            let fell_back = result
+                .warnings
+                .iter()
+                .any(|w| w.starts_with("Failed to fetch INI config"));
+            assert_eq!(fell_back, retries == 0);
+            assert_eq!(
+                yaml(&result)["proxy-groups"][0]["name"] == "Only",
+                retries > 0
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn preview_ini_lists_groups() {
+        let ini = "[custom]\n\
+                   custom_proxy_group=🚀 节点选择`select`[]♻️ 自动选择`[]DIRECT\n\
+                   custom_proxy_group=♻️ 自动选择`url-test`.*`http://www.gstatic.com/generate_204`300\n\
+                   ruleset=🚀 节点选择,https://example.com/proxy.list\n\
+                   ruleset=DIRECT,[]GEOIP,CN\n\
+                   ruleset=🚀 节点选择,[]FINAL\n";
+        let (base, _) = serve(vec![("/config.ini", vec![Reply::ok(ini)])]).await;
+        let engine = SubscriptionEngine::new(5).unwrap();
+
+        for source in [ini.to_string(), format!("{}/config.ini", base)] {
+            let preview = engine.preview_ini(&source).await.unwrap();
+            let groups: Vec<(&str, &str)> = preview
+                .groups
+                .iter()
+                .map(|g| (g.name.as_str(), g.group_type.as_str()))
+                .collect();
+            assert_eq!(
+                groups,
+                [("🚀 节点选择", "select"), ("♻️ 自动选择", "url-test")]
+            );
+            assert_eq!(preview.ruleset_count, 1);
+            assert_eq!(preview.rule_count, 2);
+        }
+    }
+
+    #[test]
+    fn rename_map_applies_before_regex_rename() {
+        let nodes = ["hk-a", "hk-b", "us-c"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                crate::parser::parse_single_link(&format!(
+                    "trojan://pw@1.1.1.{}:443#{}",
+                    i + 1,
+                    name
+                ))
+                .unwrap()
+            })
+            .collect();
+        let request = request(serde_json::json!({
+            "rename_map": { "hk-a": "HK 01", "us-c": "US 01", "jp-x": "JP 01" },
+            "rename_pattern": "^HK",
+            "rename_replacement": "香港",
+        }));
+        let mut warnings = Vec::new();
+        let (nodes, _) = select_nodes(nodes, &request, &mut warnings).unwrap();
+        let names: Vec<&str> = nodes.iter().map(|n| n.name()).collect();
+        assert_eq!(names, ["香港 01", "hk-b", "US 01"]);
+        assert_eq!(warnings, ["Rename map entry 'jp-x' matched no node"]);
+    }
+
+    #[tokio::test]
+    async fn resolve_nodes_matches_conversion() {
+        let request = request(serde_json::json!({
+            "subscription": "trojan://pw@1.1.1.1:443#HK 01\n\
+                             trojan://pw@1.1.1.2:443#HK 02\n\
+                             trojan://pw@1.1.1.3:443#HK 03\n\
+                             trojan://pw@2.2.2.2:443#US 01",
+            "include_regex": "HK",
+            "rename_pattern": "HK",
+            "rename_replacement": "香港",
+            "max_nodes": 2,
+        }));
+        let engine = SubscriptionEngine::new(5).unwrap();
+        let names: Vec<String> = engine
+            .resolve_nodes(&request)
+            .await
+            .unwrap()
+            .iter()
+            .map(|n| n.name().to_string())
+            .collect();
+        assert_eq!(names, ["香港 01", "香港 02"]);
+
+        let result = engine.convert(request).await.unwrap();
+        let config: serde_yaml::Value = serde_yaml::from_str(&result.yaml).unwrap();
+        let converted: Vec<&str> = config["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|p| p["name"].as_str())
+            .collect();
+        assert_eq!(converted, names);
+    }
+
+    async fn convert(json: serde_json::Value) -> Result<ConvertResult> {
+        SubscriptionEngine::new(5)
+            .unwrap()
+            .convert(request(json))
+            .await
+    }
+
+    fn yaml(result: &ConvertResult) -> serde_yaml::Value {
+        serde_yaml::from_str(&result.yaml).unwrap()
+    }
+
+    #[tokio::test]
+    async fn region_keywords_feed_region_groups() {
+        let subscription = "trojan://pw@1.1.1.1:443#Merlion 01\ntrojan://pw@1.1.1.2:443#HK 02";
+        let members = |config: &serde_yaml::Value, name: &str| -> Option<Vec<String>> {
+            let groups = config["proxy-groups"].as_sequence().unwrap();
+            let group = groups.iter().find(|g| g["name"] == name)?;
+            Some(
+                group["proxies"]
+                    .as_sequence()
+                    .unwrap()
+                    .iter()
+                    .filter_map(|p| p.as_str().map(str::to_string))
+                    .collect(),
+            )
+        };
+
+        let config = yaml(
+            &convert(serde_json::json!({
+                "subscription": subscription,
+                "group_mode": "region",
+                "region_keywords": { "sg": ["merlion"] },
+            }))
+            .await
+            .unwrap(),
+        );
+        assert_eq!(members(&config, "🇸🇬 新加坡").unwrap(), ["Merlion 01"]);
+        assert_eq!(members(&config, "🇭🇰 香港").unwrap(), ["HK 02"]);
+
+        let config = yaml(
+            &convert(serde_json::json!({ "subscription": subscription, "group_mode": "region" }))
+                .await
+                .unwrap(),
+        );
+        assert_eq!(members(&config, "🇸🇬 新加坡"), None);
+    }
+
+    #[tokio::test]
+    async fn tun_only_disables_mixed_port() {
+        let result = convert(serde_json::json!({
+            "subscription": "trojan://pw@1.1.1.1:443#A",
+            "enable_tun": true,
+            "tun_only": true,
+        }))
+        .await
+        .unwrap();
+        let config = yaml(&result);
+        assert_eq!(config["mixed-port"], 0);
+        assert_eq!(config["tun"]["enable"], true);
+        assert_eq!(config["dns"]["enhanced-mode"], "fake-ip");
+
+        let err = convert(serde_json::json!({
+            "subscription": "trojan://pw@1.1.1.1:443#A",
+            "tun_only": true,
+        }))
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("requires TUN"));
+    }
+
+    #[tokio::test]
+    async fn auto_groups_inherit_default_timeout() {
+        let ini = "[custom]\n\
+                   custom_proxy_group=Select`select`[]Auto`[]Fallback\n\
+                   custom_proxy_group=Auto`url-test`.*`http://www.gstatic.com/generate_204`300\n\
+                   custom_proxy_group=Fallback`fallback`.*`http://www.gstatic.com/generate_204`300\n\
+                   ruleset=Select,[]FINAL\n";
+        for extra in [
+            serde_json::json!({}),
+            serde_json::json!({ "ini_content": ini }),
+        ] {
+            let mut json = serde_json::json!({
+                "subscription": "trojan://pw@1.1.1.1:443#A",
+                "default_group_timeout": 3000,
+            });
+            json.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            let config = yaml(&convert(json).await.unwrap());
+            let groups = config["proxy-groups"].as_sequence().unwrap();
+            let mut auto = 0;
+            for group in groups {
+                match group["type"].as_str().unwrap() {
+                    "url-test" | "fallback" => {
+                        auto += 1;
+                        assert_eq!(group["timeout"], 3000, "{:?}", group["name"]);
+                    }
+                    _ => assert!(group.get("timeout").is_none()),
+                }
+            }
+            assert!(auto > 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn structured_output_lists_proxies() {
+        let structured = SubscriptionEngine::new(5)
+            .unwrap()
+            .convert_structured(request(serde_json::json!({
+                "subscription": "trojan://pw@1.1.1.1:443#A\n\
+                                 trojan://pw@1.1.1.2:443#B\n\
+                                 trojan://pw@1.1.1.3:443#C",
+            })))
+            .await
+            .unwrap();
+        let proxies = structured.config["proxies"].as_array().unwrap();
+        assert_eq!(proxies.len(), 3);
+        assert_eq!(proxies[1]["name"], "B");
+        assert_eq!(proxies[1]["server"], "1.1.1.2");
+        assert!(!structured.config["proxy-groups"]
+            .as_array()
+            .unwrap()
+            .is_empty());
+        assert!(structured.config["rules"].is_array());
+
+        let json = serde_json::to_value(&structured).unwrap();
+        assert_eq!(json["node_count"], 3);
+        assert!(json["yaml"].as_str().unwrap().contains("proxies:"));
+    }
+
+    #[tokio::test]
+    async fn global_mode_adds_global_group() {
+        let subscription = "trojan://pw@1.1.1.1:443#A\ntrojan://pw@1.1.1.2:443#B";
+        let config = yaml(
+            &convert(serde_json::json!({ "subscription": subscription, "mode": "global" }))
+                .await
+                .unwrap(),
+        );
+        assert_eq!(config["mode"], "global");
+        let groups = config["proxy-groups"].as_sequence().unwrap();
+        let global = groups.iter().find(|g| g["name"] == "GLOBAL").unwrap();
+        assert_eq!(global["type"], "select");
+        let members: Vec<&str> = global["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|p| p.as_str())
+            .collect();
+        assert_eq!(members[members.len() - 2..], ["A", "B"]);
+        for group in groups.iter().filter(|g| g["name"] != "GLOBAL") {
+            assert!(members.contains(&group["name"].as_str().unwrap()));
+        }
+
+        let config = yaml(
+            &convert(serde_json::json!({ "subscription": subscription }))
+                .await
+                .unwrap(),
+        );
+        assert!(!config["proxy-groups"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .any(|g| g["name"] == "GLOBAL"));
+    }
+
+    #[tokio::test]
+    async fn credential_conflicts_warn_but_keep_nodes() {
+        let result = convert(serde_json::json!({
+            "subscription": "vless://11111111-1111-1111-1111-111111111111@1.1.1.1:443?security=tls#old\n\
+                             vless://22222222-2222-2222-2222-222222222222@1.1.1.1:443?security=tls#new\n\
+                             vless://22222222-2222-2222-2222-222222222222@2.2.2.2:443?security=tls#other",
+        }))
+        .await
+        .unwrap();
+        assert_eq!(result.node_count, 3);
+        let conflicts: Vec<&String> = result
+            .warnings
+            .iter()
+            .filter(|w| w.contains("different credentials"))
+            .collect();
+        assert_eq!(
+            conflicts,
+            ["Endpoint 1.1.1.1:443 has 2 different credentials (possibly stale nodes): old, new"]
+        );
+    }
+
+    #[tokio::test]
+    async fn connection_toggles_can_be_turned_off() {
+        let config = yaml(
+            &convert(serde_json::json!({
+                "subscription": "trojan://pw@1.1.1.1:443#A",
+                "tcp_concurrent": false,
+            }))
+            .await
+            .unwrap(),
+        );
+        assert_eq!(config["tcp-concurrent"], false);
+        assert_eq!(config["unified-delay"], true);
+
+        let config = yaml(
+            &convert(serde_json::json!({ "subscription": "trojan://pw@1.1.1.1:443#A" }))
+                .await
+                .unwrap(),
+        );
+        assert_eq!(config["tcp-concurrent"], true);
+    }
+
+    #[tokio::test]
+    async fn find_process_mode_is_validated() {
+        let result = convert(serde_json::json!({
+            "subscription": "trojan://pw@1.1.1.1:443#A",
+            "find_process_mode": "Strict",
+        }))
+        .await
+        .unwrap();
+        assert!(result.yaml.contains("find-process-mode: strict"));
+
+        let err = convert(serde_json::json!({
+            "subscription": "trojan://pw@1.1.1.1:443#A",
+            "find_process_mode": "sometimes",
+        }))
+        .await
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Invalid find-process-mode: sometimes"));
+    }
+
+    #[tokio::test]
+    async fn geo_options_are_emitted() {
+        let result = convert(serde_json::json!({
+            "subscription": "trojan://pw@1.1.1.1:443#A",
+            "geodata_mode": true,
+            "geo_auto_update": true,
+            "geo_update_interval": 24,
+        }))
+        .await
+        .unwrap();
+        assert!(result.yaml.contains("geodata-mode: true\n"));
+        let config = yaml(&result);
+        assert_eq!(config["geo-auto-update"], true);
+        assert_eq!(config["geo-update-interval"], 24);
+
+        let config = yaml(
+            &convert(serde_json::json!({ "subscription": "trojan://pw@1.1.1.1:443#A" }))
+                .await
+                .unwrap(),
+        );
+        for key in ["geodata-mode", "geo-auto-update", "geo-update-interval"] {
+            assert!(config.get(key).is_none(), "{}", key);
+        }
+    }
+
+    #[tokio::test]
+    async fn info_groups_per_source() {
+        let gib: u64 = 1024 * 1024 * 1024;
+        let (base, _) = serve(vec![
+            (
+                "/alpha",
+                vec![Reply::ok("trojan://pw@1.1.1.1:443#A").header(
+                    "subscription-userinfo",
+                    &format!(
+                        "upload=0; download={}; total={}; expire=1767225600",
+                        gib,
+                        11 * gib
+                    ),
+                )],
+            ),
+            (
+                "/beta",
+                vec![Reply::ok("trojan://pw@2.2.2.2:443#B").header(
+                    "subscription-userinfo",
+                    &format!(
+                        "upload={}; download={}; total={}",
+                        gib / 2,
+                        2 * gib,
+                        5 * gib
+                    ),
+                )],
+            ),
+        ])
+        .await;
+        let config = yaml(
+            &convert(serde_json::json!({
+                "subscription_sources": [
+                    { "url": format!("{}/alpha", base), "name": "Alpha" },
+                    { "url": format!("{}/beta", base), "name": "Beta" },
+                ],
+                "subscription_info_groups": true,
+            }))
+            .await
+            .unwrap(),
+        );
+        let info: Vec<&str> = config["proxy-groups"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|g| g["name"].as_str())
+            .filter(|name| name.starts_with("ℹ️"))
+            .collect();
+        assert_eq!(
+            info,
+            [
+                "ℹ️ Alpha 剩余流量: 10.00 GB | 到期: 2026-01-01",
+                "ℹ️ Beta 剩余流量: 2.50 GB",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn disabled_sources_are_skipped() {
+        let (base, hits) = serve(vec![
+            ("/on", vec![Reply::ok("trojan://pw@1.1.1.1:443#On")]),
+            ("/off", vec![Reply::ok("trojan://pw@2.2.2.2:443#Off")]),
+        ])
+        .await;
+        let result = convert(serde_json::json!({
+            "subscription_sources": [
+                { "url": format!("{}/on", base) },
+                { "url": format!("{}/off", base), "enabled": false },
+            ],
+        }))
+        .await
+        .unwrap();
+        let config = yaml(&result);
+        let proxies = config["proxies"].as_sequence().unwrap();
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(proxies[0]["name"], "On");
+        assert!(!hits.lock().unwrap().contains_key("/off"));
+    }
+
+    #[tokio::test]
+    async fn manifest_applies_per_source_includes() {
+        let (base, _) = serve(vec![
+            (
+                "/a",
+                vec![Reply::ok(
+                    "trojan://pw@1.1.1.1:443#HK-A\ntrojan://pw@1.1.1.2:443#US-A",
+                )],
+            ),
+            (
+                "/b",
+                vec![Reply::ok(
+                    "trojan://pw@2.2.2.1:443#HK-B\ntrojan://pw@2.2.2.2:443#US-B",
+                )],
+            ),
+        ])
+        .await;
+        let manifest = format!(
+            r#"
+sources:
+  - url: {base}/a
+    include: ^HK
+  - url: {base}/b
+    include: ^US
+settings:
+  rename_pattern: "-"
+  rename_replacement: " "
+"#
+        );
+        let request = SubscriptionManifest::parse(&manifest)
+            .and_then(SubscriptionManifest::into_request)
+            .unwrap();
+        assert_eq!(request.subscription_sources.len(), 2);
+        let result = SubscriptionEngine::new(5)
+            .unwrap()
+            .convert(request)
+            .await
+            .unwrap();
+        let config = yaml(&result);
+        let names: Vec<&str> = config["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["HK A", "US B"]);
+    }
+
+    #[tokio::test]
+    async fn failed_source_is_reported_and_others_still_convert() {
+        let (base, _) = serve(vec![
+            ("/good", vec![Reply::ok("trojan://pw@1.1.1.1:443#Good")]),
+            ("/gone", vec![Reply::status(404)]),
+        ])
+        .await;
+        let gone = format!("{}/gone", base);
+        let result = convert(serde_json::json!({
+            "subscription": format!("{}/good\n{}", base, gone),
+        }))
+        .await
+        .unwrap();
+        let config = yaml(&result);
+        let proxies = config["proxies"].as_sequence().unwrap();
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(proxies[0]["name"], "Good");
+        let prefix = format!("source {} failed: ", gone);
+        assert!(result.warnings.iter().any(|w| w.starts_with(&prefix)));
+    }
+
+    #[tokio::test]
+    async fn repeated_conversions_are_byte_identical() {
+        let subscription = [
+            "hysteria://1.1.1.1:443?protocol=udp&auth=pw&peer=hy.example.com&insecure=1&upmbps=50&downmbps=100&alpn=h3&obfs=xplus&obfsParam=key#Hy",
+            "wg://2.2.2.2:51820/?pk=cHJpdmF0ZQ==&local_address=10.0.0.2/32&peer_pk=cHVibGlj&pre_shared_key=cHNr&mtu=1280&reserved=1,2,3&jc=4&jmin=40&jmax=70#WG",
+            "tuic://11111111-2222-3333-4444-555555555555:pw@3.3.3.3:443?sni=t.example.com&alpn=h3&congestion_control=bbr&udp_relay_mode=native#Tuic",
+        ]
+        .join("\n");
+        let first = convert(serde_json::json!({ "subscription": subscription }))
+            .await
+            .unwrap();
+        for _ in 0..5 {
+            let again = convert(serde_json::json!({ "subscription": subscription }))
+                .await
+                .unwrap();
+            assert_eq!(again.yaml, first.yaml);
+        }
+        assert_eq!(first.node_count, 3);
+    }
+
+    #[tokio::test]
+    async fn subscription_info_is_fetched_without_parsing() {
+        let (base, _) = serve(vec![(
+            "/sub",
+            vec![Reply::ok("not a subscription")
+                .header(
+                    "subscription-userinfo",
+                    "upload=100; download=200; total=1000; expire=1900000000",
+                )
+                .header(
+                    "content-disposition",
+                    "attachment; filename*=UTF-8''My%20Provider",
+                )],
+        )])
+        .await;
+        let engine = SubscriptionEngine::new(5).unwrap();
+        let (info, file_name) = engine
+            .fetch_subscription_info(&format!("{}/sub", base))
+            .await
+            .unwrap();
+        let info = info.unwrap();
+        assert_eq!(info.upload, Some(100));
+        assert_eq!(info.download, Some(200));
+        assert_eq!(info.total, Some(1000));
+        assert_eq!(info.expire, Some(1_900_000_000));
+        assert_eq!(file_name.as_deref(), Some("My Provider"));
+
+        assert!(engine
+            .fetch_subscription_info("trojan://pw@1.1.1.1:443")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn ipv6_flags_move_together() {
+        let subscription = "trojan://pw@1.1.1.1:443#A\n\
+            wg://2.2.2.2:51820/?pk=cHJpdmF0ZQ==&local_address=10.0.0.2/32&peer_pk=cHVibGlj#WG";
+        let result = convert(serde_json::json!({
+            "subscription": subscription,
+            "enable_ipv6": true,
+        }))
+        .await
+        .unwrap();
+        let config = yaml(&result);
+        assert_eq!(config["ipv6"], true);
+        assert_eq!(config["dns"]["ipv6"], true);
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| w.contains("1 WireGuard node(s) have no IPv6 address")));
+
+        let result = convert(serde_json::json!({ "subscription": subscription }))
+            .await
+            .unwrap();
+        let config = yaml(&result);
+        assert_eq!(config["ipv6"], false);
+        assert_eq!(config["dns"]["ipv6"], false);
+        assert!(!result.warnings.iter().any(|w| w.contains("IPv6")));
+    }
+
+    #[tokio::test]
+    async fn max_nodes_caps_after_filtering_and_renaming() {
+        let subscription = (1..=6)
+            .map(|i| format!("trojan://pw@1.1.1.{}:443#HK-{}", i, i))
+            .chain(["trojan://pw@1.1.1.1:443#HK-dup".to_string()])
+            .chain(["trojan://pw@2.2.2.2:443#US-1".to_string()])
+            .collect::<Vec<_>>()
+            .join("\n");
+        let result = convert(serde_json::json!({
+            "subscription": subscription,
+            "include_regex": "^HK",
+            "rename_pattern": "^HK-",
+            "rename_replacement": "Hong Kong ",
+            "max_nodes": 3,
+        }))
+        .await
+        .unwrap();
+        let config = yaml(&result);
+        let names: Vec<&str> = config["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["Hong Kong 1", "Hong Kong 2", "Hong Kong 3"]);
+        assert!(result
+            .warnings
+            .contains(&"Truncated to 3 of 6 nodes".to_string()));
+
+        let result = convert(serde_json::json!({
+            "subscription": subscription,
+            "max_nodes": 10,
+        }))
+        .await
+        .unwrap();
+        assert!(!result.warnings.iter().any(|w| w.starts_with("Truncated")));
+    }
+
+    #[tokio::test]
+    async fn split_output_references_provider_file() {
+        let result = convert(serde_json::json!({
+            "subscription": "trojan://pw@1.1.1.1:443#A\ntrojan://pw@1.1.1.2:443#B",
+            "output_format": "split",
+        }))
+        .await
+        .unwrap();
+        let config = yaml(&result);
+        assert!(config.get("proxies").is_none());
+        let provider = &config["proxy-providers"][SPLIT_PROVIDER_NAME];
+        assert_eq!(provider["path"], SPLIT_PROVIDER_PATH);
+        assert!(config["proxy-groups"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .any(|g| g["use"][0] == SPLIT_PROVIDER_NAME));
+
+        let providers: serde_yaml::Value =
+            serde_yaml::from_str(result.providers_file.as_deref().unwrap()).unwrap();
+        let names: Vec<&str> = providers["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|p| p["name"].as_str())
+            .collect();
+        assert_eq!(names, ["A", "B"]);
+    }
+
+    #[tokio::test]
+    async fn round_robin_interleaves_sources() {
+        let (base, _) = serve(vec![
+            (
+                "/one",
+                vec![Reply::ok(
+                    "trojan://pw@1.1.1.1:443#A1\n\
+                     trojan://pw@1.1.1.2:443#A2\n\
+                     trojan://pw@1.1.1.3:443#A3",
+                )],
+            ),
+            ("/two", vec![Reply::ok("trojan://pw@2.2.2.1:443#B1")]),
+        ])
+        .await;
+        let names = |order: &'static str| {
+            let base = base.clone();
+            async move {
+                let config = yaml(
+                    &convert(serde_json::json!({
+                        "subscription": format!("{0}/one\n{0}/two", base),
+                        "merge_order": order,
+                    }))
+                    .await
+                    .unwrap(),
+                );
+                config["proxies"]
+                    .as_sequence()
+                    .unwrap()
+                    .iter()
+                    .map(|p| p["name"].as_str().unwrap().to_string())
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(names("round_robin").await, ["A1", "B1", "A2", "A3"]);
+        assert_eq!(names("sequential").await, ["A1", "A2", "A3", "B1"]);
+    }
+
+    #[tokio::test]
+    async fn source_comments_label_each_proxy() {
+        let (base, _) = serve(vec![
+            ("/alpha", vec![Reply::ok("trojan://pw@1.1.1.1:443#A")]),
+            ("/beta", vec![Reply::ok("trojan://pw@2.2.2.2:443#B")]),
+        ])
+        .await;
+        let result = convert(serde_json::json!({
+            "subscription_sources": [
+                { "url": format!("{}/alpha", base), "name": "Alpha" },
+                { "url": format!("{}/beta", base), "name": "Beta" },
+            ],
+            "source_comments": true,
+            "rename_pattern": "^",
+            "rename_replacement": "node-",
+        }))
+        .await
+        .unwrap();
+        assert!(result.yaml.contains("  # from: Alpha\n  - name: node-A\n"));
+        assert!(result.yaml.contains("  # from: Beta\n  - name: node-B\n"));
+        let config = yaml(&result);
+        assert_eq!(config["proxies"].as_sequence().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn source_filters_apply_to_parsed_nodes() {
+        let provider = "proxies:\n\
+                        \x20 - {name: HK-P, type: trojan, server: 3.3.3.1, port: 443, password: pw}\n\
+                        \x20 - {name: US-P, type: trojan, server: 3.3.3.2, port: 443, password: pw}\n";
+        let (base, _) = serve(vec![
+            (
+                "/links",
+                vec![Reply::ok(
+                    "trojan://pw@1.1.1.1:443#HK-A\ntrojan://pw@1.1.1.2:443#US-A",
+                )],
+            ),
+            ("/provider", vec![Reply::ok(provider)]),
+        ])
+        .await;
+        let source = |path: &str, name: &str| serde_json::json!({ "url": format!("{}{}", base, path), "name": name, "include": "^HK" });
+        let result = convert(serde_json::json!({
+            "subscription_sources": [source("/links", "Links")],
+        }))
+        .await
+        .unwrap();
+        let names = |result: &ConvertResult| -> Vec<String> {
+            yaml(result)["proxies"]
+                .as_sequence()
+                .unwrap()
+                .iter()
+                .map(|p| p["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(names(&result), ["HK-A"]);
+        assert!(result
+            .warnings
+            .contains(&"Source 'Links': filtered out 1 node(s)".to_string()));
+
+        // A Clash YAML source is filtered the same way
+        let result = convert(serde_json::json!({
+            "subscription_sources": [source("/provider", "Provider")],
+        }))
+        .await
+        .unwrap();
+        assert_eq!(names(&result), ["HK-P"]);
+        assert!(result
+            .warnings
+            .contains(&"Source 'Provider': filtered out 1 node(s)".to_string()));
+    }
+
+    #[tokio::test]
+    async fn source_comments_label_yaml_sources() {
+        let provider =
+            "proxies:\n  - {name: P, type: trojan, server: 3.3.3.3, port: 443, password: pw}\n";
+        let (base, _) = serve(vec![("/provider", vec![Reply::ok(provider)])]).await;
+        let result = convert(serde_json::json!({
+            "subscription_sources": [{ "url": format!("{}/provider", base), "name": "Provider" }],
+            "source_comments": true,
+        }))
+        .await
+        .unwrap();
+        assert!(result.yaml.contains("  # from: Provider\n  - name: P\n"));
+        assert!(!result
+            .warnings
+            .iter()
+            .any(|w| w.contains("source comments")));
+    }
+
+    #[tokio::test]
+    async fn proxies_only_output_has_no_scaffolding() {
+        let result = convert(serde_json::json!({
+            "subscription": "trojan://pw@1.1.1.1:443#A\ntrojan://pw@1.1.1.2:443#B",
+            "output_format": "proxies_only",
+        }))
+        .await
+        .unwrap();
+        assert!(result.yaml.starts_with("proxies:"));
+        for section in ["dns:", "rules:", "proxy-groups:", "mixed-port:"] {
+            assert!(!result.yaml.contains(section), "{}", section);
+        }
+        let config = yaml(&result);
+        assert_eq!(config["proxies"].as_sequence().unwrap().len(), 2);
+        assert_eq!(config.as_mapping().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn groups_rules_only_output_has_no_proxies() {
+        let result = convert(serde_json::json!({
+            "subscription": "trojan://pw@1.1.1.1:443#A",
+            "ini_content": "[custom]\n\
+                            custom_proxy_group=Proxy`select`.*`[]DIRECT\n\
+                            ruleset=Proxy,https://example.com/proxy.list\n\
+                            ruleset=DIRECT,[]FINAL\n",
+            "output_format": "groups_rules_only",
+        }))
+        .await
+        .unwrap();
+        assert!(!result.yaml.lines().any(|l| l.starts_with("proxies:")));
+        let config = yaml(&result);
+        let keys: Vec<&str> = config
+            .as_mapping()
+            .unwrap()
+            .keys()
+            .filter_map(|k| k.as_str())
+            .collect();
+        assert_eq!(keys, ["proxy-groups", "rule-providers", "rules"]);
+        assert_eq!(config["proxy-groups"][0]["name"], "Proxy");
+        assert_eq!(config["proxy-groups"][0]["proxies"][0], "A");
+        assert_eq!(
+            config["rules"].as_sequence().unwrap().last().unwrap(),
+            "MATCH,DIRECT"
+        );
+    }
+
+    #[tokio::test]
+    async fn base_config_keeps_its_dns() {
+        let base = "mixed-port: 7777\n\
+                    dns:\n\
+                    \x20 enable: true\n\
+                    \x20 nameserver:\n\
+                    \x20   - https://dns.example.com/dns-query\n\
+                    proxies:\n\
+                    \x20 - {name: stale, type: socks5, server: 127.0.0.1, port: 1080}\n";
+        let config = yaml(
+            &convert(serde_json::json!({
+                "subscription": "trojan://pw@1.1.1.1:443#A",
+                "base_config": base,
+            }))
+            .await
+            .unwrap(),
+        );
+        assert_eq!(config["mixed-port"], 7777);
+        assert_eq!(
+            config["dns"]["nameserver"][0],
+            "https://dns.example.com/dns-query"
+        );
+        assert!(config["dns"].get("fake-ip-range").is_none());
+        let proxies = config["proxies"].as_sequence().unwrap();
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(proxies[0]["name"], "A");
+        assert!(config["proxy-groups"].as_sequence().is_some());
+        assert!(config["rules"].as_sequence().is_some());
+
+        let err = convert(serde_json::json!({
+            "subscription": "trojan://pw@1.1.1.1:443#A",
+            "base_config": "- not a mapping",
+        }))
+        .await;
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn retries_is_an_alias_for_fetch_retries() {
+        assert_eq!(request(serde_json::json!({})).fetch_retries, 2);
+        assert_eq!(
+            request(serde_json::json!({ "retries": 0 })).fetch_retries,
+            0
+        );
+        assert_eq!(
+            request(serde_json::json!({ "fetch_retries": 5 })).fetch_retries,
+            5
+        );
+    }
+
+    #[test]
+    fn request_schema_requires_subscription() {
+        let schema = ConvertRequest::json_schema();
+        let required: Vec<&str> = schema["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+        assert_eq!(required, ["subscription"]);
+        assert_eq!(schema["properties"]["subscription"]["type"], "string");
+        assert!(schema["properties"]["ini_url"].is_object());
+    }
+
+    #[tokio::test]
+    async fn auto_groups_inherit_lazy_and_expected_status() {
+        let ini = "[custom]\n\
+                   custom_proxy_group=Select`select`[]Auto`[]Own\n\
+                   custom_proxy_group=Auto`url-test`.*`http://www.gstatic.com/generate_204`300\n\
+                   custom_proxy_group=Own`fallback`.*`http://www.gstatic.com/generate_204`300`expected-status=200\n\
+                   ruleset=Select,[]FINAL\n";
+        for ini_content in [None, Some(ini)] {
+            let config = yaml(
+                &convert(serde_json::json!({
+                    "subscription": "trojan://pw@1.1.1.1:443#A",
+                    "ini_content": ini_content,
+                    "default_group_lazy": true,
+                    "default_expected_status": "204",
+                }))
+                .await
+                .unwrap(),
+            );
+            let mut auto = 0;
+            for group in config["proxy-groups"].as_sequence().unwrap() {
+                match group["type"].as_str().unwrap() {
+                    "url-test" | "fallback" => {
+                        auto += 1;
+                        assert_eq!(group["lazy"], true, "{:?}", group["name"]);
+                        let expected = if group["name"] == "Own" { "200" } else { "204" };
+                        assert_eq!(group["expected-status"], expected, "{:?}", group["name"]);
+                    }
+                    _ => {
+                        assert!(group.get("lazy").is_none());
+                        assert!(group.get("expected-status").is_none());
+                    }
+                }
+            }
+            assert!(auto > 0);
+        }
+    }
+
+    #[test]
+    fn output_format_accepts_clash_and_singbox() {
+        let format =
+            |name: &str| request(serde_json::json!({ "output_format": name })).output_format;
+        assert_eq!(format("clash"), OutputFormat::Full);
+        assert_eq!(format("full"), OutputFormat::Full);
+        assert_eq!(format("singbox"), OutputFormat::SingBox);
+        assert_eq!(
+            request(serde_json::json!({})).output_format,
+            OutputFormat::Full
+        );
+    }
+}
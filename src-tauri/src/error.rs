@@ -31,6 +31,15 @@ pub enum ConvertError {
     #[error("Missing required field: {field} in {context}")]
     MissingField { field: String, context: String },
 
+    #[error("No supported proxies found in {0}")]
+    NoSupportedProxies(String),
+
+    #[error("Duplicate proxy names (rename or filter them): {0}")]
+    DuplicateProxyNames(String),
+
+    #[error("Strict validation of the generated config failed: {0}")]
+    StrictValidation(String),
+
     #[error("Request timeout: {0}")]
     Timeout(String),
 
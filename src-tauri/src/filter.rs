@@ -2,6 +2,7 @@
 
 use crate::error::{ConvertError, Result};
 use crate::node::Node;
+use indexmap::IndexMap;
 use regex::Regex;
 use std::collections::HashSet;
 
@@ -11,25 +12,8 @@ pub fn filter_nodes(
     include_pattern: Option<&str>,
     exclude_pattern: Option<&str>,
 ) -> Result<Vec<Node>> {
-    let include_re = match include_pattern {
-        Some(p) if !p.is_empty() => {
-            Some(Regex::new(p).map_err(|e| ConvertError::InvalidRegex {
-                pattern: p.to_string(),
-                reason: e.to_string(),
-            })?)
-        }
-        _ => None,
-    };
-
-    let exclude_re = match exclude_pattern {
-        Some(p) if !p.is_empty() => {
-            Some(Regex::new(p).map_err(|e| ConvertError::InvalidRegex {
-                pattern: p.to_string(),
-                reason: e.to_string(),
-            })?)
-        }
-        _ => None,
-    };
+    let include_re = compile_optional_regex(include_pattern)?;
+    let exclude_re = compile_optional_regex(exclude_pattern)?;
 
     let filtered: Vec<Node> = nodes
         .into_iter()
@@ -57,6 +41,93 @@ pub fn filter_nodes(
     Ok(filtered)
 }
 
+/// Compile an optional regex pattern; empty patterns are treated as absent
+pub fn compile_optional_regex(pattern: Option<&str>) -> Result<Option<Regex>> {
+    match pattern {
+        Some(p) if !p.is_empty() => {
+            Regex::new(p)
+                .map(Some)
+                .map_err(|e| ConvertError::InvalidRegex {
+                    pattern: p.to_string(),
+                    reason: e.to_string(),
+                })
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Default name patterns for airport info entries (traffic/expiry/website notices)
+pub const DEFAULT_INFO_NODE_PATTERNS: &[&str] =
+    &["流量", "到期", "过期", "官网", "购买", "(?i)expire"];
+
+/// Drop info entries that aren't real proxies (names matching any pattern).
+/// Returns the kept nodes and the number dropped.
+pub fn drop_info_nodes(nodes: Vec<Node>, patterns: &[String]) -> Result<(Vec<Node>, usize)> {
+    let regexes = patterns
+        .iter()
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            Regex::new(p).map_err(|e| ConvertError::InvalidRegex {
+                pattern: p.clone(),
+                reason: e.to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let before = nodes.len();
+    let kept: Vec<Node> = nodes
+        .into_iter()
+        .filter(|node| !regexes.iter().any(|re| re.is_match(node.name())))
+        .collect();
+    let dropped = before - kept.len();
+    Ok((kept, dropped))
+}
+
+/// Compile a server allowlist entry. Plain host globs (`*.trusted.com`, `node?.example.org`)
+/// match the whole server case-insensitively; anything with other regex syntax is a regex.
+fn compile_server_pattern(pattern: &str) -> Result<Regex> {
+    let is_glob = pattern
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | ':' | '*' | '?'));
+    let source = if is_glob {
+        let body = regex::escape(pattern)
+            .replace(r"\*", ".*")
+            .replace(r"\?", ".");
+        format!("(?i)^{}$", body)
+    } else {
+        pattern.to_string()
+    };
+    Regex::new(&source).map_err(|e| ConvertError::InvalidRegex {
+        pattern: pattern.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// Keep only nodes whose server matches at least one allowlist pattern (glob or regex).
+/// An empty allowlist keeps every node. Returns the kept nodes and the number dropped.
+pub fn filter_by_server_allowlist(
+    nodes: Vec<Node>,
+    patterns: &[String],
+) -> Result<(Vec<Node>, usize)> {
+    let regexes = patterns
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(compile_server_pattern)
+        .collect::<Result<Vec<_>>>()?;
+    if regexes.is_empty() {
+        return Ok((nodes, 0));
+    }
+
+    let before = nodes.len();
+    let kept: Vec<Node> = nodes
+        .into_iter()
+        .filter(|node| regexes.iter().any(|re| re.is_match(node.server())))
+        .collect();
+    let dropped = before - kept.len();
+    Ok((kept, dropped))
+}
+
 /// Rename nodes using regex find/replace
 pub fn rename_nodes(
     mut nodes: Vec<Node>,
@@ -81,6 +152,97 @@ pub fn rename_nodes(
     Ok(nodes)
 }
 
+/// Rename nodes by exact name using an `old -> new` table.
+/// Returns the renamed nodes and the table entries that matched no node.
+pub fn rename_by_map(
+    mut nodes: Vec<Node>,
+    map: &IndexMap<String, String>,
+) -> (Vec<Node>, Vec<String>) {
+    let mut used = HashSet::new();
+    for node in &mut nodes {
+        if let Some((old, new)) = map.get_key_value(node.name()) {
+            used.insert(old.as_str());
+            node.set_name(new.clone());
+        }
+    }
+
+    let unused = map
+        .keys()
+        .filter(|k| !used.contains(k.as_str()))
+        .cloned()
+        .collect();
+    (nodes, unused)
+}
+
+/// Shorten node names longer than `max_len` characters to `max_len - 1` characters
+/// plus `…`. Names that collide afterwards get a ` 2`, ` 3`, ... suffix, cut into the
+/// kept prefix. Returns the nodes and the number of names shortened.
+pub fn truncate_node_names(mut nodes: Vec<Node>, max_len: usize) -> (Vec<Node>, usize) {
+    let is_long = |node: &Node| node.name().chars().count() > max_len;
+    let mut taken: HashSet<String> = nodes
+        .iter()
+        .filter(|n| !is_long(n))
+        .map(|n| n.name().to_string())
+        .collect();
+
+    let mut truncated = 0;
+    for node in nodes.iter_mut().filter(|n| is_long(n)) {
+        let chars: Vec<char> = node.name().chars().collect();
+        let shorten = |suffix: &str| {
+            let keep = max_len.saturating_sub(1 + suffix.chars().count());
+            format!("{}…{}", chars[..keep].iter().collect::<String>(), suffix)
+        };
+        let mut name = shorten("");
+        let mut index = 2;
+        while taken.contains(&name) {
+            name = shorten(&format!(" {}", index));
+            index += 1;
+        }
+        taken.insert(name.clone());
+        node.set_name(name);
+        truncated += 1;
+    }
+    (nodes, truncated)
+}
+
+/// Resolve nodes that share a name (mihomo rejects duplicate proxy names): later
+/// ones are dropped when `drop` is set, otherwise renamed with a ` 2`, ` 3`, ... suffix
+/// that no other node uses. Returns the nodes and how many were renamed or dropped.
+pub fn dedup_node_names(nodes: Vec<Node>, drop: bool) -> (Vec<Node>, usize) {
+    let mut taken: HashSet<String> = nodes.iter().map(|n| n.name().to_string()).collect();
+    let mut seen = HashSet::new();
+    let mut changed = 0;
+    let mut kept = Vec::with_capacity(nodes.len());
+    for mut node in nodes {
+        if seen.insert(node.name().to_string()) {
+            kept.push(node);
+            continue;
+        }
+        changed += 1;
+        if drop {
+            continue;
+        }
+        let mut index = 2;
+        let mut name = format!("{} {}", node.name(), index);
+        while taken.contains(&name) {
+            index += 1;
+            name = format!("{} {}", node.name(), index);
+        }
+        taken.insert(name.clone());
+        seen.insert(name.clone());
+        node.set_name(name);
+        kept.push(node);
+    }
+    (kept, changed)
+}
+
+/// Keep the first `max` nodes. Returns the kept nodes and how many were dropped.
+pub fn limit_nodes(mut nodes: Vec<Node>, max: usize) -> (Vec<Node>, usize) {
+    let dropped = nodes.len().saturating_sub(max);
+    nodes.truncate(max);
+    (nodes, dropped)
+}
+
 /// Match nodes against a regex pattern (used for proxy group filtering)
 pub fn match_nodes_by_pattern<'a>(nodes: &'a [Node], pattern: &str) -> Result<Vec<&'a Node>> {
     let re = Regex::new(pattern).map_err(|e| ConvertError::InvalidRegex {
@@ -101,8 +263,210 @@ pub fn deduplicate_nodes(nodes: Vec<Node>) -> Vec<Node> {
         .collect()
 }
 
+/// Aggressive deduplication on `protocol:server:port` only, ignoring credentials and
+/// transport (e.g. tcp and ws variants of one endpoint). CDN-fronted ws/grpc nodes
+/// are still told apart by host. Keeps the first occurrence.
+pub fn deduplicate_nodes_by_endpoint(nodes: Vec<Node>) -> Vec<Node> {
+    let mut seen = HashSet::new();
+    nodes
+        .into_iter()
+        .filter(|node| seen.insert(format!("{}:{}", node.clash_type(), node.service_key())))
+        .collect()
+}
+
+/// An endpoint seen with more than one distinct credential
+#[derive(Debug, Clone)]
+pub struct CredentialConflict {
+    /// Normalized `server:port` (with `@host` for CDN-fronted ws/grpc nodes)
+    pub endpoint: String,
+    /// Number of distinct credentials seen
+    pub credential_count: usize,
+    /// Names of all nodes on the endpoint
+    pub node_names: Vec<String>,
+}
+
+/// Find endpoints (`server:port`) used with several distinct credentials,
+/// e.g. stale entries left behind by a provider rotating UUIDs. Informational only.
+pub fn find_credential_conflicts(nodes: &[Node]) -> Vec<CredentialConflict> {
+    let mut endpoints: IndexMap<String, (HashSet<String>, Vec<String>)> = IndexMap::new();
+    for node in nodes {
+        let (credentials, names) = endpoints.entry(node.service_key()).or_default();
+        credentials.insert(node.credential());
+        names.push(node.name().to_string());
+    }
+
+    endpoints
+        .into_iter()
+        .filter(|(_, (credentials, _))| credentials.len() > 1)
+        .map(|(endpoint, (credentials, node_names))| CredentialConflict {
+            endpoint,
+            credential_count: credentials.len(),
+            node_names,
+        })
+        .collect()
+}
+
+/// Sort nodes by ascending latency (milliseconds, keyed by node name).
+/// Nodes missing from the map are treated as unreachable and placed last;
+/// the sort is stable so ties keep their original order.
+pub fn sort_by_latency(mut nodes: Vec<Node>, latency: &IndexMap<String, u64>) -> Vec<Node> {
+    nodes.sort_by_key(|n| latency.get(n.name()).copied().unwrap_or(u64::MAX));
+    nodes
+}
+
 /// Get node names matching a pattern
 pub fn get_matching_node_names(nodes: &[Node], pattern: &str) -> Result<Vec<String>> {
     let matched = match_nodes_by_pattern(nodes, pattern)?;
     Ok(matched.iter().map(|n| n.name().to_string()).collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_single_link;
+
+    fn nodes(names: &[&str]) -> Vec<Node> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                parse_single_link(&format!("trojan://pw@10.0.0.{}:443#{}", i + 1, name)).unwrap()
+            })
+            .collect()
+    }
+
+    fn names(nodes: &[Node]) -> Vec<&str> {
+        nodes.iter().map(|n| n.name()).collect()
+    }
+
+    #[test]
+    fn info_nodes_are_dropped() {
+        let defaults: Vec<String> = DEFAULT_INFO_NODE_PATTERNS
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let (kept, dropped) = drop_info_nodes(
+            nodes(&["剩余流量：100GB", "香港 01", "Expire: 2026-12-31"]),
+            &defaults,
+        )
+        .unwrap();
+        assert_eq!(names(&kept), ["香港 01"]);
+        assert_eq!(dropped, 2);
+
+        let (kept, dropped) =
+            drop_info_nodes(nodes(&["剩余流量：100GB", "公告"]), &["公告".to_string()]).unwrap();
+        assert_eq!(names(&kept), ["剩余流量：100GB"]);
+        assert_eq!(dropped, 1);
+
+        assert!(drop_info_nodes(nodes(&["A"]), &["(".to_string()]).is_err());
+    }
+
+    #[test]
+    fn server_allowlist_keeps_matching_servers() {
+        let servers = [
+            "hk.trusted.com",
+            "JP.Trusted.com",
+            "trusted.com.evil.net",
+            "eviltrusted.com",
+            "10.1.2.3",
+        ];
+        let nodes: Vec<Node> = servers
+            .iter()
+            .map(|s| parse_single_link(&format!("trojan://pw@{}:443#{}", s, s)).unwrap())
+            .collect();
+
+        let (kept, dropped) =
+            filter_by_server_allowlist(nodes.clone(), &["*.trusted.com".to_string()]).unwrap();
+        assert_eq!(names(&kept), ["hk.trusted.com", "JP.Trusted.com"]);
+        assert_eq!(dropped, 3);
+
+        let (kept, _) = filter_by_server_allowlist(
+            nodes.clone(),
+            &[
+                "*.trusted.com".to_string(),
+                r"^10\.\d+\.\d+\.\d+$".to_string(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            names(&kept),
+            ["hk.trusted.com", "JP.Trusted.com", "10.1.2.3"]
+        );
+
+        let (kept, dropped) = filter_by_server_allowlist(nodes, &[]).unwrap();
+        assert_eq!((kept.len(), dropped), (5, 0));
+    }
+
+    #[test]
+    fn duplicate_names_are_renamed_or_dropped() {
+        let duplicated = || nodes(&["HK", "HK", "HK 2", "HK", "US"]);
+
+        let (kept, renamed) = dedup_node_names(duplicated(), false);
+        assert_eq!(names(&kept), ["HK", "HK 3", "HK 2", "HK 4", "US"]);
+        assert_eq!(renamed, 2);
+
+        let (kept, dropped) = dedup_node_names(duplicated(), true);
+        assert_eq!(names(&kept), ["HK", "HK 2", "US"]);
+        assert_eq!(dropped, 2);
+        // The first of each name is the one kept
+        assert_eq!(kept[0].server(), "10.0.0.1");
+
+        let (kept, changed) = dedup_node_names(nodes(&["A", "B"]), false);
+        assert_eq!((names(&kept), changed), (vec!["A", "B"], 0));
+    }
+
+    #[test]
+    fn long_names_are_truncated_and_kept_unique() {
+        let (kept, shortened) = truncate_node_names(
+            nodes(&[
+                "Hong Kong Premium 01",
+                "Hong Kong Premium 02",
+                "Hong Kong Premium 03",
+                "Tokyo",
+                "Exactly 10",
+            ]),
+            10,
+        );
+        assert_eq!(shortened, 3);
+        assert_eq!(
+            names(&kept),
+            [
+                "Hong Kong…",
+                "Hong Ko… 2",
+                "Hong Ko… 3",
+                "Tokyo",
+                "Exactly 10"
+            ]
+        );
+        assert!(kept.iter().all(|n| n.name().chars().count() <= 10));
+
+        // A short name already taking the truncated form pushes the long one to a suffix
+        let (kept, _) = truncate_node_names(nodes(&["Hong Kong…", "Hong Kong Premium"]), 10);
+        assert_eq!(names(&kept), ["Hong Kong…", "Hong Ko… 2"]);
+    }
+
+    #[test]
+    fn endpoint_dedup_ignores_transport_and_credentials() {
+        let uuid = "11111111-2222-3333-4444-555555555555";
+        let nodes: Vec<Node> = [
+            format!("vless://{}@1.1.1.1:443?type=tcp&security=tls#tcp", uuid),
+            format!(
+                "vless://{}@1.1.1.1:443?type=ws&path=%2Fws&security=tls#ws",
+                uuid.replace('1', "9")
+            ),
+            "trojan://pw@1.1.1.1:443#trojan".to_string(),
+        ]
+        .iter()
+        .map(|l| parse_single_link(l).unwrap())
+        .collect();
+
+        assert_eq!(
+            names(&deduplicate_nodes(nodes.clone())),
+            ["tcp", "ws", "trojan"]
+        );
+        assert_eq!(
+            names(&deduplicate_nodes_by_endpoint(nodes)),
+            ["tcp", "trojan"]
+        );
+    }
+}
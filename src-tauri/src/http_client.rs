@@ -44,17 +44,110 @@ impl SubscriptionInfo {
         }
         info
     }
+
+    /// Remaining traffic in bytes (`total - upload - download`), if the total is known
+    pub fn remaining(&self) -> Option<u64> {
+        let used = self.upload.unwrap_or(0) + self.download.unwrap_or(0);
+        self.total.map(|total| total.saturating_sub(used))
+    }
+
+    /// Short human-readable summary, e.g. `剩余流量: 12.50 GB | 到期: 2026-11-01`
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(remaining) = self.remaining() {
+            parts.push(format!(
+                "剩余流量: {:.2} GB",
+                remaining as f64 / (1024.0 * 1024.0 * 1024.0)
+            ));
+        }
+        if let Some(expire) = self.expire.filter(|e| *e > 0) {
+            parts.push(format!("到期: {}", format_unix_date(expire)));
+        }
+        parts.join(" | ")
+    }
+}
+
+/// Format Unix epoch seconds as a UTC `YYYY-MM-DD` date
+fn format_unix_date(secs: i64) -> String {
+    // Civil-from-days (Howard Hinnant's algorithm)
+    let z = secs.div_euclid(86_400) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Byte count as `10 MiB` / `512 KiB` / `100 bytes`
+fn format_size(bytes: usize) -> String {
+    const KIB: usize = 1024;
+    const MIB: usize = 1024 * KIB;
+    if bytes >= MIB && bytes.is_multiple_of(MIB) {
+        format!("{} MiB", bytes / MIB)
+    } else if bytes >= KIB && bytes.is_multiple_of(KIB) {
+        format!("{} KiB", bytes / KIB)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+/// File name of a `content-disposition` header value, preferring the RFC 5987
+/// `filename*=UTF-8''...` form over plain `filename="..."`
+fn content_disposition_file_name(header_value: &str) -> Option<String> {
+    let mut plain = None;
+    for part in header_value.split(';') {
+        let Some((key, value)) = part.trim().split_once('=') else {
+            continue;
+        };
+        match key.trim().to_ascii_lowercase().as_str() {
+            "filename*" => {
+                let encoded = value.trim().rsplit('\'').next().unwrap_or_default();
+                if let Ok(decoded) = urlencoding::decode(encoded) {
+                    if !decoded.is_empty() {
+                        return Some(decoded.into_owned());
+                    }
+                }
+            }
+            "filename" => {
+                let name = value.trim().trim_matches('"');
+                if !name.is_empty() {
+                    plain = Some(name.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    plain
 }
 
 /// Result of fetching a URL with subscription info
 pub struct FetchWithInfoResult {
     pub body: String,
     pub subscription_info: Option<SubscriptionInfo>,
+    /// File name from the `content-disposition` header (providers use it as profile name)
+    pub file_name: Option<String>,
 }
 
+/// Extra attempts after a transient failure unless configured otherwise
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// Delay before the first retry, doubled for each one after it
+const RETRY_BACKOFF_MS: u64 = 500;
+
+/// Default cap on a response body (10 MiB); larger bodies abort the fetch
+pub const DEFAULT_MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
 /// HTTP client with configured timeout
 pub struct HttpClient {
     client: Client,
+    /// Extra attempts after a transient failure (network error, timeout, 502/503/504)
+    retries: u32,
+    /// Largest response body accepted, in bytes
+    max_body_bytes: usize,
 }
 
 impl HttpClient {
@@ -69,7 +162,24 @@ impl HttpClient {
             .build()
             .map_err(|e| ConvertError::Internal(format!("Failed to create HTTP client: {}", e)))?;
 
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            retries: DEFAULT_RETRIES,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+        })
+    }
+
+    /// Retry transient failures up to `retries` extra times (2 by default, 0 disables).
+    /// Applies to every fetch (subscriptions and INI configs alike).
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Abort fetches whose body exceeds `bytes` (10 MiB by default)
+    pub fn with_max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_bytes = bytes;
+        self
     }
 
     /// Fetch content from a URL
@@ -78,24 +188,51 @@ impl HttpClient {
         Ok(result.body)
     }
 
-    /// Fetch content from a URL, also returning subscription-userinfo if present
+    /// Fetch content from a URL, also returning subscription-userinfo if present.
+    /// Transient failures are retried with an exponential backoff (0.5s, 1s, 2s, ...);
+    /// the returned info is from the attempt that succeeded.
     pub async fn fetch_with_info(&self, url: &str) -> Result<FetchWithInfoResult> {
+        let mut attempt = 0;
+        loop {
+            match self.fetch_once(url).await {
+                Err((_, true)) if attempt < self.retries => {
+                    let delay = RETRY_BACKOFF_MS << attempt.min(6);
+                    attempt += 1;
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                }
+                result => return result.map_err(|(e, _)| e),
+            }
+        }
+    }
+
+    /// Single fetch attempt; the error flag marks failures worth retrying
+    async fn fetch_once(
+        &self,
+        url: &str,
+    ) -> std::result::Result<FetchWithInfoResult, (ConvertError, bool)> {
         let response = self.client.get(url).send().await.map_err(|e| {
-            if e.is_timeout() {
+            let err = if e.is_timeout() {
                 ConvertError::Timeout(url.to_string())
             } else {
                 ConvertError::FetchError {
                     url: url.to_string(),
-                    reason: e.to_string(),
+                    reason: root_cause(&e),
                 }
-            }
+            };
+            (err, true)
         })?;
 
-        if !response.status().is_success() {
-            return Err(ConvertError::FetchError {
-                url: url.to_string(),
-                reason: format!("HTTP {}", response.status()),
-            });
+        let status = response.status();
+        if !status.is_success() {
+            // Gateway errors are usually transient; client errors and other 5xx are not
+            let retryable = matches!(status.as_u16(), 502..=504);
+            return Err((
+                ConvertError::FetchError {
+                    url: url.to_string(),
+                    reason: format!("HTTP {}", status),
+                },
+                retryable,
+            ));
         }
 
         // Extract subscription-userinfo header before consuming the response
@@ -104,21 +241,83 @@ impl HttpClient {
             .get("subscription-userinfo")
             .and_then(|v| v.to_str().ok())
             .map(SubscriptionInfo::parse);
+        let file_name = response
+            .headers()
+            .get("content-disposition")
+            .and_then(|v| v.to_str().ok())
+            .and_then(content_disposition_file_name);
 
-        let body = response
-            .text()
-            .await
-            .map_err(|e| ConvertError::FetchError {
-                url: url.to_string(),
-                reason: e.to_string(),
-            })?;
+        let body = self.read_body(url, response).await?;
 
         Ok(FetchWithInfoResult {
             body,
             subscription_info,
+            file_name,
         })
     }
 
+    /// Read the body chunk by chunk, giving up as soon as it exceeds `max_body_bytes`
+    /// rather than buffering an arbitrarily large response
+    async fn read_body(
+        &self,
+        url: &str,
+        mut response: reqwest::Response,
+    ) -> std::result::Result<String, (ConvertError, bool)> {
+        let too_large = || {
+            (
+                ConvertError::FetchError {
+                    url: url.to_string(),
+                    reason: format!(
+                        "response exceeds the {} limit",
+                        format_size(self.max_body_bytes)
+                    ),
+                },
+                false,
+            )
+        };
+        if response
+            .content_length()
+            .is_some_and(|len| len > self.max_body_bytes as u64)
+        {
+            return Err(too_large());
+        }
+
+        let mut body = Vec::new();
+        loop {
+            let chunk = response.chunk().await.map_err(|e| {
+                (
+                    ConvertError::FetchError {
+                        url: url.to_string(),
+                        reason: root_cause(&e),
+                    },
+                    true,
+                )
+            })?;
+            let Some(chunk) = chunk else {
+                break;
+            };
+            if body.len() + chunk.len() > self.max_body_bytes {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    /// Check whether a URL is reachable (2xx). Tries HEAD first and falls back
+    /// to GET for servers that reject HEAD requests.
+    pub async fn is_reachable(&self, url: &str) -> bool {
+        if let Ok(response) = self.client.head(url).send().await {
+            if response.status().is_success() {
+                return true;
+            }
+        }
+        match self.client.get(url).send().await {
+            Ok(response) => response.status().is_success(),
+            Err(_) => false,
+        }
+    }
+
     /// Fetch multiple URLs concurrently
     pub async fn fetch_all(&self, urls: &[&str]) -> Vec<Result<String>> {
         let futures: Vec<_> = urls.iter().map(|url| self.fetch(url)).collect();
@@ -134,7 +333,199 @@ impl Default for HttpClient {
                 .user_agent(DEFAULT_USER_AGENT)
                 .build()
                 .unwrap_or_else(|_| Client::new());
-            Self { client }
+            Self {
+                client,
+                retries: DEFAULT_RETRIES,
+                max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            }
         })
     }
 }
+
+/// Innermost error message (e.g. `Connection refused`); reqwest's own message only
+/// repeats the URL
+fn root_cause(error: &(dyn std::error::Error + 'static)) -> String {
+    let mut cause = error;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause.to_string()
+}
+
+/// Minimal HTTP/1.1 server for fetch tests: each path answers with its queued
+/// replies in order, repeating the last one
+#[cfg(test)]
+pub(crate) mod mock_server {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Canned response
+    #[derive(Clone)]
+    pub(crate) struct Reply {
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: String,
+    }
+
+    impl Reply {
+        pub(crate) fn ok(body: &str) -> Self {
+            Self::status(200).body(body)
+        }
+
+        pub(crate) fn status(status: u16) -> Self {
+            Self {
+                status,
+                headers: Vec::new(),
+                body: String::new(),
+            }
+        }
+
+        pub(crate) fn body(mut self, body: &str) -> Self {
+            self.body = body.to_string();
+            self
+        }
+
+        pub(crate) fn header(mut self, name: &str, value: &str) -> Self {
+            self.headers.push((name.to_string(), value.to_string()));
+            self
+        }
+    }
+
+    /// Start serving `routes` (path → replies) on a local port; returns the base URL
+    /// (`http://127.0.0.1:port`) and a per-path hit counter. Unknown paths get a 404.
+    pub(crate) async fn serve(
+        routes: Vec<(&str, Vec<Reply>)>,
+    ) -> (String, Arc<Mutex<HashMap<String, usize>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let routes: Arc<HashMap<String, Vec<Reply>>> = Arc::new(
+            routes
+                .into_iter()
+                .map(|(path, replies)| (path.to_string(), replies))
+                .collect(),
+        );
+        let hits = Arc::new(Mutex::new(HashMap::new()));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let routes = routes.clone();
+                let hits = hits.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request);
+                    let mut line = request.lines().next().unwrap_or_default().split(' ');
+                    let method = line.next().unwrap_or_default().to_string();
+                    let path = line.next().unwrap_or_default().to_string();
+                    let hit = {
+                        let mut hits = hits.lock().unwrap();
+                        let count = hits.entry(path.clone()).or_insert(0);
+                        *count += 1;
+                        *count - 1
+                    };
+                    let reply = routes
+                        .get(&path)
+                        .and_then(|replies| replies.get(hit).or(replies.last()))
+                        .cloned()
+                        .unwrap_or_else(|| Reply::status(404));
+                    let mut response = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        reply.status,
+                        reply.body.len()
+                    );
+                    for (name, value) in &reply.headers {
+                        response.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    response.push_str("\r\n");
+                    if method != "HEAD" {
+                        response.push_str(&reply.body);
+                    }
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+        (base, counter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock_server::{serve, Reply};
+    use super::*;
+
+    #[tokio::test]
+    async fn retries_gateway_errors_and_keeps_info_of_successful_attempt() {
+        let (base, hits) = serve(vec![(
+            "/sub",
+            vec![
+                Reply::status(503).header("subscription-userinfo", "upload=1; total=1"),
+                Reply::status(502),
+                Reply::ok("vless://ok")
+                    .header("subscription-userinfo", "upload=10; download=20; total=100"),
+            ],
+        )])
+        .await;
+        let client = HttpClient::new(5).unwrap();
+        let result = client
+            .fetch_with_info(&format!("{}/sub", base))
+            .await
+            .unwrap();
+
+        assert_eq!(result.body, "vless://ok");
+        let info = result.subscription_info.unwrap();
+        assert_eq!(info.upload, Some(10));
+        assert_eq!(info.download, Some(20));
+        assert_eq!(info.total, Some(100));
+        assert_eq!(hits.lock().unwrap()["/sub"], 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let (base, hits) = serve(vec![(
+            "/sub",
+            vec![Reply::status(429), Reply::ok("vless://ok")],
+        )])
+        .await;
+        let client = HttpClient::new(5).unwrap();
+        assert!(client.fetch(&format!("{}/sub", base)).await.is_err());
+        assert_eq!(hits.lock().unwrap()["/sub"], 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_configured_retries() {
+        let (base, hits) = serve(vec![("/sub", vec![Reply::status(504)])]).await;
+        let client = HttpClient::new(5).unwrap().with_retries(1);
+        let err = client.fetch(&format!("{}/sub", base)).await.unwrap_err();
+        assert!(err.to_string().contains("504"));
+        assert_eq!(hits.lock().unwrap()["/sub"], 2);
+    }
+
+    #[tokio::test]
+    async fn oversized_body_is_rejected() {
+        let (base, hits) = serve(vec![
+            ("/big", vec![Reply::ok(&"x".repeat(2048))]),
+            ("/small", vec![Reply::ok(&"x".repeat(1024))]),
+        ])
+        .await;
+        let client = HttpClient::new(5)
+            .unwrap()
+            .with_retries(2)
+            .with_max_body_size(1024);
+        let err = client.fetch(&format!("{}/big", base)).await.unwrap_err();
+        assert!(err.to_string().contains("response exceeds the 1 KiB limit"));
+        // Not retried: the body won't shrink
+        assert_eq!(hits.lock().unwrap()["/big"], 1);
+
+        let body = client.fetch(&format!("{}/small", base)).await.unwrap();
+        assert_eq!(body.len(), 1024);
+    }
+}
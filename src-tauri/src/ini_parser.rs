@@ -4,6 +4,7 @@
 use indexmap::IndexMap;
 use ini::Ini;
 use regex::Regex;
+use serde::Serialize;
 use std::collections::HashSet;
 
 use crate::error::{ConvertError, Result};
@@ -19,8 +20,20 @@ pub struct ParsedProxyGroup {
     pub interval: Option<u32>,
     pub timeout: Option<u32>,
     pub tolerance: Option<u32>,
+    /// Expected HTTP status for health checks (e.g. `204`, `200/302`, `200-299`)
+    pub expected_status: Option<String>,
 }
 
+/// Policies mihomo provides without a group or proxy of that name
+pub const BUILTIN_POLICIES: &[&str] = &[
+    "DIRECT",
+    "REJECT",
+    "REJECT-DROP",
+    "PASS",
+    "COMPATIBLE",
+    "GLOBAL",
+];
+
 /// Matcher for proxies - can be a literal name, regex pattern, or special keyword
 #[derive(Debug, Clone)]
 pub enum ProxyMatcher {
@@ -28,7 +41,7 @@ pub enum ProxyMatcher {
     Literal(String),
     /// Regex pattern to match proxy names
     Pattern(String),
-    /// Special keyword like "[]DIRECT", "[]REJECT", etc.
+    /// Built-in policy like "[]DIRECT", "[]REJECT", "[]REJECT-DROP", "[]PASS" (see `BUILTIN_POLICIES`)
     Special(String),
     /// Include all proxies matching a group (like `[]GroupName`)
     GroupRef(String),
@@ -43,12 +56,57 @@ pub struct ParsedRule {
     pub no_resolve: bool,
 }
 
+/// Global defaults from a `[template]` / `[Advanced]` section
+#[derive(Debug, Clone, Default)]
+pub struct IniDefaults {
+    /// Test URL for url-test/fallback/load-balance groups without their own
+    pub test_url: Option<String>,
+    /// Test interval (seconds) for groups without their own
+    pub interval: Option<u32>,
+    pub udp: Option<bool>,
+    pub tfo: Option<bool>,
+}
+
 /// Result of parsing an INI config file
 #[derive(Debug)]
 pub struct ParsedIniConfig {
     pub proxy_groups: Vec<ParsedProxyGroup>,
     pub rules: Vec<ParsedRule>,
     pub ruleset_urls: Vec<(String, String)>, // (target_group, url)
+    pub defaults: IniDefaults,
+}
+
+/// Group summary for INI preview
+#[derive(Debug, Clone, Serialize)]
+pub struct IniGroupPreview {
+    pub name: String,
+    pub group_type: String,
+}
+
+/// Summary of what an INI config will produce (no nodes needed)
+#[derive(Debug, Clone, Serialize)]
+pub struct IniPreview {
+    pub groups: Vec<IniGroupPreview>,
+    pub rule_count: usize,
+    pub ruleset_count: usize,
+}
+
+impl ParsedIniConfig {
+    /// Summarize groups and rule counts for preview
+    pub fn preview(&self) -> IniPreview {
+        IniPreview {
+            groups: self
+                .proxy_groups
+                .iter()
+                .map(|g| IniGroupPreview {
+                    name: g.name.clone(),
+                    group_type: g.group_type.clone(),
+                })
+                .collect(),
+            rule_count: self.rules.len(),
+            ruleset_count: self.ruleset_urls.len(),
+        }
+    }
 }
 
 /// Parse ACL4SSR INI configuration
@@ -56,16 +114,19 @@ pub fn parse_ini_config(content: &str) -> Result<ParsedIniConfig> {
     let ini =
         Ini::load_from_str(content).map_err(|e| ConvertError::IniParseError(e.to_string()))?;
 
-    let mut proxy_groups = Vec::new();
+    // Groups are keyed by name so a redefinition replaces the earlier one in place:
+    // later lines win within `[custom]` (across repeated sections too), and a group
+    // defined in `[custom]` wins over one of the same name in `[Proxy Group]`
+    let mut proxy_groups: IndexMap<String, ParsedProxyGroup> = IndexMap::new();
     let mut rules = Vec::new();
     let mut ruleset_urls = Vec::new();
 
-    // Parse [custom] section for proxy groups
-    if let Some(custom) = ini.section(Some("custom")) {
+    // Parse [custom] sections for proxy groups
+    for custom in ini.section_all(Some("custom")) {
         for (key, value) in custom.iter() {
             if key == "custom_proxy_group" {
                 if let Some(group) = parse_proxy_group_line(value) {
-                    proxy_groups.push(group);
+                    proxy_groups.insert(group.name.clone(), group);
                 }
             } else if key == "ruleset" {
                 if let Some((target, url_or_rule)) = parse_ruleset_line(value) {
@@ -84,14 +145,18 @@ pub fn parse_ini_config(content: &str) -> Result<ParsedIniConfig> {
         }
     }
 
-    // Parse [Proxy Group] section (alternative format)
-    if let Some(section) = ini.section(Some("Proxy Group")) {
+    // Parse [Proxy Group] sections (alternative format)
+    let mut alt_groups: IndexMap<String, ParsedProxyGroup> = IndexMap::new();
+    for section in ini.section_all(Some("Proxy Group")) {
         for (_, value) in section.iter() {
             if let Some(group) = parse_proxy_group_line(value) {
-                proxy_groups.push(group);
+                alt_groups.insert(group.name.clone(), group);
             }
         }
     }
+    for (name, group) in alt_groups {
+        proxy_groups.entry(name).or_insert(group);
+    }
 
     // Parse [Rule] section
     if let Some(rule_section) = ini.section(Some("Rule")) {
@@ -111,15 +176,48 @@ pub fn parse_ini_config(content: &str) -> Result<ParsedIniConfig> {
         }
     }
 
+    // Parse [template] / [Advanced] sections for global defaults
+    let mut defaults = IniDefaults::default();
+    for section_name in ["template", "Advanced"] {
+        if let Some(section) = ini.section(Some(section_name)) {
+            for (key, value) in section.iter() {
+                apply_default_entry(&mut defaults, key, value);
+            }
+        }
+    }
+
     Ok(ParsedIniConfig {
-        proxy_groups,
+        proxy_groups: proxy_groups.into_values().collect(),
         rules,
         ruleset_urls,
+        defaults,
     })
 }
 
+/// Apply a single `key = value` entry from a template section
+fn apply_default_entry(defaults: &mut IniDefaults, key: &str, value: &str) {
+    let value = value.trim();
+    match key.trim().to_ascii_lowercase().as_str() {
+        "test_url" | "url" if value.starts_with("http://") || value.starts_with("https://") => {
+            defaults.test_url = Some(value.to_string());
+        }
+        "interval" => defaults.interval = value.parse().ok(),
+        "udp" | "udp_flag" => defaults.udp = parse_ini_bool(value),
+        "tfo" | "tfo_flag" | "tcp_fast_open" => defaults.tfo = parse_ini_bool(value),
+        _ => {}
+    }
+}
+
+fn parse_ini_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
 /// Parse a custom_proxy_group line
-/// Format: GroupName`type`proxy1`proxy2`...`[test_url]`[interval[,timeout][,tolerance]]
+/// Format: GroupName`type`proxy1`proxy2`...`[test_url]`[interval[,timeout][,tolerance]]`[expected-status=xxx]
 /// Examples:
 ///   - 🚀节点选择`select`[]♻️自动选择`[]🎯全球直连`.*
 ///   - ♻️自动选择`url-test`.*`http://www.gstatic.com/generate_204`300,,50
@@ -145,6 +243,7 @@ fn parse_proxy_group_line(line: &str) -> Option<ParsedProxyGroup> {
     let mut interval = None;
     let mut timeout = None;
     let mut tolerance = None;
+    let mut expected_status = None;
 
     // For url-test, fallback, load-balance types, we need to parse from the end
     let needs_url_test = matches!(
@@ -153,7 +252,21 @@ fn parse_proxy_group_line(line: &str) -> Option<ParsedProxyGroup> {
     );
 
     // Collect all parts after type
-    let proxy_parts: Vec<&str> = parts.iter().skip(2).map(|s| s.trim()).collect();
+    let mut proxy_parts: Vec<&str> = parts.iter().skip(2).map(|s| s.trim()).collect();
+
+    // Optional trailing `expected-status=...` token (health-check groups only)
+    if needs_url_test {
+        if let Some(status) = proxy_parts.last().and_then(|last| {
+            last.strip_prefix("expected-status=")
+                .or_else(|| last.strip_prefix("expected_status="))
+        }) {
+            let status = status.trim();
+            if !status.is_empty() {
+                expected_status = Some(status.to_string());
+            }
+            proxy_parts.pop();
+        }
+    }
 
     if proxy_parts.is_empty() {
         return Some(ParsedProxyGroup {
@@ -164,6 +277,7 @@ fn parse_proxy_group_line(line: &str) -> Option<ParsedProxyGroup> {
             interval,
             timeout,
             tolerance,
+            expected_status,
         });
     }
 
@@ -214,6 +328,7 @@ fn parse_proxy_group_line(line: &str) -> Option<ParsedProxyGroup> {
         interval,
         timeout,
         tolerance,
+        expected_status,
     })
 }
 
@@ -265,7 +380,7 @@ fn parse_interval_param(s: &str) -> (Option<u32>, Option<u32>, Option<u32>) {
 /// Parse a single proxy matcher
 fn parse_proxy_matcher(part: &str) -> ProxyMatcher {
     if let Some(inner) = part.strip_prefix("[]") {
-        if inner == "DIRECT" || inner == "REJECT" {
+        if BUILTIN_POLICIES.contains(&inner) {
             ProxyMatcher::Special(inner.to_string())
         } else {
             ProxyMatcher::GroupRef(inner.to_string())
@@ -427,10 +542,12 @@ pub fn resolve_proxy_group(
     result
 }
 
-/// Convert parsed groups to Clash format
+/// Convert parsed groups to Clash format.
+/// Groups without their own test URL/interval inherit `defaults`.
 pub fn to_clash_proxy_groups(
     parsed_groups: &[ParsedProxyGroup],
     nodes: &[Node],
+    defaults: &IniDefaults,
 ) -> Vec<IndexMap<String, serde_yaml::Value>> {
     // Collect all group names for reference validation
     let all_group_names: Vec<String> = parsed_groups.iter().map(|g| g.name.clone()).collect();
@@ -456,22 +573,27 @@ pub fn to_clash_proxy_groups(
             || group.group_type == "fallback"
             || group.group_type == "load-balance"
         {
-            if let Some(url) = &group.url {
-                map.insert("url".into(), serde_yaml::Value::String(url.clone()));
-            } else {
-                map.insert(
-                    "url".into(),
-                    serde_yaml::Value::String("http://www.gstatic.com/generate_204".into()),
-                );
-            }
+            let url = group
+                .url
+                .as_deref()
+                .or(defaults.test_url.as_deref())
+                .unwrap_or("http://www.gstatic.com/generate_204");
+            map.insert("url".into(), serde_yaml::Value::String(url.to_string()));
+            let interval = group.interval.or(defaults.interval).unwrap_or(300);
             map.insert(
                 "interval".into(),
-                serde_yaml::Value::Number((group.interval.unwrap_or(300)).into()),
+                serde_yaml::Value::Number(interval.into()),
             );
             // Add timeout if specified
             if let Some(timeout) = group.timeout {
                 map.insert("timeout".into(), serde_yaml::Value::Number(timeout.into()));
             }
+            if let Some(status) = &group.expected_status {
+                map.insert(
+                    "expected-status".into(),
+                    serde_yaml::Value::String(status.clone()),
+                );
+            }
             // Add tolerance (only for url-test)
             if group.group_type == "url-test" {
                 if let Some(tolerance) = group.tolerance {
@@ -507,3 +629,127 @@ pub fn to_clash_rules(parsed_rules: &[ParsedRule]) -> Vec<String> {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_single_link;
+
+    fn nodes(names: &[&str]) -> Vec<Node> {
+        names
+            .iter()
+            .map(|name| parse_single_link(&format!("trojan://pw@1.1.1.1:443#{}", name)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn duplicate_group_definitions_collapse_with_documented_precedence() {
+        let config = parse_ini_config(
+            "[Proxy Group]\n\
+             g1=Proxy`select`[]DIRECT\n\
+             g2=Extra`select`[]REJECT\n\
+             [custom]\n\
+             custom_proxy_group=Proxy`select`.*\n\
+             custom_proxy_group=Media`select`[]Proxy\n\
+             [custom]\n\
+             custom_proxy_group=Proxy`url-test`.*`http://www.gstatic.com/generate_204`300\n",
+        )
+        .unwrap();
+        let groups: Vec<(&str, &str)> = config
+            .proxy_groups
+            .iter()
+            .map(|g| (g.name.as_str(), g.group_type.as_str()))
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                ("Proxy", "url-test"),
+                ("Media", "select"),
+                ("Extra", "select")
+            ]
+        );
+        assert_eq!(config.proxy_groups[0].interval, Some(300));
+    }
+
+    #[test]
+    fn expected_status_is_emitted_for_health_checked_groups() {
+        let config = parse_ini_config(
+            "[custom]\n\
+             custom_proxy_group=LB`load-balance`.*`http://www.gstatic.com/generate_204`300`expected-status=204\n\
+             custom_proxy_group=Plain`url-test`.*`http://www.gstatic.com/generate_204`300\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.proxy_groups[0].expected_status.as_deref(),
+            Some("204")
+        );
+
+        let groups = to_clash_proxy_groups(&config.proxy_groups, &nodes(&["A"]), &config.defaults);
+        assert_eq!(groups[0]["expected-status"], "204");
+        assert_eq!(groups[0]["url"], "http://www.gstatic.com/generate_204");
+        assert_eq!(groups[0]["interval"], 300);
+        assert_eq!(groups[0]["proxies"][0], "A");
+        assert!(!groups[1].contains_key("expected-status"));
+    }
+
+    #[test]
+    fn newer_builtin_policies_pass_through_groups_and_rules() {
+        let config = parse_ini_config(
+            "[custom]\n\
+             custom_proxy_group=Ads`select`[]REJECT-DROP`[]PASS`[]REJECT\n\
+             ruleset=REJECT-DROP,[]DOMAIN-SUFFIX,ads.example.com\n\
+             ruleset=Ads,[]GEOIP,CN\n\
+             ruleset=DIRECT,[]FINAL\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            &config.proxy_groups[0].proxies[0],
+            ProxyMatcher::Special(policy) if policy == "REJECT-DROP"
+        ));
+        assert!(matches!(
+            &config.proxy_groups[0].proxies[1],
+            ProxyMatcher::Special(policy) if policy == "PASS"
+        ));
+
+        let nodes = nodes(&["A"]);
+        let groups = to_clash_proxy_groups(&config.proxy_groups, &nodes, &config.defaults);
+        assert_eq!(
+            groups[0]["proxies"],
+            serde_yaml::to_value(["REJECT-DROP", "PASS", "REJECT"]).unwrap()
+        );
+
+        let mut builder = crate::clash_config::ClashConfigBuilder::new()
+            .with_nodes(&nodes)
+            .with_ini_config(&config, &nodes);
+        let rewrite: IndexMap<String, String> = [("Ads".to_string(), "PASS".to_string())]
+            .into_iter()
+            .collect();
+        assert!(builder.rewrite_rule_targets(&rewrite).is_empty());
+        let yaml = builder.build_yaml().unwrap();
+        crate::clash_config::validate_yaml_strict(&yaml).unwrap();
+        assert!(yaml.contains("DOMAIN-SUFFIX,ads.example.com,REJECT-DROP"));
+    }
+
+    #[test]
+    fn template_defaults_apply_to_groups_without_their_own() {
+        let config = parse_ini_config(
+            "[custom]\n\
+             custom_proxy_group=Auto`url-test`.*\n\
+             custom_proxy_group=Own`url-test`.*`http://cp.cloudflare.com/generate_204`600\n\
+             [template]\n\
+             test_url = https://www.google.com/generate_204\n\
+             interval = 120\n\
+             udp = true\n\
+             tfo = false\n",
+        )
+        .unwrap();
+        assert_eq!(config.defaults.udp, Some(true));
+        assert_eq!(config.defaults.tfo, Some(false));
+
+        let groups = to_clash_proxy_groups(&config.proxy_groups, &nodes(&["A"]), &config.defaults);
+        assert_eq!(groups[0]["url"], "https://www.google.com/generate_204");
+        assert_eq!(groups[0]["interval"], 120);
+        assert_eq!(groups[1]["url"], "http://cp.cloudflare.com/generate_204");
+        assert_eq!(groups[1]["interval"], 600);
+    }
+}
@@ -0,0 +1,45 @@
+//! Node latency probing
+//! Measures TCP connect time to each node's server:port (no proxy handshake)
+
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+use crate::node::Node;
+
+/// Maximum number of concurrent TCP probes
+const MAX_CONCURRENT_PROBES: usize = 32;
+
+/// Latency result for a single node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeLatency {
+    pub name: String,
+    /// Connect time in milliseconds, `None` if unreachable or timed out
+    pub latency_ms: Option<u64>,
+}
+
+/// Measure TCP connect latency to `server:port`.
+/// Returns `None` on connection failure or timeout.
+pub async fn tcp_latency(server: &str, port: u16, timeout: Duration) -> Option<u64> {
+    let start = Instant::now();
+    match tokio::time::timeout(timeout, TcpStream::connect((server, port))).await {
+        Ok(Ok(_)) => Some(start.elapsed().as_millis() as u64),
+        _ => None,
+    }
+}
+
+/// Probe all nodes concurrently, preserving input order in the result
+pub async fn test_nodes_latency(nodes: &[Node], timeout_secs: u64) -> Vec<NodeLatency> {
+    let timeout = Duration::from_secs(timeout_secs.max(1));
+    stream::iter(nodes.iter())
+        .map(|node| async move {
+            NodeLatency {
+                name: node.name().to_string(),
+                latency_ms: tcp_latency(node.server(), node.port(), timeout).await,
+            }
+        })
+        .buffered(MAX_CONCURRENT_PROBES)
+        .collect()
+        .await
+}
@@ -8,13 +8,24 @@ pub mod error;
 pub mod filter;
 pub mod http_client;
 pub mod ini_parser;
+pub mod latency;
 pub mod node;
 pub mod parser;
+pub mod passthrough;
+pub mod region;
+pub mod singbox;
+pub mod surge_config;
 
-use engine::{ConvertRequest, ConvertResult, PresetConfig, SubscriptionEngine};
+use engine::{
+    ClashConfigJson, ConvertRequest, ConvertResult, PresetConfig, SubscriptionEngine,
+    SubscriptionManifest,
+};
 use error::ConvertError;
 use http_client::SubscriptionInfo;
-use serde::Serialize;
+use indexmap::IndexMap;
+use ini_parser::IniPreview;
+use latency::NodeLatency;
+use serde::{Deserialize, Serialize};
 
 // ============================================================================
 // Helper Functions
@@ -41,12 +52,39 @@ fn create_subscription_engine(
 // ============================================================================
 
 /// Node preview info for frontend display
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodePreviewItem {
     pub name: String,
     pub protocol: String,
     pub server: String,
     pub port: u16,
+    /// Region code detected from the name (`HK`, `US`, ...), if any keyword matches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Metadata from the link fragment (e.g. speed, region)
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// Tags from the link fragment (`name@@tag1,tag2`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Detected region of a previewed node
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeRegion {
+    pub name: String,
+    /// Region code such as `HK` or `US`, or `unknown`
+    pub region: String,
+    /// Flag emoji for the region (empty when unknown)
+    pub flag: String,
+}
+
+/// Subscription header info fetched without parsing nodes
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriptionHeaderInfo {
+    pub subscription_info: Option<SubscriptionInfo>,
+    /// Profile file name from `content-disposition`, if the provider sends one
+    pub file_name: Option<String>,
 }
 
 /// Result of parsing nodes for preview
@@ -59,18 +97,93 @@ pub struct ParseNodesResult {
 /// Convert subscription to Clash YAML config
 #[tauri::command]
 async fn convert_subscription(request: ConvertRequest) -> Result<ConvertResult, String> {
-    let engine = create_subscription_engine(request.timeout_secs, request.custom_user_agent.as_ref())
-        .map_err(|e| e.to_string())?;
+    let engine =
+        create_subscription_engine(request.timeout_secs, request.custom_user_agent.as_ref())
+            .map_err(|e| e.to_string())?
+            .with_retries(request.fetch_retries)
+            .with_max_response_mb(request.max_response_mb);
 
     engine.convert(request).await.map_err(|e| e.to_string())
 }
 
+/// Convert the sources of a JSON/YAML manifest (`sources` with per-source filters,
+/// `settings` with request fields)
+#[tauri::command]
+async fn convert_from_manifest(manifest: String) -> Result<ConvertResult, String> {
+    let request = SubscriptionManifest::parse(&manifest)
+        .and_then(SubscriptionManifest::into_request)
+        .map_err(|e| e.to_string())?;
+    convert_subscription(request).await
+}
+
+/// Convert subscription and return the config structure (for tables) alongside the YAML
+#[tauri::command]
+async fn convert_to_structured(request: ConvertRequest) -> Result<ClashConfigJson, String> {
+    let engine =
+        create_subscription_engine(request.timeout_secs, request.custom_user_agent.as_ref())
+            .map_err(|e| e.to_string())?
+            .with_retries(request.fetch_retries)
+            .with_max_response_mb(request.max_response_mb);
+
+    engine
+        .convert_structured(request)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// JSON Schema of `ConvertRequest` for frontend validation and form generation
+#[tauri::command]
+fn get_request_schema() -> serde_json::Value {
+    ConvertRequest::json_schema()
+}
+
 /// Get list of preset INI configurations
 #[tauri::command]
 fn get_preset_configs() -> Vec<PresetConfig> {
     SubscriptionEngine::get_preset_configs()
 }
 
+/// Check which preset INI URLs are reachable (preset name -> reachable)
+#[tauri::command]
+async fn check_preset_availability(
+    timeout_secs: Option<u64>,
+) -> Result<IndexMap<String, bool>, String> {
+    let engine = SubscriptionEngine::new(timeout_secs.unwrap_or(5)).map_err(|e| e.to_string())?;
+    Ok(engine.check_preset_availability().await)
+}
+
+/// Fetch (if URL) and parse an INI config, returning group names/types and rule counts
+#[tauri::command]
+async fn preview_ini(
+    url_or_content: String,
+    timeout_secs: Option<u64>,
+) -> Result<IniPreview, String> {
+    let engine = SubscriptionEngine::new(timeout_secs.unwrap_or(30)).map_err(|e| e.to_string())?;
+    engine
+        .preview_ini(&url_or_content)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Fetch only the traffic/expiry info (and file name) of a subscription URL
+#[tauri::command]
+async fn fetch_subscription_info(
+    url: String,
+    timeout_secs: Option<u64>,
+    custom_user_agent: Option<String>,
+) -> Result<SubscriptionHeaderInfo, String> {
+    let engine = create_subscription_engine(timeout_secs.unwrap_or(15), custom_user_agent.as_ref())
+        .map_err(|e| e.to_string())?;
+    let (subscription_info, file_name) = engine
+        .fetch_subscription_info(&url)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(SubscriptionHeaderInfo {
+        subscription_info,
+        file_name,
+    })
+}
+
 /// Parse subscription content and return node details for preview
 #[tauri::command]
 async fn parse_nodes(
@@ -79,6 +192,7 @@ async fn parse_nodes(
     exclude_regex: Option<String>,
     custom_user_agent: Option<String>,
     timeout_secs: Option<u64>,
+    max_nodes: Option<usize>,
 ) -> Result<ParseNodesResult, String> {
     let timeout = timeout_secs.unwrap_or(30);
     let engine = create_subscription_engine(timeout, custom_user_agent.as_ref())
@@ -100,6 +214,12 @@ async fn parse_nodes(
     // Deduplicate
     let nodes = filter::deduplicate_nodes(nodes);
 
+    // Same cap as conversion, so the preview lists the nodes that will be kept
+    let nodes = match max_nodes.filter(|m| *m > 0) {
+        Some(max) => filter::limit_nodes(nodes, max).0,
+        None => nodes,
+    };
+
     Ok(ParseNodesResult {
         nodes: nodes
             .iter()
@@ -108,12 +228,85 @@ async fn parse_nodes(
                 protocol: n.protocol_type().to_string(),
                 server: n.server().to_string(),
                 port: n.port(),
+                region: region::detect_region(n.name()).map(|r| r.code),
+                meta: n.meta().clone(),
+                tags: n.tags().to_vec(),
             })
             .collect(),
         subscription_info,
     })
 }
 
+/// Test TCP latency of the nodes a conversion of `request` keeps (first step of
+/// latency-sorted conversion): the same filters, renames and `max_nodes` cap apply.
+/// Pass the results as `latency_map` in the same request to order groups fastest-first.
+#[tauri::command]
+async fn test_node_latency(request: ConvertRequest) -> Result<Vec<NodeLatency>, String> {
+    let engine =
+        create_subscription_engine(request.timeout_secs, request.custom_user_agent.as_ref())
+            .map_err(|e| e.to_string())?
+            .with_retries(request.fetch_retries)
+            .with_max_response_mb(request.max_response_mb);
+
+    let nodes = engine
+        .resolve_nodes(&request)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Connect probes are capped at 5s regardless of the fetch timeout
+    Ok(latency::test_nodes_latency(&nodes, request.timeout_secs.min(5)).await)
+}
+
+/// Explain why a single link does (not) parse: scheme, failing field and a suggestion
+#[tauri::command]
+fn explain_link(link: String) -> parser::LinkExplanation {
+    parser::explain_link(&link)
+}
+
+/// Parse a single link and return the proxy YAML it maps to (for debugging one node)
+#[tauri::command]
+fn preview_node_yaml(link: String) -> Result<String, String> {
+    let node = parser::parse_single_link(link.trim()).map_err(|e| e.to_string())?;
+    clash_config::node_proxy_yaml(&node).map_err(|e| e.to_string())
+}
+
+/// Re-emit a pasted Clash config through the builder (consistent quoting and
+/// field order, duplicate proxies removed)
+#[tauri::command]
+fn normalize_config(yaml: String) -> Result<String, String> {
+    passthrough::normalize_config(&yaml)
+        .map(|output| output.yaml)
+        .map_err(|e| e.to_string())
+}
+
+/// Detect each node's region from its name, using the same keyword table as the backend.
+/// `region_keywords` (region code → keywords) is checked before the built-in table.
+#[tauri::command]
+fn detect_regions(
+    nodes: Vec<NodePreviewItem>,
+    region_keywords: Option<IndexMap<String, Vec<String>>>,
+) -> Result<Vec<NodeRegion>, String> {
+    let table = match region_keywords {
+        Some(keywords) => region::RegionTable::with_custom(&keywords).map_err(|e| e.to_string())?,
+        None => region::RegionTable::default(),
+    };
+    Ok(nodes
+        .into_iter()
+        .map(|n| match table.detect(&n.name) {
+            Some(region) => NodeRegion {
+                region: region.code.clone(),
+                flag: region.flag.clone(),
+                name: n.name,
+            },
+            None => NodeRegion {
+                region: region::UNKNOWN_REGION.to_string(),
+                flag: String::new(),
+                name: n.name,
+            },
+        })
+        .collect())
+}
+
 /// Validate regex pattern
 #[tauri::command]
 fn validate_regex(pattern: String) -> Result<bool, String> {
@@ -142,8 +335,19 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             convert_subscription,
+            convert_from_manifest,
+            convert_to_structured,
             get_preset_configs,
+            get_request_schema,
+            check_preset_availability,
+            preview_ini,
+            fetch_subscription_info,
             parse_nodes,
+            test_node_latency,
+            explain_link,
+            preview_node_yaml,
+            normalize_config,
+            detect_regions,
             validate_regex,
             fetch_url,
         ])
@@ -1,9 +1,32 @@
 //! Node definitions for various proxy protocols
-//! Supports: VLESS, VMess, Shadowsocks, ShadowsocksR, Trojan, Hysteria2, TUIC
+//! Supports: VLESS, VMess, Shadowsocks, ShadowsocksR, Trojan, Hysteria2, TUIC,
+//! WireGuard, SOCKS5, HTTP(S) and Snell
 
 use indexmap::IndexMap;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+/// `match` over every variant, binding the protocol struct to `$n` (for fields all
+/// node structs share)
+macro_rules! each_node {
+    ($node:expr, $n:ident => $body:expr) => {
+        match $node {
+            Node::Vless($n) => $body,
+            Node::Vmess($n) => $body,
+            Node::Shadowsocks($n) => $body,
+            Node::Ssr($n) => $body,
+            Node::Trojan($n) => $body,
+            Node::Hysteria($n) => $body,
+            Node::Hysteria2($n) => $body,
+            Node::Tuic($n) => $body,
+            Node::WireGuard($n) => $body,
+            Node::Socks5($n) => $body,
+            Node::Http($n) => $body,
+            Node::Snell($n) => $body,
+        }
+    };
+}
+
 /// Unified node enum supporting all major proxy protocols
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
@@ -21,6 +44,9 @@ pub enum Node {
     Tuic(TuicNode),
     #[serde(rename = "wireguard")]
     WireGuard(WireGuardNode),
+    Socks5(Socks5Node),
+    Http(HttpNode),
+    Snell(SnellNode),
 }
 
 impl Node {
@@ -35,6 +61,9 @@ impl Node {
             Node::Hysteria2(n) => &n.name,
             Node::Tuic(n) => &n.name,
             Node::WireGuard(n) => &n.name,
+            Node::Socks5(n) => &n.name,
+            Node::Http(n) => &n.name,
+            Node::Snell(n) => &n.name,
         }
     }
 
@@ -49,9 +78,85 @@ impl Node {
             Node::Hysteria2(n) => n.name = name,
             Node::Tuic(n) => n.name = name,
             Node::WireGuard(n) => n.name = name,
+            Node::Socks5(n) => n.name = name,
+            Node::Http(n) => n.name = name,
+            Node::Snell(n) => n.name = name,
+        }
+    }
+
+    /// Extra metadata from the link fragment (`name|key:value|...`); shown in previews
+    /// but never emitted to Clash
+    pub fn meta(&self) -> &IndexMap<String, String> {
+        each_node!(self, n => &n.meta)
+    }
+
+    pub fn meta_mut(&mut self) -> &mut IndexMap<String, String> {
+        each_node!(self, n => &mut n.meta)
+    }
+
+    /// Tags from the link fragment (`name@@tag1,tag2`); each becomes a select group
+    pub fn tags(&self) -> &[String] {
+        each_node!(self, n => &n.tags)
+    }
+
+    pub fn tags_mut(&mut self) -> &mut Vec<String> {
+        each_node!(self, n => &mut n.tags)
+    }
+
+    /// Whether mihomo accepts `smux` on this node (VLESS/VMess/Trojan; not with VLESS flow)
+    pub fn supports_smux(&self) -> bool {
+        match self {
+            Node::Vless(n) => n.flow.as_deref().is_none_or(str::is_empty),
+            Node::Vmess(_) | Node::Trojan(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Clash key holding the TLS server name (`servername` or `sni`), for the
+    /// protocols whose SNI can be overridden
+    pub fn sni_key(&self) -> Option<&'static str> {
+        match self {
+            Node::Vless(_) | Node::Vmess(_) => Some("servername"),
+            Node::Trojan(_) | Node::Hysteria2(_) | Node::Tuic(_) => Some("sni"),
+            _ => None,
+        }
+    }
+
+    /// Host header used as SNI for a ws+tls node that has no SNI of its own
+    pub fn sni_host_fallback(&self) -> Option<&str> {
+        match self {
+            Node::Vless(n) => n.sni_host_fallback(),
+            Node::Vmess(n) => n.sni_host_fallback(),
+            Node::Trojan(n) => n.sni_host_fallback(),
+            _ => None,
         }
     }
 
+    /// Turn on TLS for a VLESS/VMess node on port 443/8443 that has a servername or
+    /// ws Host but no explicit TLS setting (Trojan is always TLS). Returns whether it fired.
+    pub fn infer_tls_on_443(&mut self) -> bool {
+        let (tls, port, has_host) = match self {
+            Node::Vless(n) => (
+                &mut n.tls,
+                n.port,
+                non_empty(n.servername.as_deref()).is_some()
+                    || ws_host_header(&n.ws_opts).is_some(),
+            ),
+            Node::Vmess(n) => (
+                &mut n.tls,
+                n.port,
+                non_empty(n.servername.as_deref()).is_some()
+                    || ws_host_header(&n.ws_opts).is_some(),
+            ),
+            _ => return false,
+        };
+        if tls.is_some() || !matches!(port, 443 | 8443) || !has_host {
+            return false;
+        }
+        *tls = Some(true);
+        true
+    }
+
     pub fn to_clash_proxy(&self) -> IndexMap<String, serde_yaml::Value> {
         match self {
             Node::Vless(n) => n.to_clash_map(),
@@ -63,6 +168,75 @@ impl Node {
             Node::Hysteria2(n) => n.to_clash_map(),
             Node::Tuic(n) => n.to_clash_map(),
             Node::WireGuard(n) => n.to_clash_map(),
+            Node::Socks5(n) => n.to_clash_map(),
+            Node::Http(n) => n.to_clash_map(),
+            Node::Snell(n) => n.to_clash_map(),
+        }
+    }
+
+    /// Normalized `server:port` the node connects to
+    pub fn endpoint_key(&self) -> String {
+        format!("{}:{}", normalize_server(self.server()), self.port())
+    }
+
+    /// `endpoint_key` plus the virtual host of CDN-fronted (ws/grpc) nodes: the ws `Host`
+    /// header, else the SNI. Such services often share one CDN address and port.
+    pub fn service_key(&self) -> String {
+        let (network, ws_opts, sni) = match self {
+            Node::Vless(n) => (n.network.as_str(), &n.ws_opts, n.servername.as_deref()),
+            Node::Vmess(n) => (
+                n.network.as_deref().unwrap_or("tcp"),
+                &n.ws_opts,
+                n.servername.as_deref(),
+            ),
+            Node::Trojan(n) => (
+                n.network.as_deref().unwrap_or("tcp"),
+                &n.ws_opts,
+                n.sni.as_deref(),
+            ),
+            _ => return self.endpoint_key(),
+        };
+        let host = match network {
+            "ws" => ws_host_header(ws_opts).or(non_empty(sni)),
+            "grpc" => non_empty(sni),
+            _ => None,
+        };
+        match host {
+            Some(host) => format!("{}@{}", self.endpoint_key(), host.to_ascii_lowercase()),
+            None => self.endpoint_key(),
+        }
+    }
+
+    /// The secret identifying the user on the server (uuid, password, key, ...)
+    pub fn credential(&self) -> String {
+        match self {
+            Node::Vless(n) => n.uuid.clone(),
+            Node::Vmess(n) => n.uuid.clone(),
+            Node::Shadowsocks(n) => n.password.clone(),
+            Node::Ssr(n) => n.password.clone(),
+            Node::Trojan(n) => n.password.clone(),
+            Node::Hysteria(n) => n.auth_str.clone().unwrap_or_default(),
+            Node::Hysteria2(n) => n.password.clone(),
+            Node::Tuic(n) => match &n.token {
+                Some(token) => token.clone(),
+                None => format!(
+                    "{}:{}",
+                    n.uuid.as_deref().unwrap_or_default(),
+                    n.password.as_deref().unwrap_or_default()
+                ),
+            },
+            Node::WireGuard(n) => n.private_key.clone(),
+            Node::Socks5(n) => format!(
+                "{}:{}",
+                n.username.as_deref().unwrap_or_default(),
+                n.password.as_deref().unwrap_or_default()
+            ),
+            Node::Http(n) => format!(
+                "{}:{}",
+                n.username.as_deref().unwrap_or_default(),
+                n.password.as_deref().unwrap_or_default()
+            ),
+            Node::Snell(n) => n.psk.clone(),
         }
     }
 
@@ -72,13 +246,14 @@ impl Node {
             Node::Vless(n) => {
                 let mut parts = vec![
                     "vless".to_string(),
-                    n.server.clone(),
+                    normalize_server(&n.server),
                     n.port.to_string(),
                     n.uuid.clone(),
                     format!("network={}", n.network),
                 ];
                 push_opt_str(&mut parts, "flow", n.flow.as_deref());
-                push_opt_bool(&mut parts, "tls", n.tls);
+                // Emitted as `tls: false` when unset, so unset and false are the same node
+                push_opt_bool(&mut parts, "tls", Some(n.tls.unwrap_or(false)));
                 push_opt_str(&mut parts, "servername", n.servername.as_deref());
                 push_opt_bool(&mut parts, "skip-cert-verify", n.skip_cert_verify);
                 push_opt_vec(&mut parts, "alpn", &n.alpn);
@@ -100,6 +275,11 @@ impl Node {
                         parts.push(format!("h2={}", key));
                     }
                 }
+                if let Some(xhttp) = &n.xhttp_opts {
+                    if let Some(key) = xhttp_key(xhttp) {
+                        parts.push(format!("xhttp={}", key));
+                    }
+                }
                 push_opt_str(
                     &mut parts,
                     "client-fingerprint",
@@ -112,7 +292,7 @@ impl Node {
                 let network = n.network.as_deref().unwrap_or("tcp");
                 let mut parts = vec![
                     "vmess".to_string(),
-                    n.server.clone(),
+                    normalize_server(&n.server),
                     n.port.to_string(),
                     n.uuid.clone(),
                     format!("alterId={}", n.alterId),
@@ -142,7 +322,7 @@ impl Node {
             Node::Shadowsocks(n) => {
                 let mut parts = vec![
                     "ss".to_string(),
-                    n.server.clone(),
+                    normalize_server(&n.server),
                     n.port.to_string(),
                     n.cipher.clone(),
                     n.password.clone(),
@@ -154,7 +334,7 @@ impl Node {
             Node::Ssr(n) => {
                 let mut parts = vec![
                     "ssr".to_string(),
-                    n.server.clone(),
+                    normalize_server(&n.server),
                     n.port.to_string(),
                     n.cipher.clone(),
                     n.password.clone(),
@@ -170,7 +350,7 @@ impl Node {
                 let network = n.network.as_deref().unwrap_or("tcp");
                 let mut parts = vec![
                     "trojan".to_string(),
-                    n.server.clone(),
+                    normalize_server(&n.server),
                     n.port.to_string(),
                     n.password.clone(),
                     format!("network={}", network),
@@ -198,7 +378,7 @@ impl Node {
             Node::Hysteria(n) => {
                 let mut parts = vec![
                     "hy".to_string(),
-                    n.server.clone(),
+                    normalize_server(&n.server),
                     n.port.to_string(),
                     n.auth_str.clone().unwrap_or_default(),
                 ];
@@ -215,7 +395,7 @@ impl Node {
             Node::Hysteria2(n) => {
                 let mut parts = vec![
                     "hy2".to_string(),
-                    n.server.clone(),
+                    normalize_server(&n.server),
                     n.port.to_string(),
                     n.password.clone(),
                 ];
@@ -234,7 +414,7 @@ impl Node {
                 let id = n.uuid.as_deref().or(n.token.as_deref()).unwrap_or("");
                 let mut parts = vec![
                     "tuic".to_string(),
-                    n.server.clone(),
+                    normalize_server(&n.server),
                     n.port.to_string(),
                     id.to_string(),
                 ];
@@ -255,7 +435,7 @@ impl Node {
             Node::WireGuard(n) => {
                 let mut parts = vec![
                     "wg".to_string(),
-                    n.server.clone(),
+                    normalize_server(&n.server),
                     n.port.to_string(),
                     n.public_key.clone(),
                 ];
@@ -267,11 +447,74 @@ impl Node {
                 push_opt_vec_u8(&mut parts, "reserved", &n.reserved);
                 push_opt_u32(&mut parts, "mtu", n.mtu);
                 push_opt_vec(&mut parts, "dns", &n.dns);
+                push_opt_u32(&mut parts, "keepalive", n.persistent_keepalive);
+                if let Some(peers) = &n.peers {
+                    for peer in peers {
+                        parts.push(format!("peer={}", peer.dedup_key()));
+                    }
+                }
+                parts.join("|")
+            }
+            Node::Socks5(n) => {
+                let mut parts = vec![
+                    "socks5".to_string(),
+                    normalize_server(&n.server),
+                    n.port.to_string(),
+                ];
+                push_opt_str(&mut parts, "username", n.username.as_deref());
+                push_opt_str(&mut parts, "password", n.password.as_deref());
+                push_opt_bool(&mut parts, "tls", Some(n.tls.unwrap_or(false)));
+                push_opt_bool(&mut parts, "skip-cert-verify", n.skip_cert_verify);
+                parts.join("|")
+            }
+            Node::Http(n) => {
+                let mut parts = vec![
+                    "http".to_string(),
+                    normalize_server(&n.server),
+                    n.port.to_string(),
+                ];
+                push_opt_str(&mut parts, "username", n.username.as_deref());
+                push_opt_str(&mut parts, "password", n.password.as_deref());
+                push_opt_bool(&mut parts, "tls", Some(n.tls.unwrap_or(false)));
+                push_opt_str(&mut parts, "sni", n.sni.as_deref());
+                push_opt_bool(&mut parts, "skip-cert-verify", n.skip_cert_verify);
+                parts.join("|")
+            }
+            Node::Snell(n) => {
+                let mut parts = vec![
+                    "snell".to_string(),
+                    normalize_server(&n.server),
+                    n.port.to_string(),
+                    n.psk.clone(),
+                    format!("version={}", n.version),
+                ];
+                if let Some(obfs) = &n.obfs_opts {
+                    push_opt_str(&mut parts, "obfs", Some(&obfs.mode));
+                    push_opt_str(&mut parts, "obfs-host", obfs.host.as_deref());
+                }
                 parts.join("|")
             }
         }
     }
 
+    /// Clash `type` value (also the canonical link scheme), e.g. `ss`, `hysteria2`
+    pub fn clash_type(&self) -> &'static str {
+        match self {
+            Node::Vless(_) => "vless",
+            Node::Vmess(_) => "vmess",
+            Node::Shadowsocks(_) => "ss",
+            Node::Ssr(_) => "ssr",
+            Node::Trojan(_) => "trojan",
+            Node::Hysteria(_) => "hysteria",
+            Node::Hysteria2(_) => "hysteria2",
+            Node::Tuic(_) => "tuic",
+            Node::WireGuard(_) => "wireguard",
+            Node::Socks5(_) => "socks5",
+            Node::Http(_) => "http",
+            Node::Snell(_) => "snell",
+        }
+    }
+
     /// Protocol type string for display
     pub fn protocol_type(&self) -> &str {
         match self {
@@ -284,6 +527,9 @@ impl Node {
             Node::Hysteria2(_) => "Hysteria2",
             Node::Tuic(_) => "TUIC",
             Node::WireGuard(_) => "WireGuard",
+            Node::Socks5(_) => "SOCKS5",
+            Node::Http(_) => "HTTP",
+            Node::Snell(_) => "Snell",
         }
     }
 
@@ -299,6 +545,52 @@ impl Node {
             Node::Hysteria2(n) => &n.server,
             Node::Tuic(n) => &n.server,
             Node::WireGuard(n) => &n.server,
+            Node::Socks5(n) => &n.server,
+            Node::Http(n) => &n.server,
+            Node::Snell(n) => &n.server,
+        }
+    }
+
+    /// Whether the connection is TLS-wrapped (QUIC-based protocols always are)
+    pub fn tls(&self) -> bool {
+        match self {
+            Node::Vless(n) => n.tls.unwrap_or(false) || n.reality_opts.is_some(),
+            Node::Vmess(n) => n.tls.unwrap_or(false),
+            Node::Trojan(_) | Node::Hysteria(_) | Node::Hysteria2(_) | Node::Tuic(_) => true,
+            Node::Socks5(n) => n.tls.unwrap_or(false),
+            Node::Http(n) => n.tls.unwrap_or(false),
+            Node::Shadowsocks(_) | Node::Ssr(_) | Node::WireGuard(_) | Node::Snell(_) => false,
+        }
+    }
+
+    /// QUIC- and WireGuard-based protocols, which only carry traffic over UDP
+    pub fn is_udp_native(&self) -> bool {
+        matches!(
+            self,
+            Node::Hysteria(_) | Node::Hysteria2(_) | Node::Tuic(_) | Node::WireGuard(_)
+        )
+    }
+
+    /// WireGuard tunnels with only an IPv4 interface address, which can't carry IPv6 traffic
+    pub fn is_ipv4_only(&self) -> bool {
+        match self {
+            Node::WireGuard(n) => n.ip.is_some() && n.ipv6.as_deref().is_none_or(str::is_empty),
+            _ => false,
+        }
+    }
+
+    /// Transport network for display (ws, grpc, tcp, udp, ...)
+    pub fn network(&self) -> &str {
+        match self {
+            Node::Vless(n) => &n.network,
+            Node::Vmess(n) => n.network.as_deref().unwrap_or("tcp"),
+            Node::Trojan(n) => n.network.as_deref().unwrap_or("tcp"),
+            Node::Shadowsocks(_)
+            | Node::Ssr(_)
+            | Node::Socks5(_)
+            | Node::Http(_)
+            | Node::Snell(_) => "tcp",
+            Node::Hysteria(_) | Node::Hysteria2(_) | Node::Tuic(_) | Node::WireGuard(_) => "udp",
         }
     }
 
@@ -314,10 +606,41 @@ impl Node {
             Node::Hysteria2(n) => n.port,
             Node::Tuic(n) => n.port,
             Node::WireGuard(n) => n.port,
+            Node::Socks5(n) => n.port,
+            Node::Http(n) => n.port,
+            Node::Snell(n) => n.port,
         }
     }
 }
 
+/// Render nodes as CSV (name, protocol, server, port, tls, network) with a header row
+pub fn nodes_to_csv(nodes: &[Node]) -> String {
+    let mut output = String::from("name,protocol,server,port,tls,network\n");
+    for node in nodes {
+        let fields = [
+            node.name().to_string(),
+            node.protocol_type().to_string(),
+            node.server().to_string(),
+            node.port().to_string(),
+            node.tls().to_string(),
+            node.network().to_string(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        output.push_str(&row.join(","));
+        output.push('\n');
+    }
+    output
+}
+
+/// Quote a CSV field when it contains a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
 fn join_kv_map(map: &IndexMap<String, String>) -> String {
     map.iter()
         .map(|(k, v)| format!("{k}={v}"))
@@ -325,6 +648,11 @@ fn join_kv_map(map: &IndexMap<String, String>) -> String {
         .join(",")
 }
 
+/// Normalize a server host for dedup: case-insensitive, ignore a trailing root dot
+fn normalize_server(server: &str) -> String {
+    server.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
 fn push_opt_str(parts: &mut Vec<String>, key: &str, value: Option<&str>) {
     if let Some(value) = value {
         if !value.is_empty() {
@@ -392,6 +720,19 @@ fn ws_key(ws: &WsOpts) -> Option<String> {
 fn grpc_key(grpc: &GrpcOpts) -> Option<String> {
     let mut parts = Vec::new();
     push_opt_str(&mut parts, "service", grpc.grpc_service_name.as_deref());
+    push_opt_str(&mut parts, "mode", grpc.grpc_mode.as_deref());
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(";"))
+    }
+}
+
+fn xhttp_key(xhttp: &XhttpOpts) -> Option<String> {
+    let mut parts = Vec::new();
+    push_opt_str(&mut parts, "path", xhttp.path.as_deref());
+    push_opt_str(&mut parts, "host", xhttp.host.as_deref());
+    push_opt_str(&mut parts, "mode", xhttp.mode.as_deref());
     if parts.is_empty() {
         None
     } else {
@@ -449,15 +790,40 @@ pub struct VlessNode {
     pub grpc_opts: Option<GrpcOpts>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub h2_opts: Option<H2Opts>,
+    /// XHTTP (SplitHTTP) options for `network: xhttp`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xhttp_opts: Option<XhttpOpts>,
     /// uTLS client fingerprint (chrome, firefox, safari, etc.)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub client_fingerprint: Option<String>,
     /// UDP packet encoding: xudp (xray) or packetaddr (v2ray 5+)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub packet_encoding: Option<String>,
+    /// TCP Fast Open from link (overrides the global switch when set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tfo: Option<bool>,
+    /// `|key:value` metadata from the fragment; not emitted to Clash
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// `@@tag` labels from the fragment; each becomes a select group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl VlessNode {
+    /// Host header to use as SNI when ws+tls is set without a servername (not for Reality)
+    pub fn sni_host_fallback(&self) -> Option<&str> {
+        if self.reality_opts.is_some() {
+            return None;
+        }
+        ws_host_sni_fallback(
+            self.tls.unwrap_or(false),
+            self.servername.as_deref(),
+            &self.network,
+            &self.ws_opts,
+        )
+    }
+
     /// Convert to Clash YAML map with correct field order for VLESS Reality
     /// Order matters! Mihomo requires: name, type, server, port, uuid, udp, tls, network, flow, servername, reality-opts, client-fingerprint
     pub fn to_clash_map(&self) -> IndexMap<String, serde_yaml::Value> {
@@ -491,11 +857,9 @@ impl VlessNode {
             }
         }
 
-        // 5. Servername (SNI)
-        if let Some(sni) = &self.servername {
-            if !sni.is_empty() {
-                map.insert("servername".into(), v_str(sni));
-            }
+        // 5. Servername (SNI), falling back to the ws Host header
+        if let Some(sni) = non_empty(self.servername.as_deref()).or(self.sni_host_fallback()) {
+            map.insert("servername".into(), v_str(sni));
         }
 
         // 6. Skip cert verify (if present)
@@ -529,7 +893,7 @@ impl VlessNode {
             }
         }
 
-        // 10. Network-specific options (ws-opts, grpc-opts, h2-opts)
+        // 10. Network-specific options (ws-opts, grpc-opts, h2-opts, xhttp-opts)
         insert_transport_opts(
             &mut map,
             &self.network,
@@ -537,6 +901,27 @@ impl VlessNode {
             &self.grpc_opts,
             &self.h2_opts,
         );
+        if self.network == "xhttp" {
+            if let Some(xhttp) = &self.xhttp_opts {
+                let mut m = serde_yaml::Mapping::new();
+                if let Some(path) = non_empty(xhttp.path.as_deref()) {
+                    m.insert(v_key("path"), v_str(path));
+                }
+                if let Some(host) = non_empty(xhttp.host.as_deref()) {
+                    m.insert(v_key("host"), v_str(host));
+                }
+                if let Some(mode) = non_empty(xhttp.mode.as_deref()) {
+                    m.insert(v_key("mode"), v_str(mode));
+                }
+                if !m.is_empty() {
+                    map.insert("xhttp-opts".into(), serde_yaml::Value::Mapping(m));
+                }
+            }
+        }
+
+        if let Some(tfo) = self.tfo {
+            map.insert("tfo".into(), v_bool(tfo));
+        }
 
         map
     }
@@ -570,9 +955,28 @@ pub struct VmessNode {
     pub h2_opts: Option<H2Opts>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub grpc_opts: Option<GrpcOpts>,
+    /// TCP Fast Open from link (overrides the global switch when set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tfo: Option<bool>,
+    /// `|key:value` metadata from the fragment; not emitted to Clash
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// `@@tag` labels from the fragment; each becomes a select group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl VmessNode {
+    /// Host header to use as SNI when ws+tls is set without a servername
+    pub fn sni_host_fallback(&self) -> Option<&str> {
+        ws_host_sni_fallback(
+            self.tls.unwrap_or(false),
+            self.servername.as_deref(),
+            self.network.as_deref().unwrap_or("tcp"),
+            &self.ws_opts,
+        )
+    }
+
     pub fn to_clash_map(&self) -> IndexMap<String, serde_yaml::Value> {
         let mut map = IndexMap::new();
         map.insert("name".into(), v_str(&self.name));
@@ -599,10 +1003,8 @@ impl VmessNode {
             map.insert("skip-cert-verify".into(), v_bool(skip));
         }
 
-        if let Some(sni) = &self.servername {
-            if !sni.is_empty() {
-                map.insert("servername".into(), v_str(sni));
-            }
+        if let Some(sni) = non_empty(self.servername.as_deref()).or(self.sni_host_fallback()) {
+            map.insert("servername".into(), v_str(sni));
         }
 
         let network = self.network.as_deref().unwrap_or("tcp");
@@ -614,6 +1016,10 @@ impl VmessNode {
             &self.h2_opts,
         );
 
+        if let Some(tfo) = self.tfo {
+            map.insert("tfo".into(), v_bool(tfo));
+        }
+
         map
     }
 }
@@ -635,6 +1041,15 @@ pub struct ShadowsocksNode {
     pub plugin: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub plugin_opts: Option<IndexMap<String, String>>,
+    /// TCP Fast Open from link (overrides the global switch when set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tfo: Option<bool>,
+    /// `|key:value` metadata from the fragment; not emitted to Clash
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// `@@tag` labels from the fragment; each becomes a select group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl ShadowsocksNode {
@@ -665,6 +1080,10 @@ impl ShadowsocksNode {
             }
         }
 
+        if let Some(tfo) = self.tfo {
+            map.insert("tfo".into(), v_bool(tfo));
+        }
+
         map
     }
 }
@@ -688,6 +1107,12 @@ pub struct SsrNode {
     pub obfs_param: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub group: Option<String>,
+    /// `|key:value` metadata from the fragment; not emitted to Clash
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// `@@tag` labels from the fragment; each becomes a select group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl SsrNode {
@@ -744,9 +1169,28 @@ pub struct TrojanNode {
     /// uTLS client fingerprint
     #[serde(skip_serializing_if = "Option::is_none")]
     pub client_fingerprint: Option<String>,
+    /// TCP Fast Open from link (overrides the global switch when set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tfo: Option<bool>,
+    /// `|key:value` metadata from the fragment; not emitted to Clash
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// `@@tag` labels from the fragment; each becomes a select group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl TrojanNode {
+    /// Host header to use as SNI for ws transport without an sni (trojan is always TLS)
+    pub fn sni_host_fallback(&self) -> Option<&str> {
+        ws_host_sni_fallback(
+            true,
+            self.sni.as_deref(),
+            self.network.as_deref().unwrap_or("tcp"),
+            &self.ws_opts,
+        )
+    }
+
     pub fn to_clash_map(&self) -> IndexMap<String, serde_yaml::Value> {
         let mut map = IndexMap::new();
         map.insert("name".into(), v_str(&self.name));
@@ -756,10 +1200,8 @@ impl TrojanNode {
         map.insert("password".into(), v_str(&self.password));
         map.insert("udp".into(), v_bool(true));
 
-        if let Some(sni) = &self.sni {
-            if !sni.is_empty() {
-                map.insert("sni".into(), v_str(sni));
-            }
+        if let Some(sni) = non_empty(self.sni.as_deref()).or(self.sni_host_fallback()) {
+            map.insert("sni".into(), v_str(sni));
         }
 
         if let Some(skip) = self.skip_cert_verify {
@@ -783,6 +1225,10 @@ impl TrojanNode {
             insert_transport_opts(&mut map, network, &self.ws_opts, &self.grpc_opts, &None);
         }
 
+        if let Some(tfo) = self.tfo {
+            map.insert("tfo".into(), v_bool(tfo));
+        }
+
         map
     }
 }
@@ -814,6 +1260,12 @@ pub struct HysteriaNode {
     pub alpn: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fingerprint: Option<String>,
+    /// `|key:value` metadata from the fragment; not emitted to Clash
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// `@@tag` labels from the fragment; each becomes a select group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl HysteriaNode {
@@ -895,6 +1347,12 @@ pub struct Hysteria2Node {
     pub up: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub down: Option<String>,
+    /// `|key:value` metadata from the fragment; not emitted to Clash
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// `@@tag` labels from the fragment; each becomes a select group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl Hysteria2Node {
@@ -978,6 +1436,12 @@ pub struct TuicNode {
     pub udp_relay_mode: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub congestion_controller: Option<String>,
+    /// `|key:value` metadata from the fragment; not emitted to Clash
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// `@@tag` labels from the fragment; each becomes a select group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl TuicNode {
@@ -1061,17 +1525,158 @@ pub struct WireGuardNode {
     /// DNS servers for remote resolution
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dns: Option<Vec<String>>,
+    /// Keepalive interval in seconds (keeps NAT mappings alive, common for WARP)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persistent_keepalive: Option<u32>,
+    /// Multiple peers (mihomo `peers`); replaces the flat server/public-key fields
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peers: Option<Vec<WgPeer>>,
+    /// AmneziaWG obfuscation parameters (mihomo `amnezia-wg-option`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amnezia: Option<AmneziaOpts>,
+    /// `|key:value` metadata from the fragment; not emitted to Clash
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// `@@tag` labels from the fragment; each becomes a select group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// A single WireGuard peer for multi-peer setups
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WgPeer {
+    pub server: String,
+    pub port: u16,
+    pub public_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_shared_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_ips: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reserved: Option<Vec<u8>>,
+}
+
+impl WgPeer {
+    fn to_clash_value(&self) -> serde_yaml::Value {
+        let mut m = serde_yaml::Mapping::new();
+        m.insert(v_key("server"), v_str(&self.server));
+        m.insert(v_key("port"), v_num(self.port));
+        m.insert(v_key("public-key"), v_str(&self.public_key));
+        if let Some(psk) = &self.pre_shared_key {
+            if !psk.is_empty() {
+                m.insert(v_key("pre-shared-key"), v_str(psk));
+            }
+        }
+        if let Some(reserved) = &self.reserved {
+            if !reserved.is_empty() {
+                m.insert(v_key("reserved"), v_u8_seq(reserved));
+            }
+        }
+        match &self.allowed_ips {
+            Some(allowed_ips) if !allowed_ips.is_empty() => {
+                m.insert(v_key("allowed-ips"), v_str_seq(allowed_ips));
+            }
+            _ => {
+                m.insert(v_key("allowed-ips"), v_str_seq(&default_wg_allowed_ips()));
+            }
+        }
+        serde_yaml::Value::Mapping(m)
+    }
+
+    fn dedup_key(&self) -> String {
+        let mut parts = vec![
+            normalize_server(&self.server),
+            self.port.to_string(),
+            self.public_key.clone(),
+        ];
+        push_opt_vec(&mut parts, "allowed-ips", &self.allowed_ips);
+        push_opt_vec_u8(&mut parts, "reserved", &self.reserved);
+        parts.join(";")
+    }
+}
+
+/// AmneziaWG junk-packet and header obfuscation parameters
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AmneziaOpts {
+    /// Junk packet count
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jc: Option<u32>,
+    /// Minimum junk packet size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jmin: Option<u32>,
+    /// Maximum junk packet size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jmax: Option<u32>,
+    /// Init packet junk size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s1: Option<u32>,
+    /// Response packet junk size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s2: Option<u32>,
+    /// Header magic values for the four message types
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub h1: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub h2: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub h3: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub h4: Option<u32>,
+}
+
+impl AmneziaOpts {
+    fn fields(&self) -> [(&'static str, Option<u32>); 9] {
+        [
+            ("jc", self.jc),
+            ("jmin", self.jmin),
+            ("jmax", self.jmax),
+            ("s1", self.s1),
+            ("s2", self.s2),
+            ("h1", self.h1),
+            ("h2", self.h2),
+            ("h3", self.h3),
+            ("h4", self.h4),
+        ]
+    }
+
+    /// True when no parameter is set
+    pub fn is_empty(&self) -> bool {
+        self.fields().iter().all(|(_, v)| v.is_none())
+    }
+
+    /// Clash `amnezia-wg-option:` mapping (only the parameters that are set)
+    pub fn to_clash_value(&self) -> serde_yaml::Value {
+        let mut m = serde_yaml::Mapping::new();
+        for (key, value) in self.fields() {
+            if let Some(n) = value {
+                m.insert(v_key(key), serde_yaml::Value::Number(n.into()));
+            }
+        }
+        serde_yaml::Value::Mapping(m)
+    }
+}
+
+/// Full-tunnel allowed IPs (mihomo's own default)
+fn default_wg_allowed_ips() -> Vec<String> {
+    vec!["0.0.0.0/0".to_string(), "::/0".to_string()]
 }
 
 impl WireGuardNode {
     pub fn to_clash_map(&self) -> IndexMap<String, serde_yaml::Value> {
+        let peers = self.peers.as_ref().filter(|p| !p.is_empty());
+
         let mut map = IndexMap::new();
         map.insert("name".into(), v_str(&self.name));
         map.insert("type".into(), v_str("wireguard"));
-        map.insert("server".into(), v_str(&self.server));
-        map.insert("port".into(), v_num(self.port));
+        // With multiple peers, server/port/public-key live inside each peer entry
+        if peers.is_none() {
+            map.insert("server".into(), v_str(&self.server));
+            map.insert("port".into(), v_num(self.port));
+        }
         map.insert("private-key".into(), v_str(&self.private_key));
-        map.insert("public-key".into(), v_str(&self.public_key));
+        if peers.is_none() {
+            map.insert("public-key".into(), v_str(&self.public_key));
+        }
 
         if let Some(ip) = &self.ip {
             if !ip.is_empty() {
@@ -1086,18 +1691,16 @@ impl WireGuardNode {
                 map.insert("ipv6".into(), v_str(ipv6_clean));
             }
         }
-        if let Some(psk) = &self.pre_shared_key {
-            if !psk.is_empty() {
-                map.insert("pre-shared-key".into(), v_str(psk));
+        if peers.is_none() {
+            if let Some(psk) = &self.pre_shared_key {
+                if !psk.is_empty() {
+                    map.insert("pre-shared-key".into(), v_str(psk));
+                }
             }
-        }
-        if let Some(reserved) = &self.reserved {
-            if !reserved.is_empty() {
-                let seq: Vec<serde_yaml::Value> = reserved
-                    .iter()
-                    .map(|v| serde_yaml::Value::Number((*v).into()))
-                    .collect();
-                map.insert("reserved".into(), serde_yaml::Value::Sequence(seq));
+            if let Some(reserved) = &self.reserved {
+                if !reserved.is_empty() {
+                    map.insert("reserved".into(), v_u8_seq(reserved));
+                }
             }
         }
 
@@ -1115,16 +1718,204 @@ impl WireGuardNode {
             }
         }
 
-        if let Some(allowed_ips) = &self.allowed_ips {
-            if !allowed_ips.is_empty() {
-                map.insert("allowed-ips".into(), v_str_seq(allowed_ips));
+        // Allowed IPs default to full tunnel (mihomo's own default) when unspecified
+        if peers.is_none() {
+            match &self.allowed_ips {
+                Some(allowed_ips) if !allowed_ips.is_empty() => {
+                    map.insert("allowed-ips".into(), v_str_seq(allowed_ips));
+                }
+                _ => {
+                    map.insert("allowed-ips".into(), v_str_seq(&default_wg_allowed_ips()));
+                }
+            }
+        }
+
+        if let Some(keepalive) = self.persistent_keepalive {
+            map.insert(
+                "persistent-keepalive".into(),
+                serde_yaml::Value::Number(keepalive.into()),
+            );
+        }
+
+        if let Some(amnezia) = self.amnezia.as_ref().filter(|a| !a.is_empty()) {
+            map.insert("amnezia-wg-option".into(), amnezia.to_clash_value());
+        }
+
+        if let Some(peers) = peers {
+            map.insert(
+                "peers".into(),
+                serde_yaml::Value::Sequence(peers.iter().map(WgPeer::to_clash_value).collect()),
+            );
+        }
+
+        map
+    }
+}
+
+// ============================================================================
+// SOCKS5 Node
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Socks5Node {
+    pub name: String,
+    pub server: String,
+    pub port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_cert_verify: Option<bool>,
+    /// `|key:value` metadata from the fragment; not emitted to Clash
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// `@@tag` labels from the fragment; each becomes a select group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Socks5Node {
+    pub fn to_clash_map(&self) -> IndexMap<String, serde_yaml::Value> {
+        let mut map = IndexMap::new();
+        map.insert("name".into(), v_str(&self.name));
+        map.insert("type".into(), v_str("socks5"));
+        map.insert("server".into(), v_str(&self.server));
+        map.insert("port".into(), v_num(self.port));
+        insert_proxy_auth(&mut map, &self.username, &self.password);
+        map.insert("udp".into(), v_bool(true));
+        if self.tls == Some(true) {
+            map.insert("tls".into(), v_bool(true));
+            if let Some(skip) = self.skip_cert_verify {
+                map.insert("skip-cert-verify".into(), v_bool(skip));
             }
         }
+        map
+    }
+}
 
+// ============================================================================
+// HTTP(S) Node
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpNode {
+    pub name: String,
+    pub server: String,
+    pub port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// HTTPS proxy (`https://` links)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_cert_verify: Option<bool>,
+    /// `|key:value` metadata from the fragment; not emitted to Clash
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// `@@tag` labels from the fragment; each becomes a select group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl HttpNode {
+    pub fn to_clash_map(&self) -> IndexMap<String, serde_yaml::Value> {
+        let mut map = IndexMap::new();
+        map.insert("name".into(), v_str(&self.name));
+        map.insert("type".into(), v_str("http"));
+        map.insert("server".into(), v_str(&self.server));
+        map.insert("port".into(), v_num(self.port));
+        insert_proxy_auth(&mut map, &self.username, &self.password);
+        if self.tls == Some(true) {
+            map.insert("tls".into(), v_bool(true));
+            if let Some(sni) = non_empty(self.sni.as_deref()) {
+                map.insert("sni".into(), v_str(sni));
+            }
+            if let Some(skip) = self.skip_cert_verify {
+                map.insert("skip-cert-verify".into(), v_bool(skip));
+            }
+        }
         map
     }
 }
 
+// ============================================================================
+// Snell Node
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnellNode {
+    pub name: String,
+    pub server: String,
+    pub port: u16,
+    pub psk: String,
+    /// Protocol version; links without `version=` are v1
+    #[serde(default = "default_snell_version")]
+    pub version: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfs_opts: Option<SnellObfsOpts>,
+    /// `|key:value` metadata from the fragment; not emitted to Clash
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub meta: IndexMap<String, String>,
+    /// `@@tag` labels from the fragment; each becomes a select group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Snell `obfs-opts`; `mode` is `http` or `tls`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnellObfsOpts {
+    pub mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+
+/// Snell version assumed when a link or config doesn't say
+pub fn default_snell_version() -> u8 {
+    1
+}
+
+impl SnellNode {
+    pub fn to_clash_map(&self) -> IndexMap<String, serde_yaml::Value> {
+        let mut map = IndexMap::new();
+        map.insert("name".into(), v_str(&self.name));
+        map.insert("type".into(), v_str("snell"));
+        map.insert("server".into(), v_str(&self.server));
+        map.insert("port".into(), v_num(self.port));
+        map.insert("psk".into(), v_str(&self.psk));
+        map.insert("version".into(), v_num(self.version.into()));
+        if let Some(obfs) = &self.obfs_opts {
+            let mut opts = serde_yaml::Mapping::new();
+            opts.insert(v_key("mode"), v_str(&obfs.mode));
+            if let Some(host) = non_empty(obfs.host.as_deref()) {
+                opts.insert(v_key("host"), v_str(host));
+            }
+            map.insert("obfs-opts".into(), serde_yaml::Value::Mapping(opts));
+        }
+        map
+    }
+}
+
+/// `username`/`password` of SOCKS5 and HTTP proxies, omitted when empty
+fn insert_proxy_auth(
+    map: &mut IndexMap<String, serde_yaml::Value>,
+    username: &Option<String>,
+    password: &Option<String>,
+) {
+    if let Some(username) = non_empty(username.as_deref()) {
+        map.insert("username".into(), v_str(username));
+    }
+    if let Some(password) = non_empty(password.as_deref()) {
+        map.insert("password".into(), v_str(password));
+    }
+}
+
 // ============================================================================
 // Common Options Structs
 // ============================================================================
@@ -1148,6 +1939,21 @@ pub struct WsOpts {
 pub struct GrpcOpts {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub grpc_service_name: Option<String>,
+    /// Transport mode, `gun` or `multi` (emitted as `_grpc-type`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_mode: Option<String>,
+}
+
+/// XHTTP (SplitHTTP) transport options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct XhttpOpts {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// auto, packet-up, stream-up or stream-one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1158,6 +1964,49 @@ pub struct H2Opts {
     pub host: Option<Vec<String>>,
 }
 
+/// sing-box multiplex options (`smux:`); presence means enabled
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct SmuxOpts {
+    /// smux, yamux or h2mux (mihomo defaults to h2mux)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_streams: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_streams: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding: Option<bool>,
+}
+
+impl SmuxOpts {
+    /// Clash `smux:` mapping
+    pub fn to_clash_value(&self) -> serde_yaml::Value {
+        let mut m = serde_yaml::Mapping::new();
+        m.insert(v_key("enabled"), v_bool(true));
+        if let Some(protocol) = self.protocol.as_deref().filter(|p| !p.is_empty()) {
+            m.insert(v_key("protocol"), v_str(protocol));
+        }
+        if let Some(n) = self.max_connections {
+            m.insert(
+                v_key("max-connections"),
+                serde_yaml::Value::Number(n.into()),
+            );
+        }
+        if let Some(n) = self.min_streams {
+            m.insert(v_key("min-streams"), serde_yaml::Value::Number(n.into()));
+        }
+        if let Some(n) = self.max_streams {
+            m.insert(v_key("max-streams"), serde_yaml::Value::Number(n.into()));
+        }
+        if let Some(padding) = self.padding {
+            m.insert(v_key("padding"), v_bool(padding));
+        }
+        serde_yaml::Value::Mapping(m)
+    }
+}
+
 // ============================================================================
 // YAML helpers (reduce boilerplate)
 // ============================================================================
@@ -1178,6 +2027,15 @@ fn v_num(n: u16) -> serde_yaml::Value {
     serde_yaml::Value::Number(n.into())
 }
 
+fn v_u8_seq(items: &[u8]) -> serde_yaml::Value {
+    serde_yaml::Value::Sequence(
+        items
+            .iter()
+            .map(|v| serde_yaml::Value::Number((*v).into()))
+            .collect(),
+    )
+}
+
 fn v_str_seq(items: &[String]) -> serde_yaml::Value {
     serde_yaml::Value::Sequence(
         items
@@ -1187,6 +2045,35 @@ fn v_str_seq(items: &[String]) -> serde_yaml::Value {
     )
 }
 
+fn non_empty(s: Option<&str>) -> Option<&str> {
+    s.filter(|s| !s.is_empty())
+}
+
+/// For ws+tls without an SNI, the ws `Host` header (mihomo would otherwise use the server address)
+fn ws_host_sni_fallback<'a>(
+    tls: bool,
+    sni: Option<&str>,
+    network: &str,
+    ws_opts: &'a Option<WsOpts>,
+) -> Option<&'a str> {
+    if !tls || non_empty(sni).is_some() || network != "ws" {
+        return None;
+    }
+    ws_host_header(ws_opts)
+}
+
+/// Non-empty `Host` header of the ws options
+fn ws_host_header(ws_opts: &Option<WsOpts>) -> Option<&str> {
+    ws_opts
+        .as_ref()?
+        .headers
+        .as_ref()?
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("host"))
+        .map(|(_, v)| v.trim())
+        .filter(|v| !v.is_empty())
+}
+
 /// Insert transport options (ws-opts, grpc-opts, h2-opts) based on network type
 fn insert_transport_opts(
     map: &mut IndexMap<String, serde_yaml::Value>,
@@ -1220,6 +2107,9 @@ fn insert_transport_opts(
                 if let Some(sn) = &grpc.grpc_service_name {
                     m.insert(v_key("grpc-service-name"), v_str(sn));
                 }
+                if let Some(mode) = &grpc.grpc_mode {
+                    m.insert(v_key("_grpc-type"), v_str(mode));
+                }
                 if !m.is_empty() {
                     map.insert("grpc-opts".into(), serde_yaml::Value::Mapping(m));
                 }
@@ -1336,3 +2226,195 @@ pub fn is_valid_ssr_cipher(cipher: &str) -> bool {
     let normalized = normalize_cipher(cipher);
     SSR_VALID_CIPHERS.contains(&normalized.as_str())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_single_link;
+
+    fn proxy(link: &str) -> IndexMap<String, serde_yaml::Value> {
+        parse_single_link(link).unwrap().to_clash_proxy()
+    }
+
+    #[test]
+    fn tls_inference_fires_only_on_documented_conditions() {
+        let uuid = "11111111-2222-3333-4444-555555555555";
+        let infer = |rest: &str| {
+            let mut node = parse_single_link(&format!("vless://{}@{}", uuid, rest)).unwrap();
+            let fired = node.infer_tls_on_443();
+            (fired, node.to_clash_proxy().get("tls").cloned())
+        };
+        let on = Some(serde_yaml::Value::Bool(true));
+
+        assert_eq!(
+            infer("1.1.1.1:443?sni=cdn.example.com#a"),
+            (true, on.clone())
+        );
+        assert_eq!(
+            infer("1.1.1.1:8443?type=ws&host=cdn.example.com#b"),
+            (true, on)
+        );
+        assert!(!infer("1.1.1.1:80?sni=cdn.example.com#c").0);
+        assert!(!infer("1.1.1.1:443#d").0);
+        assert!(!infer("1.1.1.1:443?security=none&sni=cdn.example.com#e").0);
+
+        let mut trojan =
+            parse_single_link("trojan://pw@1.1.1.1:443?sni=cdn.example.com#t").unwrap();
+        assert!(!trojan.infer_tls_on_443());
+    }
+
+    #[test]
+    fn csv_quotes_commas_and_quotes() {
+        let nodes: Vec<Node> = [
+            "trojan://pw@1.1.1.1:443#HK, Premium",
+            "trojan://pw@1.1.1.2:443#The %22Fast%22 one",
+            "trojan://pw@1.1.1.3:443#Plain",
+        ]
+        .iter()
+        .map(|l| parse_single_link(l).unwrap())
+        .collect();
+        let csv = nodes_to_csv(&nodes);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "name,protocol,server,port,tls,network");
+        assert_eq!(lines[1], "\"HK, Premium\",Trojan,1.1.1.1,443,true,tcp");
+        assert_eq!(
+            lines[2],
+            "\"The \"\"Fast\"\" one\",Trojan,1.1.1.2,443,true,tcp"
+        );
+        assert_eq!(lines[3], "Plain,Trojan,1.1.1.3,443,true,tcp");
+    }
+
+    #[test]
+    fn ws_tls_without_sni_uses_host_header() {
+        let uuid = "11111111-2222-3333-4444-555555555555";
+        let link = format!(
+            "vless://{}@1.2.3.4:443?type=ws&security=tls&host=cdn.example.com&path=%2Fws#v",
+            uuid
+        );
+        let node = parse_single_link(&link).unwrap();
+        assert_eq!(node.sni_host_fallback(), Some("cdn.example.com"));
+        let map = node.to_clash_proxy();
+        assert_eq!(map["servername"], "cdn.example.com");
+        assert_eq!(map["ws-opts"]["headers"]["Host"], "cdn.example.com");
+
+        let map = proxy("trojan://pw@1.2.3.4:443?type=ws&host=cdn.example.com#t");
+        assert_eq!(map["sni"], "cdn.example.com");
+
+        let node =
+            parse_single_link(&format!("{}&sni=real.example.com", link.replace("#v", ""))).unwrap();
+        assert_eq!(node.sni_host_fallback(), None);
+        assert_eq!(node.to_clash_proxy()["servername"], "real.example.com");
+
+        let plain = link.replace("security=tls", "security=none");
+        assert_eq!(parse_single_link(&plain).unwrap().sni_host_fallback(), None);
+    }
+
+    #[test]
+    fn dedup_key_normalizes_server_case_and_trailing_dot() {
+        let key = |link: &str| parse_single_link(link).unwrap().dedup_key();
+        let plain = key("trojan://pw@example.com:443#A");
+        assert_eq!(key("trojan://pw@Example.com.:443#B"), plain);
+        assert_eq!(key("trojan://pw@EXAMPLE.COM:443#C"), plain);
+        assert_ne!(key("trojan://pw@example.org:443#D"), plain);
+
+        let nodes = [
+            "trojan://pw@Example.com.:443#A",
+            "trojan://pw@example.com:443#B",
+        ]
+        .iter()
+        .map(|l| parse_single_link(l).unwrap())
+        .collect();
+        let deduped = crate::filter::deduplicate_nodes(nodes);
+        assert_eq!(deduped.len(), 1);
+        assert_eq!(deduped[0].name(), "A");
+    }
+
+    #[test]
+    fn cdn_fronted_nodes_are_told_apart_by_host() {
+        let uuid = "11111111-2222-3333-4444-555555555555";
+        let nodes: Vec<Node> = [
+            format!(
+                "vless://{}@104.16.0.1:443?type=ws&host=a.example.com&security=tls#A",
+                uuid
+            ),
+            format!(
+                "vless://{}@104.16.0.1:443?type=ws&host=B.example.com&security=tls#B",
+                uuid
+            ),
+            format!(
+                "vless://{}@104.16.0.1:443?type=ws&host=b.example.com&security=tls#B2",
+                uuid
+            ),
+            format!(
+                "vless://{}@104.16.0.1:443?type=grpc&sni=c.example.com&security=tls#C",
+                uuid
+            ),
+            format!(
+                "vless://{}@104.16.0.1:443?type=tcp&sni=d.example.com&security=tls#D",
+                uuid
+            ),
+        ]
+        .iter()
+        .map(|l| parse_single_link(l).unwrap())
+        .collect();
+        assert_eq!(nodes[0].service_key(), "104.16.0.1:443@a.example.com");
+        assert_eq!(nodes[4].service_key(), nodes[4].endpoint_key());
+
+        let kept = crate::filter::deduplicate_nodes_by_endpoint(nodes.clone());
+        let names: Vec<&str> = kept.iter().map(|n| n.name()).collect();
+        assert_eq!(names, ["A", "B", "C", "D"]);
+        assert_eq!(
+            crate::filter::deduplicate_nodes(nodes[..2].to_vec()).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn wireguard_keepalive_and_allowed_ips() {
+        let map = proxy("wireguard://1.2.3.4:51820?pk=cHJpdg&peer_pk=cHVi&keepalive=25#wg");
+        assert_eq!(map["persistent-keepalive"], 25);
+        assert_eq!(map["allowed-ips"][0], "0.0.0.0/0");
+        assert_eq!(map["allowed-ips"][1], "::/0");
+
+        let map = proxy(
+            "wireguard://1.2.3.4:51820?pk=cHJpdg&peer_pk=cHVi&allowed_ips=10.0.0.0/8,172.16.0.0/12#wg",
+        );
+        assert!(map.get("persistent-keepalive").is_none());
+        let allowed: Vec<&str> = map["allowed-ips"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|ip| ip.as_str())
+            .collect();
+        assert_eq!(allowed, ["10.0.0.0/8", "172.16.0.0/12"]);
+    }
+
+    #[test]
+    fn awg_params_emit_obfuscation_block() {
+        let map = proxy(
+            "wireguard://1.2.3.4:51820?pk=cHJpdg&peer_pk=cHVi\
+             &jc=4&jmin=40&jmax=70&s1=15&s2=99&h1=1&h2=2&h3=3&h4=4#awg",
+        );
+        let awg = &map["amnezia-wg-option"];
+        for (key, value) in [
+            ("jc", 4),
+            ("jmin", 40),
+            ("jmax", 70),
+            ("s1", 15),
+            ("s2", 99),
+            ("h1", 1),
+            ("h2", 2),
+            ("h3", 3),
+            ("h4", 4),
+        ] {
+            assert_eq!(awg[key], value, "{}", key);
+        }
+
+        let map = proxy("wireguard://1.2.3.4:51820?pk=cHJpdg&peer_pk=cHVi&jc=3#partial");
+        assert_eq!(map["amnezia-wg-option"]["jc"], 3);
+        assert!(map["amnezia-wg-option"].get("jmin").is_none());
+
+        let map = proxy("wireguard://1.2.3.4:51820?pk=cHJpdg&peer_pk=cHVi#plain");
+        assert!(!map.contains_key("amnezia-wg-option"));
+    }
+}
@@ -0,0 +1,420 @@
+//! Passthrough mode for full Clash YAML input
+//! Filters/renames the `proxies` list while keeping groups, rules, DNS, etc. intact,
+//! or re-bakes a pasted config through the builder (`normalize_config`)
+
+use indexmap::IndexMap;
+use regex::Regex;
+use serde_yaml::Value;
+use std::collections::{HashMap, HashSet};
+
+use crate::clash_config::{ClashConfigBuilder, RuleProvider};
+use crate::error::{ConvertError, Result};
+use crate::filter::compile_optional_regex;
+use crate::parser::parse_clash_yaml;
+
+/// Outcome of rewriting a full Clash config
+#[derive(Debug, Clone)]
+pub struct PassthroughOutput {
+    pub yaml: String,
+    pub node_count: usize,
+    pub filtered_count: usize,
+    pub group_count: usize,
+    pub rule_count: usize,
+    pub warnings: Vec<String>,
+}
+
+/// Parse content as a full Clash config.
+/// Returns `None` unless it is a YAML mapping with a `proxies` sequence.
+pub fn parse_clash_document(content: &str) -> Option<Value> {
+    let content = content.strip_prefix('\u{FEFF}').unwrap_or(content);
+    let mut doc: Value = serde_yaml::from_str(content).ok()?;
+    // Resolve `<<:` merge keys so proxies built from anchors keep their fields
+    doc.apply_merge().ok()?;
+    match doc.get("proxies") {
+        Some(Value::Sequence(_)) => Some(doc),
+        _ => None,
+    }
+}
+
+/// Filter and rename proxies in a full Clash config, leaving everything else intact.
+/// Group member lists and rule targets are rewritten to follow renames;
+/// group members and rules referencing removed proxies are dropped.
+pub fn rewrite_clash_document(
+    mut doc: Value,
+    include_pattern: Option<&str>,
+    exclude_pattern: Option<&str>,
+    rename: Option<(&str, &str)>,
+) -> Result<PassthroughOutput> {
+    let include_re = compile_optional_regex(include_pattern)?;
+    let exclude_re = compile_optional_regex(exclude_pattern)?;
+    let rename_re = match rename {
+        Some((pattern, replacement)) => {
+            compile_optional_regex(Some(pattern))?.map(|re| (re, replacement))
+        }
+        None => None,
+    };
+
+    let mut warnings = Vec::new();
+    // Old name -> new name for kept proxies
+    let mut kept: IndexMap<String, String> = IndexMap::new();
+    let mut removed: HashSet<String> = HashSet::new();
+
+    let proxies = match doc.get_mut("proxies") {
+        Some(Value::Sequence(seq)) => seq,
+        _ => return Err(ConvertError::Internal("Config has no proxies list".into())),
+    };
+    let node_count = proxies.len();
+
+    proxies.retain_mut(|proxy| {
+        let Some(name) = proxy
+            .get("name")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+        else {
+            return true;
+        };
+        if !name_passes(&name, include_re.as_ref(), exclude_re.as_ref()) {
+            removed.insert(name);
+            return false;
+        }
+        let new_name = match &rename_re {
+            Some((re, replacement)) => re.replace_all(&name, *replacement).to_string(),
+            None => name.clone(),
+        };
+        if new_name != name {
+            if let Some(map) = proxy.as_mapping_mut() {
+                map.insert(Value::from("name"), Value::from(new_name.clone()));
+            }
+        }
+        kept.insert(name, new_name);
+        true
+    });
+    let filtered_count = proxies.len();
+
+    if filtered_count == 0 {
+        return Err(ConvertError::Internal(
+            "All nodes were filtered out. Check your filter patterns.".into(),
+        ));
+    }
+
+    let mut group_count = 0;
+    if let Some(Value::Sequence(groups)) = doc.get_mut("proxy-groups") {
+        group_count = groups.len();
+        for group in groups.iter_mut() {
+            let group_name = group
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            let has_provider = group.get("use").is_some();
+            let Some(Value::Sequence(members)) = group.get_mut("proxies") else {
+                continue;
+            };
+            members.retain_mut(|member| {
+                let Some(name) = member.as_str() else {
+                    return true;
+                };
+                if removed.contains(name) {
+                    return false;
+                }
+                if let Some(new_name) = kept.get(name) {
+                    *member = Value::from(new_name.clone());
+                }
+                true
+            });
+            if members.is_empty() && !has_provider {
+                members.push(Value::from("DIRECT"));
+                warnings.push(format!(
+                    "Proxy group '{}' lost all members after filtering, fell back to DIRECT",
+                    group_name
+                ));
+            }
+        }
+    }
+
+    let mut rule_count = 0;
+    if let Some(Value::Sequence(rules)) = doc.get_mut("rules") {
+        rules.retain_mut(|rule| {
+            let Some(text) = rule.as_str() else {
+                return true;
+            };
+            let mut parts: Vec<&str> = text.split(',').collect();
+            let target_idx = if parts[0].trim().eq_ignore_ascii_case("MATCH") {
+                1
+            } else {
+                2
+            };
+            let Some(target) = parts.get(target_idx).map(|t| t.trim()) else {
+                return true;
+            };
+            if removed.contains(target) {
+                warnings.push(format!("Dropped rule targeting removed proxy: {}", text));
+                return false;
+            }
+            if let Some(new_name) = kept.get(target).filter(|n| n.as_str() != target) {
+                parts[target_idx] = new_name;
+                *rule = Value::from(parts.join(","));
+            }
+            true
+        });
+        rule_count = rules.len();
+    }
+
+    let yaml =
+        serde_yaml::to_string(&doc).map_err(|e| ConvertError::YamlSerializeError(e.to_string()))?;
+
+    Ok(PassthroughOutput {
+        yaml,
+        node_count,
+        filtered_count,
+        group_count,
+        rule_count,
+        warnings,
+    })
+}
+
+/// Re-bake a pasted Clash config through the builder: proxies are parsed and
+/// deduplicated, groups, rules and HTTP rule-providers are carried over (references
+/// to dropped duplicates follow the kept proxy), everything else gets builder defaults.
+pub fn normalize_config(content: &str) -> Result<PassthroughOutput> {
+    let doc = parse_clash_document(content)
+        .ok_or_else(|| ConvertError::Internal("Config has no proxies list".into()))?;
+    let (mut nodes, mut warnings) = parse_clash_yaml(content)
+        .ok_or_else(|| ConvertError::Internal("Config has no proxies list".into()))?;
+    let node_count = nodes.len();
+
+    // Dropped duplicate name -> name of the identical proxy that was kept
+    let mut kept_by_key: HashMap<String, String> = HashMap::new();
+    let mut renamed: HashMap<String, String> = HashMap::new();
+    nodes.retain(|node| match kept_by_key.get(&node.dedup_key()) {
+        Some(kept) => {
+            warnings.push(format!(
+                "Removed duplicate proxy '{}' (same as '{}')",
+                node.name(),
+                kept
+            ));
+            if kept != node.name() {
+                renamed.insert(node.name().to_string(), kept.clone());
+            }
+            false
+        }
+        None => {
+            kept_by_key.insert(node.dedup_key(), node.name().to_string());
+            true
+        }
+    });
+    if nodes.is_empty() {
+        return Err(ConvertError::Internal("No valid proxy nodes found".into()));
+    }
+
+    let mut groups: Vec<IndexMap<String, Value>> = Vec::new();
+    if let Some(Value::Sequence(seq)) = doc.get("proxy-groups") {
+        for group in seq {
+            let Ok(mut group) = serde_yaml::from_value::<IndexMap<String, Value>>(group.clone())
+            else {
+                continue;
+            };
+            if let Some(Value::Sequence(members)) = group.get_mut("proxies") {
+                let mut seen = HashSet::new();
+                members.retain_mut(|member| {
+                    if let Some(kept) = member.as_str().and_then(|n| renamed.get(n)) {
+                        *member = Value::from(kept.clone());
+                    }
+                    seen.insert(member.clone())
+                });
+            }
+            groups.push(group);
+        }
+    }
+
+    let mut rules: Vec<String> = match doc.get("rules") {
+        Some(Value::Sequence(seq)) => seq
+            .iter()
+            .filter_map(Value::as_str)
+            .map(|rule| {
+                let mut parts: Vec<&str> = rule.split(',').collect();
+                let target_idx = if parts[0].trim().eq_ignore_ascii_case("MATCH") {
+                    1
+                } else {
+                    2
+                };
+                match parts.get(target_idx).and_then(|t| renamed.get(t.trim())) {
+                    Some(kept) => {
+                        parts[target_idx] = kept;
+                        parts.join(",")
+                    }
+                    None => rule.to_string(),
+                }
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let mut rule_providers = Vec::new();
+    let mut dropped_providers = HashSet::new();
+    if let Some(Value::Mapping(providers)) = doc.get("rule-providers") {
+        for (name, provider) in providers {
+            let name = name.as_str().unwrap_or_default().to_string();
+            match http_rule_provider(&name, provider) {
+                Some(rp) => rule_providers.push(rp),
+                None => {
+                    warnings.push(format!(
+                        "Rule provider '{}' dropped: only http providers with a url are kept",
+                        name
+                    ));
+                    dropped_providers.insert(name);
+                }
+            }
+        }
+    }
+    rules.retain(|rule| {
+        let mut parts = rule.split(',').map(str::trim);
+        let uses_dropped = parts
+            .next()
+            .is_some_and(|t| t.eq_ignore_ascii_case("RULE-SET"))
+            && parts.next().is_some_and(|p| dropped_providers.contains(p));
+        if uses_dropped {
+            warnings.push(format!("Dropped rule using a dropped provider: {}", rule));
+        }
+        !uses_dropped
+    });
+
+    let group_count = groups.len();
+    let rule_count = rules.len();
+    let mut builder = ClashConfigBuilder::new()
+        .with_nodes(&nodes)
+        .with_proxy_groups(groups)
+        .with_rules(rules)
+        .with_rule_providers(rule_providers);
+    if let Some(mode) = doc.get("mode").and_then(Value::as_str) {
+        builder = builder.with_mode(mode);
+    }
+    let duplicates = builder.duplicate_proxy_names();
+    if !duplicates.is_empty() {
+        warnings.push(format!(
+            "Different proxies share a name: {}",
+            duplicates.join(", ")
+        ));
+    }
+    let yaml = builder
+        .build_yaml()
+        .map_err(|e| ConvertError::YamlSerializeError(e.to_string()))?;
+
+    Ok(PassthroughOutput {
+        yaml,
+        node_count,
+        filtered_count: nodes.len(),
+        group_count,
+        rule_count,
+        warnings,
+    })
+}
+
+/// Read an `http` rule-provider entry into the builder's representation
+fn http_rule_provider(name: &str, provider: &Value) -> Option<RuleProvider> {
+    let field = |key: &str| {
+        provider
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    if field("type").as_deref() != Some("http") {
+        return None;
+    }
+    Some(RuleProvider {
+        name: name.to_string(),
+        url: field("url")?,
+        target: String::new(),
+        provider_type: "http".to_string(),
+        behavior: field("behavior").unwrap_or_else(|| "classical".to_string()),
+        format: field("format"),
+        path: field("path"),
+        proxy: field("proxy"),
+        header: None,
+        size_limit: provider
+            .get("size-limit")
+            .and_then(Value::as_u64)
+            .and_then(|n| u32::try_from(n).ok()),
+        interval: provider
+            .get("interval")
+            .and_then(Value::as_u64)
+            .and_then(|n| u32::try_from(n).ok())
+            .unwrap_or(86400),
+    })
+}
+
+fn name_passes(name: &str, include_re: Option<&Regex>, exclude_re: Option<&Regex>) -> bool {
+    include_re.is_none_or(|re| re.is_match(name)) && !exclude_re.is_some_and(|re| re.is_match(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+mixed-port: 7890
+dns:
+  enable: true
+proxies:
+  - { name: HK 01, type: trojan, server: 1.1.1.1, port: 443, password: pw }
+  - { name: US 01, type: trojan, server: 2.2.2.2, port: 443, password: pw }
+proxy-groups:
+  - { name: Proxy, type: select, proxies: [HK 01, US 01, DIRECT] }
+rules:
+  - DOMAIN-SUFFIX,example.com,US 01
+  - MATCH,Proxy
+";
+
+    #[test]
+    fn filtering_drops_references_to_removed_proxy() {
+        let doc = parse_clash_document(CONFIG).unwrap();
+        let output = rewrite_clash_document(doc, None, Some("US"), None).unwrap();
+        let config: Value = serde_yaml::from_str(&output.yaml).unwrap();
+
+        assert_eq!(output.node_count, 2);
+        assert_eq!(output.filtered_count, 1);
+        assert_eq!(config["proxies"].as_sequence().unwrap().len(), 1);
+        assert_eq!(
+            config["proxy-groups"][0]["proxies"],
+            serde_yaml::from_str::<Value>("[HK 01, DIRECT]").unwrap()
+        );
+        assert_eq!(config["rules"].as_sequence().unwrap().len(), 1);
+        assert_eq!(config["rules"][0], "MATCH,Proxy");
+        // Untouched sections survive as-is
+        assert_eq!(config["mixed-port"], 7890);
+        assert_eq!(config["dns"]["enable"], true);
+    }
+
+    #[test]
+    fn normalize_removes_duplicate_proxy() {
+        let messy = CONFIG.replace(
+            "proxy-groups:",
+            "  - { name: HK copy, type: trojan, server: 1.1.1.1, port: 443, password: pw }\n\
+             proxy-groups:\n  - { name: Backup, type: select, proxies: [HK copy, HK 01] }",
+        );
+        let output = normalize_config(&messy).unwrap();
+        let config: Value = serde_yaml::from_str(&output.yaml).unwrap();
+
+        let names: Vec<&str> = config["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|p| p["name"].as_str())
+            .collect();
+        assert_eq!(names, ["HK 01", "US 01"]);
+        assert_eq!(
+            output.warnings,
+            ["Removed duplicate proxy 'HK copy' (same as 'HK 01')"]
+        );
+        let backup = config["proxy-groups"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .find(|g| g["name"] == "Backup")
+            .unwrap();
+        assert_eq!(
+            backup["proxies"],
+            serde_yaml::from_str::<Value>("[HK 01]").unwrap()
+        );
+    }
+}
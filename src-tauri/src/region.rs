@@ -0,0 +1,385 @@
+//! Region detection from node names (country keywords, codes and flag emoji).
+//! `RegionTable` is the single keyword → region mapping shared by flag, group and
+//! detection features.
+
+use indexmap::IndexMap;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::sync::OnceLock;
+
+use crate::error::{ConvertError, Result};
+
+/// Region code reported when no keyword matches
+pub const UNKNOWN_REGION: &str = "unknown";
+
+/// Region keyword table: ISO 3166 code, display name, extra uppercase codes, and name
+/// keywords (matched case-insensitively). Checked in order; the first match wins.
+const REGION_KEYWORDS: &[(&str, &str, &[&str], &[&str])] = &[
+    ("HK", "香港", &["HKG"], &["香港", "Hong ?Kong"]),
+    (
+        "TW",
+        "台湾",
+        &["TWN"],
+        &[
+            "台湾", "台灣", "臺灣", "台北", "新北", "彰化", "Taiwan", "Taipei",
+        ],
+    ),
+    (
+        "JP",
+        "日本",
+        &["JPN"],
+        &[
+            "日本", "东京", "東京", "大阪", "埼玉", "Japan", "Tokyo", "Osaka",
+        ],
+    ),
+    (
+        "SG",
+        "新加坡",
+        &["SGP"],
+        &["新加坡", "狮城", "獅城", "Singapore"],
+    ),
+    (
+        "KR",
+        "韩国",
+        &["KOR"],
+        &["韩国", "韓國", "首尔", "首爾", "春川", "Korea", "Seoul"],
+    ),
+    (
+        "US",
+        "美国",
+        &["USA"],
+        &[
+            "美国",
+            "美國",
+            "洛杉矶",
+            "硅谷",
+            "西雅图",
+            "芝加哥",
+            "纽约",
+            "United States",
+            "Los Angeles",
+            "San Jose",
+            "Silicon Valley",
+            "Seattle",
+            "Chicago",
+            "New York",
+        ],
+    ),
+    (
+        "GB",
+        "英国",
+        &["UK", "GBR"],
+        &[
+            "英国",
+            "英國",
+            "伦敦",
+            "倫敦",
+            "United Kingdom",
+            "Britain",
+            "England",
+            "London",
+        ],
+    ),
+    (
+        "DE",
+        "德国",
+        &["DEU"],
+        &["德国", "德國", "法兰克福", "Germany", "Frankfurt"],
+    ),
+    (
+        "FR",
+        "法国",
+        &["FRA"],
+        &["法国", "法國", "巴黎", "France", "Paris"],
+    ),
+    (
+        "NL",
+        "荷兰",
+        &["NLD"],
+        &["荷兰", "荷蘭", "阿姆斯特丹", "Netherlands", "Amsterdam"],
+    ),
+    (
+        "RU",
+        "俄罗斯",
+        &["RUS"],
+        &["俄罗斯", "俄羅斯", "莫斯科", "Russia", "Moscow"],
+    ),
+    (
+        "CA",
+        "加拿大",
+        &["CAN"],
+        &[
+            "加拿大",
+            "多伦多",
+            "温哥华",
+            "Canada",
+            "Toronto",
+            "Vancouver",
+        ],
+    ),
+    (
+        "AU",
+        "澳大利亚",
+        &["AUS"],
+        &["澳大利亚", "澳洲", "悉尼", "Australia", "Sydney"],
+    ),
+    ("IN", "印度", &["IND"], &["印度", "孟买", "India", "Mumbai"]),
+    (
+        "TR",
+        "土耳其",
+        &["TUR"],
+        &["土耳其", "伊斯坦布尔", "Turkey", "Istanbul"],
+    ),
+    (
+        "MY",
+        "马来西亚",
+        &["MYS"],
+        &["马来西亚", "馬來西亞", "吉隆坡", "Malaysia"],
+    ),
+    (
+        "TH",
+        "泰国",
+        &["THA"],
+        &["泰国", "泰國", "曼谷", "Thailand", "Bangkok"],
+    ),
+    ("VN", "越南", &["VNM"], &["越南", "胡志明", "Vietnam"]),
+    (
+        "PH",
+        "菲律宾",
+        &["PHL"],
+        &["菲律宾", "菲律賓", "马尼拉", "Philippines"],
+    ),
+    (
+        "ID",
+        "印度尼西亚",
+        &["IDN"],
+        &["印尼", "印度尼西亚", "雅加达", "Indonesia"],
+    ),
+    ("AR", "阿根廷", &["ARG"], &["阿根廷", "Argentina"]),
+    ("BR", "巴西", &["BRA"], &["巴西", "圣保罗", "Brazil"]),
+];
+
+/// Flag emoji for a two-letter region code (regional indicator pair); empty for
+/// anything else
+pub fn region_flag(code: &str) -> String {
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return String::new();
+    }
+    code.chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .filter_map(|c| char::from_u32(0x1F1E6 + (c.to_ascii_uppercase() as u32 - 'A' as u32)))
+        .collect()
+}
+
+/// A detected region
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Region {
+    /// Region code such as `HK` or `US`
+    pub code: String,
+    /// Display name, e.g. `香港`
+    pub name: String,
+    /// Flag emoji (empty unless the code is a two-letter country code)
+    pub flag: String,
+}
+
+impl Region {
+    fn new(code: &str, name: &str) -> Self {
+        Self {
+            code: code.to_string(),
+            name: name.to_string(),
+            flag: region_flag(code),
+        }
+    }
+}
+
+/// Ordered region matchers; the first entry matching a node name wins
+#[derive(Debug, Clone)]
+pub struct RegionTable {
+    entries: Vec<(Region, Regex)>,
+}
+
+impl RegionTable {
+    /// The built-in keyword table
+    pub fn builtin() -> &'static RegionTable {
+        static TABLE: OnceLock<RegionTable> = OnceLock::new();
+        TABLE.get_or_init(|| {
+            let entries = REGION_KEYWORDS
+                .iter()
+                .map(|(code, name, extra_codes, keywords)| {
+                    // Codes must be uppercase and not glued to other letters ("HK01" matches,
+                    // "RUSH" does not); names and the flag emoji match anywhere
+                    let codes = std::iter::once(*code)
+                        .chain(extra_codes.iter().copied())
+                        .collect::<Vec<_>>()
+                        .join("|");
+                    let pattern = format!(
+                        r"(?:^|[^A-Za-z])(?:{})(?:[^A-Za-z]|$)|(?i:{})|{}",
+                        codes,
+                        keywords.join("|"),
+                        regex::escape(&region_flag(code))
+                    );
+                    let re = Regex::new(&pattern).expect("valid region keyword regex");
+                    (Region::new(code, name), re)
+                })
+                .collect();
+            RegionTable { entries }
+        })
+    }
+
+    /// The built-in table with user keywords (code → keywords) checked first, so they
+    /// can both override it and add regions or names it doesn't know. Keywords are
+    /// literal and case-insensitive; known codes keep their built-in display name.
+    pub fn with_custom(keywords: &IndexMap<String, Vec<String>>) -> Result<Self> {
+        let builtin = Self::builtin();
+        let mut entries = Vec::new();
+        for (code, words) in keywords {
+            let code = code.trim().to_ascii_uppercase();
+            let words: Vec<String> = words
+                .iter()
+                .map(|w| w.trim())
+                .filter(|w| !w.is_empty())
+                .map(regex::escape)
+                .collect();
+            if code.is_empty() || words.is_empty() {
+                continue;
+            }
+            let pattern = words.join("|");
+            let re = RegexBuilder::new(&pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| ConvertError::InvalidRegex {
+                    pattern,
+                    reason: e.to_string(),
+                })?;
+            let name = builtin
+                .region(&code)
+                .map_or(code.as_str(), |r| r.name.as_str());
+            entries.push((Region::new(&code, name), re));
+        }
+        entries.extend(builtin.entries.iter().cloned());
+        Ok(Self { entries })
+    }
+
+    /// Region of a node name, if any entry matches
+    pub fn detect(&self, name: &str) -> Option<&Region> {
+        self.entries
+            .iter()
+            .find(|(_, re)| re.is_match(name))
+            .map(|(region, _)| region)
+    }
+
+    /// Regions in match order (custom entries first, codes may repeat)
+    pub fn regions(&self) -> impl Iterator<Item = &Region> {
+        self.entries.iter().map(|(region, _)| region)
+    }
+
+    /// Region with the given code
+    pub fn region(&self, code: &str) -> Option<&Region> {
+        self.entries
+            .iter()
+            .map(|(region, _)| region)
+            .find(|region| region.code == code)
+    }
+}
+
+impl Default for RegionTable {
+    fn default() -> Self {
+        Self::builtin().clone()
+    }
+}
+
+/// Detect the region of a node from its name using the built-in table
+pub fn detect_region(name: &str) -> Option<Region> {
+    RegionTable::builtin().detect(name).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_common_regions() {
+        let table = RegionTable::default();
+        let detect = |name: &str| {
+            table
+                .detect(name)
+                .map(|r| (r.code.as_str(), r.flag.as_str()))
+        };
+        assert_eq!(detect("香港 01"), Some(("HK", "🇭🇰")));
+        assert_eq!(detect("HK-IPLC"), Some(("HK", "🇭🇰")));
+        assert_eq!(detect("🇸🇬 狮城 02"), Some(("SG", "🇸🇬")));
+        assert_eq!(detect("Singapore Premium"), Some(("SG", "🇸🇬")));
+        assert_eq!(detect("美国 洛杉矶"), Some(("US", "🇺🇸")));
+        assert_eq!(detect("US01"), Some(("US", "🇺🇸")));
+        assert_eq!(detect("RUSH hour"), None);
+        assert_eq!(detect("剩余流量"), None);
+    }
+
+    #[test]
+    fn detects_representative_provider_names() {
+        let cases = [
+            ("🇭🇰 香港 IPLC 01", Some("HK")),
+            ("hong kong 02", Some("HK")),
+            ("台湾 家宽", Some("TW")),
+            ("JP-Tokyo-03", Some("JP")),
+            ("tokyo premium", Some("JP")),
+            ("新加坡 04", Some("SG")),
+            ("韩国 首尔", Some("KR")),
+            ("UK London", Some("GB")),
+            ("Germany Frankfurt", Some("DE")),
+            ("🇺🇸 United States 05", Some("US")),
+            ("SEATTLE", Some("US")),
+            ("加拿大 温哥华", Some("CA")),
+            ("BRA-01", Some("BR")),
+            ("官网 example.com", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(
+                detect_region(name).map(|r| r.code).as_deref(),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn ambiguous_names_resolve_by_table_order() {
+        let code = |name: &str| detect_region(name).map(|r| r.code);
+        assert_eq!(detect_region("美国洛杉矶"), Some(Region::new("US", "美国")));
+        assert_eq!(code("Los Angeles 01").as_deref(), Some("US"));
+        // A relay naming two regions resolves to the earlier table entry
+        assert_eq!(code("香港-美国 中转").as_deref(), Some("HK"));
+        assert_eq!(code("US via HK").as_deref(), Some("HK"));
+        // Codes glued to other letters are not matches
+        assert_eq!(code("USERS 01"), None);
+        assert_eq!(code("JPNEW"), None);
+        assert_eq!(code("Local Test"), None);
+        assert_eq!(code(""), None);
+    }
+
+    #[test]
+    fn custom_keywords_come_before_the_builtin_table() {
+        let mut keywords = IndexMap::new();
+        keywords.insert(
+            "sg".to_string(),
+            vec!["狮城".to_string(), "Merlion".to_string()],
+        );
+        keywords.insert("HK".to_string(), vec!["Tokyo-HKT".to_string()]);
+        let table = RegionTable::with_custom(&keywords).unwrap();
+        let code = |name: &str| table.detect(name).map(|r| r.code.as_str());
+
+        assert_eq!(code("狮城 01"), Some("SG"));
+        assert_eq!(code("merlion-premium"), Some("SG"));
+        assert_eq!(RegionTable::default().detect("merlion-premium"), None);
+        // Custom entries win over built-in keywords and keep the built-in display name
+        assert_eq!(code("Tokyo-HKT 02"), Some("HK"));
+        assert_eq!(
+            table.detect("Merlion").unwrap().name,
+            RegionTable::builtin().region("SG").unwrap().name
+        );
+        assert_eq!(table.detect("Merlion").unwrap().flag, "🇸🇬");
+        // Names without a custom keyword still use the built-in table
+        assert_eq!(code("美国 洛杉矶"), Some("US"));
+    }
+}
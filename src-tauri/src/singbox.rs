@@ -0,0 +1,631 @@
+//! sing-box output
+//! Translates the built Clash config (proxies and proxy groups) into sing-box `outbounds`
+
+use indexmap::IndexSet;
+use serde_json::{json, Map, Value};
+use serde_yaml::Value as YamlValue;
+
+use crate::clash_config::ClashConfig;
+
+/// Tags of the built-in outbounds, named like the Clash policies groups refer to
+const DIRECT_TAG: &str = "DIRECT";
+const BLOCK_TAG: &str = "REJECT";
+
+/// Generated sing-box config and notes about what could not be carried over
+#[derive(Debug, Clone)]
+pub struct SingBoxOutput {
+    /// Pretty-printed sing-box JSON (`outbounds` and `route.final`)
+    pub json: String,
+    /// Skipped proxies, approximated groups and untranslated rules
+    pub warnings: Vec<String>,
+}
+
+/// Render a built Clash config as sing-box JSON. Groups become `selector` / `urltest`
+/// outbounds ahead of the proxies; `MATCH` becomes `route.final`. Other rules are not
+/// translated.
+pub fn build_singbox_config(config: &ClashConfig) -> Result<SingBoxOutput, serde_json::Error> {
+    let mut warnings = Vec::new();
+
+    let mut proxies = Vec::new();
+    for proxy in &config.proxies {
+        let name = yaml_str(proxy, "name").unwrap_or_default();
+        match proxy_outbound(proxy) {
+            Ok(outbound) => proxies.push(outbound),
+            Err(reason) => {
+                warnings.push(format!("sing-box output: skipped '{}' ({})", name, reason))
+            }
+        }
+    }
+    let known: Vec<&str> = proxies
+        .iter()
+        .filter_map(|p| p["tag"].as_str())
+        .chain(
+            config
+                .proxy_groups
+                .iter()
+                .filter_map(|g| yaml_str(g, "name")),
+        )
+        .chain([DIRECT_TAG, BLOCK_TAG])
+        .collect();
+
+    let mut outbounds: Vec<Value> = config
+        .proxy_groups
+        .iter()
+        .filter_map(|group| group_outbound(group, &known, &mut warnings))
+        .collect();
+    outbounds.extend(proxies);
+    outbounds.push(json!({ "type": "direct", "tag": DIRECT_TAG }));
+    outbounds.push(json!({ "type": "block", "tag": BLOCK_TAG }));
+
+    let mut route = Map::new();
+    let mut untranslated = 0;
+    for rule in &config.rules {
+        match rule.strip_prefix("MATCH,") {
+            Some(target) => {
+                route.insert("final".into(), policy_tag(target.trim()).into());
+            }
+            None => untranslated += 1,
+        }
+    }
+    if untranslated > 0 {
+        warnings.push(format!(
+            "sing-box output: {} rule(s) not translated, only MATCH is kept as route.final",
+            untranslated
+        ));
+    }
+
+    let mut root = Map::new();
+    root.insert("outbounds".into(), Value::Array(outbounds));
+    if !route.is_empty() {
+        root.insert("route".into(), Value::Object(route));
+    }
+    let json = serde_json::to_string_pretty(&Value::Object(root))?;
+    Ok(SingBoxOutput { json, warnings })
+}
+
+/// Selector / urltest outbound for a Clash proxy group. `url-test`, `fallback` and
+/// `load-balance` all become `urltest`, the closest sing-box has.
+fn group_outbound(group: &YamlValue, known: &[&str], warnings: &mut Vec<String>) -> Option<Value> {
+    let name = yaml_str(group, "name")?;
+    let group_type = yaml_str(group, "type").unwrap_or("select");
+
+    // Members dropped with their proxy are left out; REJECT variants may collapse
+    let mut members: Vec<String> = group
+        .get("proxies")
+        .and_then(YamlValue::as_sequence)
+        .into_iter()
+        .flatten()
+        .filter_map(YamlValue::as_str)
+        .map(policy_tag)
+        .filter(|m| known.contains(&m.as_str()))
+        .collect::<IndexSet<_>>()
+        .into_iter()
+        .collect();
+    if members.is_empty() {
+        members.push(DIRECT_TAG.to_string());
+    }
+
+    let mut outbound = Map::new();
+    if group_type == "select" {
+        outbound.insert("type".into(), "selector".into());
+        outbound.insert("tag".into(), name.into());
+        outbound.insert("outbounds".into(), members.into());
+        return Some(Value::Object(outbound));
+    }
+
+    if group_type != "url-test" {
+        warnings.push(format!(
+            "sing-box output: {} group '{}' emitted as urltest",
+            group_type, name
+        ));
+    }
+    outbound.insert("type".into(), "urltest".into());
+    outbound.insert("tag".into(), name.into());
+    outbound.insert("outbounds".into(), members.into());
+    put(&mut outbound, "url", yaml_str(group, "url").map(Into::into));
+    put(
+        &mut outbound,
+        "interval",
+        yaml_u64(group, "interval").map(|s| format!("{}s", s).into()),
+    );
+    put(
+        &mut outbound,
+        "tolerance",
+        yaml_u64(group, "tolerance").map(Into::into),
+    );
+    Some(Value::Object(outbound))
+}
+
+/// Clash policy name as a sing-box outbound tag (REJECT variants share the block outbound)
+fn policy_tag(policy: &str) -> String {
+    match policy {
+        "REJECT" | "REJECT-DROP" | "REJECT-TINYGIF" => BLOCK_TAG.to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// sing-box outbound for one Clash proxy, or why it can't be written (types sing-box
+/// lacks such as ssr and snell, or a proxy missing its server)
+fn proxy_outbound(proxy: &YamlValue) -> Result<Value, String> {
+    let missing = |field: &str| format!("missing {}", field);
+    let proxy_type = yaml_str(proxy, "type").ok_or_else(|| missing("type"))?;
+    let mut out = Map::new();
+
+    let outbound_type = match proxy_type {
+        "vless" => "vless",
+        "vmess" => "vmess",
+        "ss" => "shadowsocks",
+        "trojan" => "trojan",
+        "hysteria" => "hysteria",
+        "hysteria2" => "hysteria2",
+        "tuic" => "tuic",
+        "wireguard" => "wireguard",
+        "socks5" => "socks",
+        "http" => "http",
+        other => return Err(format!("{} is not supported by sing-box", other)),
+    };
+    out.insert("type".into(), outbound_type.into());
+    out.insert(
+        "tag".into(),
+        yaml_str(proxy, "name")
+            .ok_or_else(|| missing("name"))?
+            .into(),
+    );
+
+    // Multi-peer WireGuard carries server/port per peer instead
+    let peers = proxy
+        .get("peers")
+        .and_then(YamlValue::as_sequence)
+        .filter(|peers| proxy_type == "wireguard" && !peers.is_empty());
+    if peers.is_none() {
+        out.insert(
+            "server".into(),
+            yaml_str(proxy, "server")
+                .ok_or_else(|| missing("server"))?
+                .into(),
+        );
+        out.insert(
+            "server_port".into(),
+            yaml_u64(proxy, "port")
+                .ok_or_else(|| missing("port"))?
+                .into(),
+        );
+    }
+
+    let str_field = |key: &str| yaml_str(proxy, key).map(Value::from);
+    match proxy_type {
+        "vless" => {
+            put(&mut out, "uuid", str_field("uuid"));
+            put(
+                &mut out,
+                "flow",
+                yaml_str(proxy, "flow")
+                    .filter(|f| !f.is_empty())
+                    .map(Into::into),
+            );
+            put(&mut out, "packet_encoding", str_field("packet-encoding"));
+        }
+        "vmess" => {
+            put(&mut out, "uuid", str_field("uuid"));
+            put(&mut out, "security", str_field("cipher"));
+            put(
+                &mut out,
+                "alter_id",
+                yaml_u64(proxy, "alterId").map(Into::into),
+            );
+        }
+        "ss" => {
+            put(&mut out, "method", str_field("cipher"));
+            put(&mut out, "password", str_field("password"));
+            if let Some((plugin, opts)) = ss_plugin(proxy) {
+                out.insert("plugin".into(), plugin.into());
+                out.insert("plugin_opts".into(), opts.into());
+            }
+        }
+        "trojan" => {
+            put(&mut out, "password", str_field("password"));
+        }
+        "hysteria" => {
+            put(&mut out, "auth_str", str_field("auth-str"));
+            put(&mut out, "obfs", str_field("obfs"));
+            put(&mut out, "up_mbps", yaml_mbps(proxy, "up"));
+            put(&mut out, "down_mbps", yaml_mbps(proxy, "down"));
+        }
+        "hysteria2" => {
+            put(&mut out, "password", str_field("password"));
+            put(&mut out, "up_mbps", yaml_mbps(proxy, "up"));
+            put(&mut out, "down_mbps", yaml_mbps(proxy, "down"));
+            if let Some(obfs) = yaml_str(proxy, "obfs") {
+                let mut opts = Map::new();
+                opts.insert("type".into(), obfs.into());
+                put(&mut opts, "password", str_field("obfs-password"));
+                out.insert("obfs".into(), Value::Object(opts));
+            }
+        }
+        "tuic" => {
+            put(&mut out, "uuid", str_field("uuid"));
+            put(&mut out, "password", str_field("password"));
+            put(
+                &mut out,
+                "congestion_control",
+                str_field("congestion-controller"),
+            );
+            put(&mut out, "udp_relay_mode", str_field("udp-relay-mode"));
+            put(
+                &mut out,
+                "zero_rtt_handshake",
+                yaml_bool(proxy, "reduce-rtt").map(Into::into),
+            );
+        }
+        "wireguard" => {
+            put(&mut out, "private_key", str_field("private-key"));
+            put(&mut out, "peer_public_key", str_field("public-key"));
+            put(&mut out, "pre_shared_key", str_field("pre-shared-key"));
+            let local_address: Vec<Value> = [("ip", "32"), ("ipv6", "128")]
+                .into_iter()
+                .filter_map(|(key, prefix)| {
+                    let ip = yaml_str(proxy, key).filter(|ip| !ip.is_empty())?;
+                    Some(if ip.contains('/') {
+                        ip.into()
+                    } else {
+                        format!("{}/{}", ip, prefix).into()
+                    })
+                })
+                .collect();
+            if !local_address.is_empty() {
+                out.insert("local_address".into(), local_address.into());
+            }
+            put(&mut out, "reserved", yaml_u8_seq(proxy, "reserved"));
+            if let Some(peers) = peers {
+                let peers = peers
+                    .iter()
+                    .map(wireguard_peer)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|field| missing(&format!("peer {}", field)))?;
+                out.insert("peers".into(), peers.into());
+            }
+            put(&mut out, "mtu", yaml_u64(proxy, "mtu").map(Into::into));
+        }
+        "socks5" => {
+            out.insert("version".into(), "5".into());
+            put(&mut out, "username", str_field("username"));
+            put(&mut out, "password", str_field("password"));
+        }
+        "http" => {
+            put(&mut out, "username", str_field("username"));
+            put(&mut out, "password", str_field("password"));
+        }
+        _ => {}
+    }
+
+    if yaml_bool(proxy, "udp") == Some(false) && proxy_type != "wireguard" {
+        out.insert("network".into(), "tcp".into());
+    }
+    if let Some(tls) = tls_options(proxy, proxy_type) {
+        out.insert("tls".into(), tls);
+    }
+    if let Some(transport) = transport_options(proxy) {
+        out.insert("transport".into(), transport);
+    }
+    Ok(Value::Object(out))
+}
+
+/// sing-box WireGuard `peers` entry, or the name of the missing field
+fn wireguard_peer(peer: &YamlValue) -> Result<Value, &'static str> {
+    let mut out = Map::new();
+    out.insert(
+        "server".into(),
+        yaml_str(peer, "server").ok_or("server")?.into(),
+    );
+    out.insert(
+        "server_port".into(),
+        yaml_u64(peer, "port").ok_or("port")?.into(),
+    );
+    out.insert(
+        "public_key".into(),
+        yaml_str(peer, "public-key").ok_or("public-key")?.into(),
+    );
+    put(
+        &mut out,
+        "pre_shared_key",
+        yaml_str(peer, "pre-shared-key").map(Into::into),
+    );
+    if let Some(allowed_ips) = peer.get("allowed-ips").and_then(YamlValue::as_sequence) {
+        let allowed_ips: Vec<Value> = allowed_ips
+            .iter()
+            .filter_map(YamlValue::as_str)
+            .map(Into::into)
+            .collect();
+        out.insert("allowed_ips".into(), allowed_ips.into());
+    }
+    put(&mut out, "reserved", yaml_u8_seq(peer, "reserved"));
+    Ok(Value::Object(out))
+}
+
+/// sing-box `tls` block; always on for trojan and the QUIC protocols
+fn tls_options(proxy: &YamlValue, proxy_type: &str) -> Option<Value> {
+    let enabled = matches!(proxy_type, "trojan" | "hysteria" | "hysteria2" | "tuic")
+        || yaml_bool(proxy, "tls") == Some(true);
+    if !enabled {
+        return None;
+    }
+
+    let mut tls = Map::new();
+    tls.insert("enabled".into(), true.into());
+    put(
+        &mut tls,
+        "server_name",
+        yaml_str(proxy, "servername")
+            .or_else(|| yaml_str(proxy, "sni"))
+            .map(Into::into),
+    );
+    if yaml_bool(proxy, "skip-cert-verify") == Some(true) {
+        tls.insert("insecure".into(), true.into());
+    }
+    if let Some(alpn) = proxy.get("alpn").and_then(YamlValue::as_sequence) {
+        let alpn: Vec<Value> = alpn
+            .iter()
+            .filter_map(YamlValue::as_str)
+            .map(Into::into)
+            .collect();
+        tls.insert("alpn".into(), alpn.into());
+    }
+    if let Some(fingerprint) = yaml_str(proxy, "client-fingerprint") {
+        tls.insert(
+            "utls".into(),
+            json!({ "enabled": true, "fingerprint": fingerprint }),
+        );
+    }
+    if let Some(reality) = proxy.get("reality-opts") {
+        let mut opts = Map::new();
+        opts.insert("enabled".into(), true.into());
+        put(
+            &mut opts,
+            "public_key",
+            yaml_str(reality, "public-key").map(Into::into),
+        );
+        put(
+            &mut opts,
+            "short_id",
+            yaml_str(reality, "short-id").map(Into::into),
+        );
+        tls.insert("reality".into(), Value::Object(opts));
+    }
+    Some(Value::Object(tls))
+}
+
+/// sing-box `transport` block for ws / grpc / h2 networks
+fn transport_options(proxy: &YamlValue) -> Option<Value> {
+    let mut transport = Map::new();
+    match yaml_str(proxy, "network")? {
+        "ws" => {
+            transport.insert("type".into(), "ws".into());
+            if let Some(opts) = proxy.get("ws-opts") {
+                put(
+                    &mut transport,
+                    "path",
+                    yaml_str(opts, "path").map(Into::into),
+                );
+                if let Some(headers) = opts.get("headers").and_then(YamlValue::as_mapping) {
+                    let headers: Map<String, Value> = headers
+                        .iter()
+                        .filter_map(|(k, v)| Some((k.as_str()?.to_string(), v.as_str()?.into())))
+                        .collect();
+                    transport.insert("headers".into(), Value::Object(headers));
+                }
+            }
+        }
+        "grpc" => {
+            transport.insert("type".into(), "grpc".into());
+            if let Some(opts) = proxy.get("grpc-opts") {
+                put(
+                    &mut transport,
+                    "service_name",
+                    yaml_str(opts, "grpc-service-name").map(Into::into),
+                );
+            }
+        }
+        "h2" => {
+            transport.insert("type".into(), "http".into());
+            if let Some(opts) = proxy.get("h2-opts") {
+                put(
+                    &mut transport,
+                    "path",
+                    yaml_str(opts, "path").map(Into::into),
+                );
+                if let Some(hosts) = opts.get("host").and_then(YamlValue::as_sequence) {
+                    let hosts: Vec<Value> = hosts
+                        .iter()
+                        .filter_map(YamlValue::as_str)
+                        .map(Into::into)
+                        .collect();
+                    transport.insert("host".into(), hosts.into());
+                }
+            }
+        }
+        _ => return None,
+    }
+    Some(Value::Object(transport))
+}
+
+/// Shadowsocks `plugin` / `plugin_opts` (SIP003 option string) for obfs and v2ray-plugin
+fn ss_plugin(proxy: &YamlValue) -> Option<(&'static str, String)> {
+    let opts = proxy.get("plugin-opts");
+    let opt = |key: &str| opts.and_then(|o| yaml_str(o, key));
+    match yaml_str(proxy, "plugin")? {
+        "obfs" => {
+            let mut parts = vec![format!("obfs={}", opt("mode").unwrap_or("http"))];
+            if let Some(host) = opt("host") {
+                parts.push(format!("obfs-host={}", host));
+            }
+            Some(("obfs-local", parts.join(";")))
+        }
+        "v2ray-plugin" => {
+            let mut parts = Vec::new();
+            if opts.and_then(|o| yaml_bool(o, "tls")) == Some(true) {
+                parts.push("tls".to_string());
+            }
+            if let Some(host) = opt("host") {
+                parts.push(format!("host={}", host));
+            }
+            if let Some(path) = opt("path") {
+                parts.push(format!("path={}", path));
+            }
+            Some(("v2ray-plugin", parts.join(";")))
+        }
+        _ => None,
+    }
+}
+
+fn put(map: &mut Map<String, Value>, key: &str, value: Option<Value>) {
+    if let Some(value) = value {
+        map.insert(key.into(), value);
+    }
+}
+
+fn yaml_str<'a>(value: &'a YamlValue, key: &str) -> Option<&'a str> {
+    value.get(key)?.as_str()
+}
+
+fn yaml_u64(value: &YamlValue, key: &str) -> Option<u64> {
+    match value.get(key)? {
+        YamlValue::Number(n) => n.as_u64(),
+        YamlValue::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn yaml_u8_seq(value: &YamlValue, key: &str) -> Option<Value> {
+    let seq = value.get(key)?.as_sequence()?;
+    Some(
+        seq.iter()
+            .filter_map(YamlValue::as_u64)
+            .map(Value::from)
+            .collect::<Vec<_>>()
+            .into(),
+    )
+}
+
+fn yaml_bool(value: &YamlValue, key: &str) -> Option<bool> {
+    value.get(key)?.as_bool()
+}
+
+/// Bandwidth such as `100 Mbps` or `100` as whole Mbps
+fn yaml_mbps(value: &YamlValue, key: &str) -> Option<Value> {
+    let raw = match value.get(key)? {
+        YamlValue::Number(n) => return n.as_u64().map(Into::into),
+        YamlValue::String(s) => s,
+        _ => return None,
+    };
+    let digits: String = raw
+        .trim()
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse::<u64>().ok().map(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clash_config::ClashConfigBuilder;
+    use crate::parser::parse_single_link;
+
+    #[test]
+    fn fixture_matches_expected_json() {
+        let nodes: Vec<_> = [
+            "ss://YWVzLTEyOC1nY206cHc@1.1.1.1:8388#SS",
+            // {"v":"2","ps":"VM","add":"vm.example.com","port":"443","id":"1111...","aid":"0",
+            //  "net":"ws","path":"/ws","host":"vm.example.com","tls":"tls"}
+            "vmess://eyJ2IjoiMiIsInBzIjoiVk0iLCJhZGQiOiJ2bS5leGFtcGxlLmNvbSIsInBvcnQiOiI0NDMiLCJpZCI6IjExMTExMTExLTIyMjItMzMzMy00NDQ0LTU1NTU1NTU1NTU1NSIsImFpZCI6IjAiLCJuZXQiOiJ3cyIsInBhdGgiOiIvd3MiLCJob3N0Ijoidm0uZXhhbXBsZS5jb20iLCJ0bHMiOiJ0bHMifQ==",
+            "wireguard://10.0.0.1:51820?pk=cHJpdmF0ZQ&local_address=172.16.0.2/32\
+             &peer=a.example.com:51820%3BcGVlckE%3B0.0.0.0%2F0\
+             &peer=b.example.com:2408%3BcGVlckI%3B10.0.0.0%2F8%3B1%2C2%2C3#WG",
+            "snell://psk@3.3.3.3:443?version=3#SN",
+        ]
+        .iter()
+        .map(|link| parse_single_link(link).unwrap())
+        .collect();
+        let mut config = ClashConfigBuilder::new().with_nodes(&nodes).build();
+        config.proxy_groups = serde_yaml::from_str(
+            "- { name: Proxy, type: select, proxies: [Auto, SS, SN, DIRECT] }\n\
+             - { name: Auto, type: url-test, url: 'http://www.gstatic.com/generate_204', \
+                 interval: 300, proxies: [SS, VM, WG] }",
+        )
+        .unwrap();
+        config.rules = vec![
+            "DOMAIN-SUFFIX,example.com,DIRECT".into(),
+            "MATCH,Proxy".into(),
+        ];
+
+        let output = build_singbox_config(&config).unwrap();
+        let actual: Value = serde_json::from_str(&output.json).unwrap();
+        let expected = json!({
+            "outbounds": [
+                { "type": "selector", "tag": "Proxy", "outbounds": ["Auto", "SS", "DIRECT"] },
+                {
+                    "type": "urltest",
+                    "tag": "Auto",
+                    "outbounds": ["SS", "VM", "WG"],
+                    "url": "http://www.gstatic.com/generate_204",
+                    "interval": "300s"
+                },
+                {
+                    "type": "shadowsocks",
+                    "tag": "SS",
+                    "server": "1.1.1.1",
+                    "server_port": 8388,
+                    "method": "aes-128-gcm",
+                    "password": "pw"
+                },
+                {
+                    "type": "vmess",
+                    "tag": "VM",
+                    "server": "vm.example.com",
+                    "server_port": 443,
+                    "uuid": "11111111-2222-3333-4444-555555555555",
+                    "security": "auto",
+                    "alter_id": 0,
+                    "tls": { "enabled": true, "server_name": "vm.example.com" },
+                    "transport": {
+                        "type": "ws",
+                        "path": "/ws",
+                        "headers": { "Host": "vm.example.com" }
+                    }
+                },
+                {
+                    "type": "wireguard",
+                    "tag": "WG",
+                    "private_key": "cHJpdmF0ZQ",
+                    "local_address": ["172.16.0.2/32"],
+                    "peers": [
+                        {
+                            "server": "a.example.com",
+                            "server_port": 51820,
+                            "public_key": "cGVlckE",
+                            "allowed_ips": ["0.0.0.0/0"]
+                        },
+                        {
+                            "server": "b.example.com",
+                            "server_port": 2408,
+                            "public_key": "cGVlckI",
+                            "allowed_ips": ["10.0.0.0/8"],
+                            "reserved": [1, 2, 3]
+                        }
+                    ]
+                },
+                { "type": "direct", "tag": "DIRECT" },
+                { "type": "block", "tag": "REJECT" }
+            ],
+            "route": { "final": "Proxy" }
+        });
+        assert_eq!(actual, expected);
+        assert_eq!(
+            output.warnings,
+            [
+                "sing-box output: skipped 'SN' (snell is not supported by sing-box)",
+                "sing-box output: 1 rule(s) not translated, only MATCH is kept as route.final",
+            ]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Surge output
+//! Renders nodes as a Surge `[Proxy]` section (`Name = type, server, port, key=value, ...`)
+
+use crate::node::{Node, WsOpts};
+
+/// Surge `[Proxy]` section and the nodes left out of it
+#[derive(Debug, Clone)]
+pub struct SurgeOutput {
+    /// `[Proxy]` header followed by one line per node
+    pub text: String,
+    /// One warning per node Surge can't represent
+    pub warnings: Vec<String>,
+}
+
+/// Build the `[Proxy]` section. VLESS, SSR, Hysteria, TUIC and WireGuard nodes, and
+/// transports other than tcp/ws, are skipped with a warning.
+pub fn build_surge_proxies(nodes: &[Node]) -> SurgeOutput {
+    let mut text = String::from("[Proxy]\n");
+    let mut warnings = Vec::new();
+    for node in nodes {
+        match surge_proxy_line(node) {
+            Ok(line) => {
+                text.push_str(&line);
+                text.push('\n');
+            }
+            Err(reason) => warnings.push(format!(
+                "Surge output: skipped '{}' ({})",
+                node.name(),
+                reason
+            )),
+        }
+    }
+    SurgeOutput { text, warnings }
+}
+
+/// One `[Proxy]` line, or why the node can't be written
+fn surge_proxy_line(node: &Node) -> Result<String, String> {
+    let mut fields: Vec<String> = Vec::new();
+    let mut kv = |key: &str, value: &str| fields.push(format!("{}={}", key, surge_value(value)));
+
+    let surge_type = match node {
+        Node::Shadowsocks(n) => {
+            kv("encrypt-method", &n.cipher);
+            kv("password", &n.password);
+            match n.plugin.as_deref() {
+                None | Some("") => {}
+                Some("obfs") => {
+                    let opts = n.plugin_opts.as_ref();
+                    let opt = |key: &str| opts.and_then(|o| o.get(key)).map(String::as_str);
+                    kv("obfs", opt("mode").unwrap_or("http"));
+                    if let Some(host) = opt("host") {
+                        kv("obfs-host", host);
+                    }
+                }
+                Some(plugin) => return Err(format!("ss plugin {} is not supported", plugin)),
+            }
+            if n.udp == Some(true) {
+                kv("udp-relay", "true");
+            }
+            "ss"
+        }
+        Node::Vmess(n) => {
+            kv("username", &n.uuid);
+            write_ws(&mut kv, n.network.as_deref(), &n.ws_opts)?;
+            if n.tls == Some(true) {
+                kv("tls", "true");
+                if let Some(sni) = n.servername.as_deref().filter(|s| !s.is_empty()) {
+                    kv("sni", sni);
+                }
+                if let Some(skip) = n.skip_cert_verify {
+                    kv("skip-cert-verify", &skip.to_string());
+                }
+            }
+            if n.alterId == 0 {
+                kv("vmess-aead", "true");
+            }
+            "vmess"
+        }
+        Node::Trojan(n) => {
+            kv("password", &n.password);
+            write_ws(&mut kv, n.network.as_deref(), &n.ws_opts)?;
+            if let Some(sni) = n.sni.as_deref().filter(|s| !s.is_empty()) {
+                kv("sni", sni);
+            }
+            if let Some(skip) = n.skip_cert_verify {
+                kv("skip-cert-verify", &skip.to_string());
+            }
+            "trojan"
+        }
+        Node::Hysteria2(n) => {
+            if n.obfs.is_some() {
+                return Err("Surge has no Hysteria2 obfs".into());
+            }
+            kv("password", &n.password);
+            if let Some(sni) = n.sni.as_deref().filter(|s| !s.is_empty()) {
+                kv("sni", sni);
+            }
+            if let Some(skip) = n.skip_cert_verify {
+                kv("skip-cert-verify", &skip.to_string());
+            }
+            let mbps = n
+                .down
+                .as_deref()
+                .map(|d| {
+                    d.chars()
+                        .take_while(char::is_ascii_digit)
+                        .collect::<String>()
+                })
+                .filter(|d| !d.is_empty());
+            if let Some(mbps) = mbps {
+                kv("download-bandwidth", &mbps);
+            }
+            "hysteria2"
+        }
+        Node::Snell(n) => {
+            kv("psk", &n.psk);
+            kv("version", &n.version.to_string());
+            if let Some(obfs) = &n.obfs_opts {
+                kv("obfs", &obfs.mode);
+                if let Some(host) = obfs.host.as_deref() {
+                    kv("obfs-host", host);
+                }
+            }
+            "snell"
+        }
+        Node::Socks5(n) if n.tls == Some(true) => {
+            if let Some(skip) = n.skip_cert_verify {
+                kv("skip-cert-verify", &skip.to_string());
+            }
+            "socks5-tls"
+        }
+        Node::Socks5(_) => "socks5",
+        Node::Http(n) if n.tls == Some(true) => {
+            if let Some(sni) = n.sni.as_deref().filter(|s| !s.is_empty()) {
+                kv("sni", sni);
+            }
+            if let Some(skip) = n.skip_cert_verify {
+                kv("skip-cert-verify", &skip.to_string());
+            }
+            "https"
+        }
+        Node::Http(_) => "http",
+        Node::Vless(_) | Node::Ssr(_) | Node::Hysteria(_) | Node::Tuic(_) | Node::WireGuard(_) => {
+            return Err(format!(
+                "{} is not supported by Surge",
+                node.protocol_type()
+            ));
+        }
+    };
+
+    // SOCKS5/HTTP credentials are positional, right after the port
+    let credentials = match node {
+        Node::Socks5(n) => Some((&n.username, &n.password)),
+        Node::Http(n) => Some((&n.username, &n.password)),
+        _ => None,
+    };
+    let mut line = format!(
+        "{} = {}, {}, {}",
+        node.name(),
+        surge_type,
+        node.server(),
+        node.port()
+    );
+    if let Some((Some(username), password)) = credentials {
+        line.push_str(&format!(
+            ", {}, {}",
+            surge_value(username),
+            surge_value(password.as_deref().unwrap_or_default())
+        ));
+    }
+    for field in fields {
+        line.push_str(", ");
+        line.push_str(&field);
+    }
+    Ok(line)
+}
+
+/// `ws=true, ws-path=..., ws-headers=Host:...` for ws nodes; tcp needs nothing and
+/// other transports (grpc, h2) can't be written
+fn write_ws(
+    kv: &mut impl FnMut(&str, &str),
+    network: Option<&str>,
+    ws_opts: &Option<WsOpts>,
+) -> Result<(), String> {
+    match network.unwrap_or("tcp") {
+        "tcp" | "" => Ok(()),
+        "ws" => {
+            kv("ws", "true");
+            if let Some(ws) = ws_opts {
+                if let Some(path) = ws.path.as_deref().filter(|p| !p.is_empty()) {
+                    kv("ws-path", path);
+                }
+                if let Some(headers) = ws.headers.as_ref().filter(|h| !h.is_empty()) {
+                    let headers: Vec<String> = headers
+                        .iter()
+                        .map(|(k, v)| format!("{}:{}", k, v))
+                        .collect();
+                    kv("ws-headers", &headers.join("|"));
+                }
+            }
+            Ok(())
+        }
+        other => Err(format!("Surge has no {} transport", other)),
+    }
+}
+
+/// Quote values that would otherwise split the comma-separated line
+fn surge_value(value: &str) -> String {
+    if value.contains(',') || value.contains('"') {
+        format!("\"{}\"", value.replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_single_link, parse_surge};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    const UUID: &str = "11111111-2222-3333-4444-555555555555";
+
+    #[test]
+    fn formats_ss_vmess_ws_and_trojan_lines() {
+        let vmess = format!(
+            r#"{{"v":"2","ps":"VMess","add":"v.example.com","port":"443","id":"{}","aid":"0","net":"ws","path":"/ws","host":"cdn.example.com","tls":"tls","sni":"v.example.com"}}"#,
+            UUID
+        );
+        let links = [
+            "ss://YWVzLTEyOC1nY206c2VjcmV0@1.2.3.4:8388#SS".to_string(),
+            format!("vmess://{}", STANDARD.encode(vmess)),
+            "trojan://pw@t.example.com:443?sni=sni.example.com#Trojan".to_string(),
+            format!("tuic://{}:pw@1.1.1.1:443#TUIC", UUID),
+        ];
+        let nodes: Vec<Node> = links
+            .iter()
+            .map(|l| parse_single_link(l).unwrap())
+            .collect();
+        let output = build_surge_proxies(&nodes);
+
+        let lines: Vec<&str> = output.text.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "[Proxy]",
+                "SS = ss, 1.2.3.4, 8388, encrypt-method=aes-128-gcm, password=secret, udp-relay=true",
+                &format!(
+                    "VMess = vmess, v.example.com, 443, username={}, ws=true, ws-path=/ws, \
+                     ws-headers=Host:cdn.example.com, tls=true, sni=v.example.com, vmess-aead=true",
+                    UUID
+                ),
+                "Trojan = trojan, t.example.com, 443, password=pw, sni=sni.example.com",
+            ]
+        );
+        assert_eq!(output.warnings.len(), 1);
+        assert!(output.warnings[0].starts_with("Surge output: skipped 'TUIC'"));
+
+        // The emitted section parses back to the same nodes
+        let (reparsed, warnings) = parse_surge(&output.text).unwrap();
+        assert!(warnings.is_empty());
+        let names: Vec<&str> = reparsed.iter().map(|n| n.name()).collect();
+        assert_eq!(names, ["SS", "VMess", "Trojan"]);
+    }
+}
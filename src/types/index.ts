@@ -1,27 +1,107 @@
 // Types for LocalSub frontend
 
+export interface SubscriptionSource {
+  url: string;
+  enabled?: boolean;
+  name?: string;
+  include?: string;
+  exclude?: string;
+}
+
+export interface SubscriptionManifest {
+  sources: SubscriptionSource[];
+  settings?: Partial<ConvertRequest>;
+}
+
 export interface ConvertRequest {
   subscription: string;
+  subscription_sources?: SubscriptionSource[];
   ini_url?: string;
   ini_content?: string;
+  infer_tls_on_443?: boolean;
+  merge_order?: 'sequential' | 'round_robin';
+  dedup_ignore_transport?: boolean;
+  disabled_protocols?: string[];
+  server_allowlist?: string[];
+  source_comments?: boolean;
   include_regex?: string;
   exclude_regex?: string;
   rename_pattern?: string;
   rename_replacement?: string;
+  rename_map?: Record<string, string>;
+  max_name_len?: number;
+  dedup_by_name?: boolean;
+  max_nodes?: number;
   timeout_secs?: number;
   enable_tun?: boolean;
+  tun_only?: boolean;
+  drop_info_nodes?: boolean;
+  info_node_patterns?: string[];
+  output_format?: 'full' | 'clash' | 'proxies_only' | 'groups_rules_only' | 'csv' | 'split' | 'singbox' | 'surge';
+  compact?: boolean;
+  skip_validation?: boolean;
+  strict_validate?: boolean;
+  subscription_info_groups?: boolean;
+  base_config?: string;
+  mode?: "rule" | "global" | "direct";
+  keep_original_structure?: boolean;
   custom_user_agent?: string;
+  fetch_retries?: number;
+  max_response_mb?: number;
   enable_udp?: boolean;
   enable_tfo?: boolean;
   skip_cert_verify?: boolean;
+  global_smux?: SmuxOpts;
+  allow_lan?: boolean;
+  bind_address?: string;
+  lan_allowed_ips?: string[];
+  lan_disallowed_ips?: string[];
+  unified_delay?: boolean;
+  tcp_concurrent?: boolean;
+  find_process_mode?: "off" | "strict" | "always";
+  geodata_mode?: boolean;
+  geo_auto_update?: boolean;
+  geo_update_interval?: number;
+  dns_respect_rules?: boolean;
+  enable_ipv6?: boolean;
+  dns_use_hosts?: boolean;
+  dns_use_system_hosts?: boolean;
+  explicit_cert_verify?: boolean;
+  sni_override?: string;
+  sni_override_regex?: string;
   vless_reality_short_id_override?: string;
   api_listen_lan?: boolean;
   api_secret?: string;
   rule_provider_proxy?: string;
+  rule_provider_proxy_by_behavior?: Record<string, string>;
   rule_provider_header?: string;
   rule_provider_size_limit?: number;
   rule_provider_path_omit?: boolean;
   rule_provider_path_template?: string;
+  latency_map?: Record<string, number>;
+  default_group_timeout?: number;
+  default_group_lazy?: boolean;
+  default_expected_status?: string;
+  auto_interval?: number;
+  auto_tolerance?: number;
+  group_mode?: 'default' | 'region';
+  region_keywords?: Record<string, string[]>;
+  number_groups?: boolean;
+  append_direct_reject?: boolean;
+  dedup_rules?: boolean;
+  max_rules?: number;
+  rule_target_rewrite?: Record<string, string>;
+  skip_cn_direct?: boolean;
+  skip_private_direct?: boolean;
+  skip_match?: boolean;
+}
+
+export interface SmuxOpts {
+  protocol?: 'smux' | 'yamux' | 'h2mux';
+  max_connections?: number;
+  min_streams?: number;
+  max_streams?: number;
+  padding?: boolean;
 }
 
 export interface SubscriptionInfo {
@@ -31,6 +111,11 @@ export interface SubscriptionInfo {
   expire?: number;
 }
 
+export interface SubscriptionHeaderInfo {
+  subscription_info?: SubscriptionInfo | null;
+  file_name?: string | null;
+}
+
 export interface ConvertResult {
   yaml: string;
   node_count: number;
@@ -39,12 +124,24 @@ export interface ConvertResult {
   rule_count: number;
   warnings: string[];
   subscription_info?: SubscriptionInfo;
+  providers_file?: string;
+}
+
+export interface ClashConfigJson extends ConvertResult {
+  config: {
+    proxies?: Record<string, unknown>[];
+    'proxy-groups'?: Record<string, unknown>[];
+    rules?: string[];
+    'rule-providers'?: Record<string, Record<string, unknown>>;
+    [key: string]: unknown;
+  };
 }
 
 export interface PresetConfig {
   name: string;
   url: string;
   description: string;
+  mirrors: string[];
 }
 
 export interface NodePreviewItem {
@@ -52,6 +149,31 @@ export interface NodePreviewItem {
   protocol: string;
   server: string;
   port: number;
+  region?: string;
+  meta?: Record<string, string>;
+  tags?: string[];
+}
+
+export interface NodeRegion {
+  name: string;
+  region: string;
+  flag: string;
+}
+
+export interface NodeLatency {
+  name: string;
+  latency_ms?: number | null;
+}
+
+export interface IniGroupPreview {
+  name: string;
+  group_type: string;
+}
+
+export interface IniPreview {
+  groups: IniGroupPreview[];
+  rule_count: number;
+  ruleset_count: number;
 }
 
 export interface ParseNodesResult {
@@ -59,9 +181,21 @@ export interface ParseNodesResult {
   subscription_info?: SubscriptionInfo;
 }
 
+export interface LinkExplanation {
+  valid: boolean;
+  scheme: string | null;
+  supported: boolean;
+  name: string | null;
+  field: string | null;
+  error: string | null;
+  suggestion: string | null;
+  warnings: string[];
+}
+
 export interface AppState {
   // Input
   subscription: string;
+  subscription_sources?: SubscriptionSource[];
   selectedPreset: string | null;
   customIniUrl: string;
 